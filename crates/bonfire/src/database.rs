@@ -1,7 +1,8 @@
 use once_cell::sync::OnceCell;
-use revolt_database::{Database, DatabaseInfo};
+use revolt_database::{Database, DatabaseInfo, AMQP};
 
 static DBCONN: OnceCell<Database> = OnceCell::new();
+static AMQPCONN: OnceCell<AMQP> = OnceCell::new();
 
 /// Connect Bonfire to the database.
 pub async fn connect() {
@@ -13,9 +14,20 @@ pub async fn connect() {
     if DBCONN.set(database).is_err() {
         panic!("couldn't set database")
     }
+
+    // Only typing events are published from Bonfire
+    let config = revolt_config::config().await;
+    if config.pushd.typing_events && AMQPCONN.set(AMQP::connect(&config).await).is_err() {
+        panic!("couldn't set AMQP")
+    }
 }
 
 /// Get a reference to the current database.
 pub fn get_db() -> &'static Database {
     DBCONN.get().expect("Valid `Database`")
 }
+
+/// Get a reference to the notification publisher, if typing events are enabled.
+pub fn get_amqp() -> Option<&'static AMQP> {
+    AMQPCONN.get()
+}
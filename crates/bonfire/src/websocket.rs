@@ -18,6 +18,7 @@ use revolt_config::report_internal_error;
 use revolt_database::{
     events::{client::EventV1, server::ClientMessage},
     iso8601_timestamp::Timestamp,
+    util::activity::{clear_session_activity, touch_session},
     Database, User, UserHint,
 };
 use revolt_presence::{create_session, delete_session};
@@ -31,6 +32,7 @@ use revolt_result::create_error;
 use sentry::Level;
 
 use crate::config::{ProtocolConfiguration, WebsocketHandshakeCallback};
+use crate::database;
 use crate::events::state::{State, SubscriptionStateChange};
 
 type WsReader = SplitStream<WebSocketStream<TcpStream>>;
@@ -105,6 +107,8 @@ pub async fn client(db: &'static Database, stream: TcpStream, addr: SocketAddr)
         .await
         .ok();
 
+    touch_session(&user.id, &session_id).await.ok();
+
     // Create local state.
     let mut state = State::from(user, session_id);
     let user_id = state.cache.user_id.clone();
@@ -385,6 +389,17 @@ async fn listener(
     report_internal_error!(subscriber.quit().await).ok();
 }
 
+/// Publish a typing indicator for other services, if enabled
+///
+/// The user is known to be able to view the channel, as they are subscribed to it.
+async fn publish_typing(channel: String, user_id: String, is_typing: bool) {
+    if let Some(amqp) = database::get_amqp() {
+        if let Err(err) = amqp.typing(channel, user_id, is_typing).await {
+            warn!("Failed to publish typing event: {err:?}");
+        }
+    }
+}
+
 #[allow(clippy::too_many_arguments)]
 async fn worker_with_kill_signal(
     addr: SocketAddr,
@@ -471,6 +486,8 @@ async fn worker(
                         }
                         .p(channel.clone())
                         .await;
+
+                        publish_typing(channel, user_id.clone(), true).await;
                     }
                     ClientMessage::EndTyping { channel } => {
                         if !subscribed.read().await.contains(&channel) {
@@ -483,6 +500,8 @@ async fn worker(
                         }
                         .p(channel.clone())
                         .await;
+
+                        publish_typing(channel, user_id.clone(), false).await;
                     }
                     ClientMessage::Subscribe { server_id } => {
                         let mut servers = active_servers.lock().await;
@@ -513,20 +532,12 @@ async fn worker(
 
 /// Clear all open channels for a user session when WebSocket disconnects
 async fn clear_session_open_channels(user_id: &str, session_id: &str) {
-    use redis_kiss::{get_connection, AsyncCommands};
-
-    if let Ok(mut conn) = get_connection().await {
-        let session_key = format!("open_channels:{}:{}", user_id, session_id);
-
-        if let Err(err) = conn.del::<&str, ()>(&session_key).await {
-            error!("Failed to clear session channels: {:?}", err);
-        } else {
-            info!(
-                "Cleared open channels for user {} session {}",
-                user_id, session_id
-            );
-        }
+    if let Err(err) = clear_session_activity(user_id, session_id).await {
+        error!("Failed to clear session channels: {:?}", err);
     } else {
-        error!("Failed to get Redis connection for clearing session channels");
+        info!(
+            "Cleared open channels for user {} session {}",
+            user_id, session_id
+        );
     }
 }
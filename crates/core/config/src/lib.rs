@@ -116,6 +116,7 @@ pub struct Rabbit {
     pub port: u16,
     pub username: String,
     pub password: String,
+    pub publish_channels: usize,
 }
 
 #[derive(Deserialize, Debug, Clone)]
@@ -201,6 +202,12 @@ pub struct ApiWorkers {
 #[derive(Deserialize, Debug, Clone)]
 pub struct ApiUsers {
     pub early_adopter_cutoff: Option<u64>,
+    pub activity_refreshes_presence: bool,
+    pub untracked_activity_channel_types: Vec<String>,
+    pub max_open_channels: usize,
+    pub evict_open_channels: bool,
+    pub min_activity_ttl: u64,
+    pub max_activity_ttl: u64,
 }
 
 #[derive(Deserialize, Debug, Clone)]
@@ -215,16 +222,46 @@ pub struct Api {
 #[derive(Deserialize, Debug, Clone)]
 pub struct Pushd {
     pub production: bool,
+    pub disabled: bool,
     pub exchange: String,
     pub mass_mention_chunk_size: usize,
+    pub mass_mention_batch_size: usize,
+    pub max_body_length: usize,
+    pub min_body_length: usize,
+    pub suppress_attachment_only: bool,
+    pub ack_grace_period: u64,
+    pub filter_dm_viewers: bool,
+    pub broadcast_channels: Vec<String>,
+    pub broadcast_channels_override_mute: bool,
+    pub ack_dedup_per_message: bool,
+    pub publish_retries: usize,
+    pub transient_notifications: Vec<String>,
+    pub max_in_flight_publishes: usize,
+    pub breaker_threshold: usize,
+    pub breaker_cooldown: u64,
+    pub transport: String,
+    pub http_endpoint: String,
+    pub http_timeout: u64,
+    pub outbox: bool,
+    pub outbox_retry_after: u64,
+    pub outbox_max_age: u64,
+    pub ack_batch_interval: u64,
+    pub typing_events: bool,
+    pub typing_throttle: u64,
+    pub member_join_permission: String,
+    pub excluded_system_messages: Vec<String>,
+    pub stale_subscription_days: u64,
 
     // Queues
     pub message_queue: String,
+    pub pinned_queue: String,
+    pub member_joined_queue: String,
     pub mass_mention_queue: String,
     pub fr_accepted_queue: String,
     pub fr_received_queue: String,
     pub generic_queue: String,
     pub ack_queue: String,
+    pub typing_queue: String,
 
     pub vapid: PushVapid,
     pub fcm: PushFcm,
@@ -247,6 +284,14 @@ impl Pushd {
         self.get_routing_key(self.message_queue.clone())
     }
 
+    pub fn get_pinned_routing_key(&self) -> String {
+        self.get_routing_key(self.pinned_queue.clone())
+    }
+
+    pub fn get_member_joined_routing_key(&self) -> String {
+        self.get_routing_key(self.member_joined_queue.clone())
+    }
+
     pub fn get_mass_mention_routing_key(&self) -> String {
         self.get_routing_key(self.mass_mention_queue.clone())
     }
@@ -289,6 +334,8 @@ pub struct Files {
     pub blocked_mime_types: Vec<String>,
     pub clamd_host: String,
     pub scan_mime_types: Vec<String>,
+    pub signed_url_key: String,
+    pub signed_url_ttl: u64,
 
     pub limit: FilesLimit,
     pub preview: HashMap<String, [usize; 2]>,
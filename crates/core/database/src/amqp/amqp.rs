@@ -1,29 +1,91 @@
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
+use super::breaker::{BreakerState, CircuitBreaker};
+use super::notifier::{HttpNotifier, Notifier, RabbitNotifier};
+use super::outbox::{self, OutboxEntry};
+use super::{format, metrics};
 use crate::events::rabbit::*;
-use crate::User;
-use amqprs::channel::BasicPublishArguments;
-use amqprs::{channel::Channel, connection::Connection, error::Error as AMQPError};
+use crate::util::activity::{fetch_session_activity, open_channels_key, unfocused_session_key};
+use crate::util::bulk_permissions::BulkDatabasePermissionQuery;
+use crate::util::delivery::fetch_delivered;
+use crate::util::retry::redis_connection;
+use crate::{
+    Channel as DbChannel, ChannelNotificationSettings, Database, RelationshipStatus, Server, User,
+};
+use amqprs::{
+    channel::{Channel, ExchangeDeclareArguments},
+    connection::{Connection, OpenConnectionArguments},
+    error::Error as AMQPError,
+};
 use amqprs::{BasicProperties, FieldTable};
-use revolt_models::v0::PushNotification;
+use async_lock::Semaphore;
+use async_std::task::{sleep, spawn};
+use futures::try_join;
+use iso8601_timestamp::Timestamp;
+use revolt_config::Settings;
+use revolt_models::v0::{self, PushNotification};
 
 use log::{debug, info, warn};
 use serde_json::to_string;
 
+/// Split users into those who muted the channel and those always notified of it
+fn notification_overrides(
+    settings: Vec<ChannelNotificationSettings>,
+) -> (HashSet<String>, HashSet<String>) {
+    let mut muted = HashSet::new();
+    let mut always = HashSet::new();
+    for settings in settings {
+        if settings.is_muted() {
+            muted.insert(settings.id.user);
+        } else if settings.always_notifies() {
+            always.insert(settings.id.user);
+        }
+    }
+
+    (muted, always)
+}
+
+/// Find users who have currently muted the channel, and those who always want its notifications
+async fn filter_muted(
+    db: &Database,
+    recipients: &[String],
+    channel_id: &str,
+) -> (HashSet<String>, HashSet<String>) {
+    match db
+        .fetch_channel_notification_settings(channel_id, recipients)
+        .await
+    {
+        Ok(settings) => notification_overrides(settings),
+        Err(err) => {
+            warn!("Failed to fetch channel notification settings: {err:?}");
+            (HashSet::new(), HashSet::new())
+        }
+    }
+}
+
 /// Filter out users who are currently viewing the channel
 async fn filter_viewers(recipients: &[String], channel_id: &str) -> HashSet<String> {
-    use redis_kiss::{get_connection, AsyncCommands};
+    find_viewers(redis_connection().await.ok(), recipients, channel_id).await
+}
 
+/// Find recipients with the channel open in any focused session, using the given Redis connection
+async fn find_viewers<C: redis_kiss::AsyncCommands>(
+    conn: Option<C>,
+    recipients: &[String],
+    channel_id: &str,
+) -> HashSet<String> {
     let mut viewer_ids = HashSet::new();
 
-    // Get Redis connection
-    let Ok(mut conn) = get_connection().await else {
+    let Some(mut conn) = conn else {
         warn!("Failed to get Redis connection for filtering viewers");
         return viewer_ids;
     };
 
     for user_id in recipients {
-        let session_pattern = format!("open_channels:{}:*", user_id);
+        let session_pattern = open_channels_key(user_id, "*");
 
         // Get all session keys for this user
         let Ok(keys): Result<Vec<String>, _> = conn.keys(&session_pattern).await else {
@@ -31,14 +93,24 @@ async fn filter_viewers(recipients: &[String], channel_id: &str) -> HashSet<Stri
             continue;
         };
 
-        // Check if any session has this channel open
+        // Check if any focused session has this channel open
         for key in keys {
             let Ok(members): Result<HashSet<String>, _> = conn.smembers(&key).await else {
                 debug!("Failed to get members for key {}", key);
                 continue;
             };
 
-            if members.contains(channel_id) {
+            if !members.contains(channel_id) {
+                continue;
+            }
+
+            let session_id = key.rsplit(':').next().unwrap_or_default();
+            let unfocused: bool = conn
+                .exists(unfocused_session_key(user_id, session_id))
+                .await
+                .unwrap_or_default();
+
+            if !unfocused {
                 debug!(
                     "User {} is currently viewing channel {}",
                     user_id, channel_id
@@ -54,259 +126,2589 @@ async fn filter_viewers(recipients: &[String], channel_id: &str) -> HashSet<Stri
     viewer_ids
 }
 
-#[derive(Clone)]
-pub struct AMQP {
-    #[allow(unused)]
-    connection: Connection,
-    channel: Channel,
+/// Whether a message's system message is of a type which shouldn't notify anyone
+fn is_excluded_system_message(system: Option<&v0::SystemMessage>, excluded: &[String]) -> bool {
+    system.is_some_and(|system| {
+        excluded
+            .iter()
+            .any(|type_name| type_name == system.type_name())
+    })
 }
 
-impl AMQP {
-    pub fn new(connection: Connection, channel: Channel) -> AMQP {
-        AMQP {
-            connection,
-            channel,
+/// Whether the user has snoozed all of their notifications
+fn should_suppress_all(user: &User) -> bool {
+    user.notifications_snoozed_until
+        .is_some_and(|until| until > Timestamp::now_utc())
+}
+
+/// Whether the user has blocked the author of a message
+fn has_blocked(user: &User, author_id: &str) -> bool {
+    user.relationship_with(author_id) == RelationshipStatus::Blocked
+}
+
+/// Drop recipients for whom the given check gives a reason, returning who was dropped and why
+async fn filter_users<F>(
+    db: &Database,
+    recipients: Vec<String>,
+    suppress: F,
+) -> (Vec<String>, HashMap<String, SuppressionReason>)
+where
+    F: Fn(&User) -> Option<SuppressionReason>,
+{
+    let suppressed: HashMap<String, SuppressionReason> = match db.fetch_users(&recipients).await {
+        Ok(users) => users
+            .into_iter()
+            .filter_map(|user| suppress(&user).map(|reason| (user.id, reason)))
+            .collect(),
+        Err(err) => {
+            warn!("Failed to fetch users to filter recipients: {err:?}");
+            return (recipients, HashMap::new());
+        }
+    };
+
+    let recipients = recipients
+        .into_iter()
+        .filter(|id| !suppressed.contains_key(id))
+        .collect();
+
+    (recipients, suppressed)
+}
+
+/// Keep only recipients who can still see the channel and read its history
+///
+/// Access may have been revoked since the message was sent, so the channel is
+/// fetched again rather than trusting the payload. Nobody is kept if access can't
+/// be checked, to avoid leaking message content.
+async fn filter_access(db: &Database, recipients: Vec<String>, channel_id: &str) -> Vec<String> {
+    let channel = match db.fetch_channel(channel_id).await {
+        Ok(channel) => channel,
+        Err(err) => {
+            warn!("Failed to fetch channel to check recipient access: {err:?}");
+            return vec![];
+        }
+    };
+
+    match &channel {
+        DbChannel::DirectMessage {
+            recipients: members,
+            ..
+        }
+        | DbChannel::Group {
+            recipients: members,
+            ..
+        } => recipients
+            .into_iter()
+            .filter(|id| members.contains(id))
+            .collect(),
+        DbChannel::SavedMessages { user, .. } => {
+            recipients.into_iter().filter(|id| id == user).collect()
+        }
+        DbChannel::TextChannel { server, .. } | DbChannel::VoiceChannel { server, .. } => {
+            let (server, members) = match try_join!(
+                db.fetch_server(server),
+                db.fetch_members(server, &recipients)
+            ) {
+                Ok(result) => result,
+                Err(err) => {
+                    warn!("Failed to fetch members to check recipient access: {err:?}");
+                    return vec![];
+                }
+            };
+
+            let can_read = BulkDatabasePermissionQuery::new(db, server)
+                .channel(&channel)
+                .members(&members)
+                .members_can_read_channel()
+                .await;
+
+            recipients
+                .into_iter()
+                .filter(|id| can_read.get(id).copied().unwrap_or_default())
+                .collect()
         }
     }
+}
 
-    pub async fn friend_request_accepted(
-        &self,
-        accepted_request_user: &User,
-        sent_request_user: &User,
-    ) -> Result<(), AMQPError> {
-        let config = revolt_config::config().await;
-        let payload = FRAcceptedPayload {
-            accepted_user: accepted_request_user.to_owned(),
-            user: sent_request_user.id.clone(),
-        };
-        let payload = to_string(&payload).unwrap();
+/// Pick which of a user's subscribed sessions was active most recently
+///
+/// Returns `None` if none of them have any recorded activity, in which case every session is notified.
+fn pick_primary_session(session_ids: &[String], activity: &HashMap<String, u64>) -> Option<String> {
+    session_ids
+        .iter()
+        .filter_map(|id| activity.get(id).map(|last_active| (id, *last_active)))
+        .max_by_key(|(_, last_active)| *last_active)
+        .map(|(id, _)| id.clone())
+}
+
+/// Find the only session to notify for recipients who want notifications on their primary session only
+async fn find_primary_sessions(db: &Database, recipients: &[String]) -> HashMap<String, String> {
+    let user_ids: Vec<String> = match db.fetch_users(recipients).await {
+        Ok(users) => users
+            .into_iter()
+            .filter(|user| user.push_primary_session_only)
+            .map(|user| user.id)
+            .collect(),
+        Err(err) => {
+            warn!("Failed to fetch users to find primary sessions: {err:?}");
+            return HashMap::new();
+        }
+    };
+
+    if user_ids.is_empty() {
+        return HashMap::new();
+    }
+
+    let sessions = match db.fetch_push_subscribed_sessions(&user_ids).await {
+        Ok(sessions) => sessions,
+        Err(err) => {
+            warn!("Failed to fetch push sessions to find primary sessions: {err:?}");
+            return HashMap::new();
+        }
+    };
+
+    let mut primary_sessions = HashMap::new();
+    for user_id in user_ids {
+        let session_ids: Vec<String> = sessions
+            .iter()
+            .filter(|session| session.user_id == user_id)
+            .map(|session| session.id.clone())
+            .collect();
+
+        if session_ids.len() < 2 {
+            continue;
+        }
+
+        match fetch_session_activity(&user_id).await {
+            Ok(activity) => {
+                if let Some(session_id) = pick_primary_session(&session_ids, &activity) {
+                    primary_sessions.insert(user_id, session_id);
+                }
+            }
+            Err(err) => warn!("Failed to fetch session activity for {user_id}: {err:?}"),
+        }
+    }
+
+    primary_sessions
+}
+
+/// How long a user is remembered as typing without starting again
+const TYPING_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Whether a typing event should be published, given when the user was last published as typing
+///
+/// Starts are throttled, and stops are only published for users known to be typing.
+fn should_publish_typing(
+    last_started: Option<Instant>,
+    is_typing: bool,
+    throttle: Duration,
+    now: Instant,
+) -> bool {
+    match (is_typing, last_started) {
+        (true, Some(started)) => now.duration_since(started) >= throttle,
+        (true, None) => true,
+        (false, last_started) => last_started.is_some(),
+    }
+}
+
+/// Drop recipients who have snoozed all of their notifications
+async fn filter_snoozed(db: &Database, recipients: Vec<String>) -> Vec<String> {
+    filter_users(db, recipients, |user| {
+        should_suppress_all(user).then_some(SuppressionReason::Snoozed)
+    })
+    .await
+    .0
+}
+
+/// How a channel listed in `pushd.broadcast_channels` bypasses the usual filters
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct BroadcastRules {
+    /// Notify users who are currently viewing the channel
+    notify_viewers: bool,
+    /// Notify users who muted the channel
+    notify_muted: bool,
+}
+
+/// Find how a channel bypasses the usual filters, if it is a broadcast channel
+fn broadcast_rules(config: &Settings, channel_id: &str) -> BroadcastRules {
+    let broadcast = config
+        .pushd
+        .broadcast_channels
+        .iter()
+        .any(|id| id == channel_id);
 
+    BroadcastRules {
+        notify_viewers: broadcast,
+        notify_muted: broadcast && config.pushd.broadcast_channels_override_mute,
+    }
+}
+
+/// Drop recipients who muted the channel or, unless forced, are currently viewing it
+///
+/// Recipients who asked to always be notified of the channel are kept while viewing it.
+/// Muted recipients are kept only if `ignore_mutes` is set.
+/// Also returns which of the recipients were dropped and why.
+async fn filter_recipients(
+    db: &Database,
+    recipients: Vec<String>,
+    channel_id: &str,
+    force: bool,
+    ignore_mutes: bool,
+) -> (Vec<String>, HashMap<String, SuppressionReason>) {
+    // Filter out users who have muted the channel
+    let (mut muted_ids, always_ids) = filter_muted(db, &recipients, channel_id).await;
+    if ignore_mutes {
+        muted_ids.clear();
+    }
+    let recipients = (&recipients.into_iter().collect::<HashSet<String>>() - &muted_ids)
+        .into_iter()
+        .collect::<Vec<String>>();
+
+    let mut suppressed: HashMap<String, SuppressionReason> = muted_ids
+        .into_iter()
+        .map(|id| (id, SuppressionReason::Muted))
+        .collect();
+
+    if recipients.is_empty() {
         debug!(
-            "Sending friend request accept payload on channel {}: {}",
-            config.pushd.get_fr_accepted_routing_key(),
-            payload
+            "Everyone has muted channel {}, not sending notification",
+            channel_id
         );
-        self.channel
-            .basic_publish(
-                BasicProperties::default()
-                    .with_content_type("application/json")
-                    .with_persistence(true)
-                    .finish(),
-                payload.into(),
-                BasicPublishArguments::new(
-                    &config.pushd.exchange,
-                    &config.pushd.get_fr_accepted_routing_key(),
-                ),
-            )
-            .await
+        return (recipients, suppressed);
     }
 
-    pub async fn friend_request_received(
-        &self,
-        received_request_user: &User,
-        sent_request_user: &User,
-    ) -> Result<(), AMQPError> {
-        let config = revolt_config::config().await;
-        let payload = FRReceivedPayload {
-            from_user: sent_request_user.to_owned(),
-            user: received_request_user.id.clone(),
-        };
-        let payload = to_string(&payload).unwrap();
-
+    // Filter out users who are currently viewing the channel
+    if force {
         debug!(
-            "Sending friend request received payload on channel {}: {}",
-            config.pushd.get_fr_received_routing_key(),
-            payload
+            "Forcing notification for channel {}, skipping viewer filter",
+            channel_id
         );
+        (recipients, suppressed)
+    } else {
+        let viewer_ids = &filter_viewers(&recipients, channel_id).await - &always_ids;
+        let recipients = (&recipients.into_iter().collect::<HashSet<String>>() - &viewer_ids)
+            .into_iter()
+            .collect::<Vec<String>>();
 
-        self.channel
-            .basic_publish(
-                BasicProperties::default()
-                    .with_content_type("application/json")
-                    .with_persistence(true)
-                    .finish(),
-                payload.into(),
-                BasicPublishArguments::new(
-                    &config.pushd.exchange,
-                    &config.pushd.get_fr_received_routing_key(),
-                ),
-            )
-            .await
+        metrics::record_suppressed_viewing(viewer_ids.len());
+
+        suppressed.extend(
+            viewer_ids
+                .into_iter()
+                .map(|id| (id, SuppressionReason::Viewing)),
+        );
+
+        (recipients, suppressed)
     }
+}
 
-    pub async fn generic_message(
-        &self,
-        user: &User,
-        title: String,
-        body: String,
-        icon: Option<String>,
-    ) -> Result<(), AMQPError> {
-        let config = revolt_config::config().await;
-        let payload = GenericPayload {
-            title,
-            body,
-            icon,
-            user: user.to_owned(),
-        };
-        let payload = to_string(&payload).unwrap();
+/// Redis key marking a notification that is waiting to be published to a user
+fn pending_push_key(user_id: &str, channel_id: &str) -> String {
+    format!("pending_push:{user_id}:{channel_id}")
+}
 
-        debug!(
-            "Sending generic payload on channel {}: {}",
-            config.pushd.get_generic_routing_key(),
-            payload
-        );
+/// Mark notifications for a message as pending for the given recipients
+///
+/// Returns false if the markers could not be written.
+async fn mark_pending(
+    recipients: &[String],
+    channel_id: &str,
+    message_id: &str,
+    grace_period: u64,
+) -> bool {
+    use redis_kiss::AsyncCommands;
 
-        self.channel
-            .basic_publish(
-                BasicProperties::default()
-                    .with_content_type("application/json")
-                    .with_persistence(true)
-                    .finish(),
-                payload.into(),
-                BasicPublishArguments::new(
-                    &config.pushd.exchange,
-                    &config.pushd.get_generic_routing_key(),
-                ),
+    let Ok(mut conn) = redis_connection().await else {
+        warn!("Failed to get Redis connection for marking pending notifications");
+        return false;
+    };
+
+    for user_id in recipients {
+        let result: Result<(), _> = conn
+            .set_ex(
+                pending_push_key(user_id, channel_id),
+                message_id,
+                (grace_period * 2) as usize,
             )
-            .await
+            .await;
+
+        if let Err(err) = result {
+            warn!("Failed to mark pending notification: {err:?}");
+            return false;
+        }
     }
 
-    pub async fn message_sent(
-        &self,
-        recipients: Vec<String>,
-        mut payload: PushNotification,
-    ) -> Result<(), AMQPError> {
-        if recipients.is_empty() {
-            return Ok(());
+    true
+}
+
+/// Take recipients whose pending notification was not cancelled by an ack
+async fn take_pending(recipients: Vec<String>, channel_id: &str, message_id: &str) -> Vec<String> {
+    use redis_kiss::AsyncCommands;
+
+    let Ok(mut conn) = redis_connection().await else {
+        warn!("Failed to get Redis connection for resolving pending notifications");
+        return recipients;
+    };
+
+    let mut pending = vec![];
+    for user_id in recipients {
+        let key = pending_push_key(&user_id, channel_id);
+        match conn.get::<_, Option<String>>(&key).await {
+            Ok(Some(id)) if id == message_id => {
+                let _: Result<(), _> = conn.del(&key).await;
+                pending.push(user_id);
+            }
+            // Acked in the meantime, or superseded by a newer message
+            Ok(_) => debug!(
+                "Dropping notification for user {} in channel {}",
+                user_id, channel_id
+            ),
+            Err(_) => pending.push(user_id),
         }
+    }
 
-        let config = revolt_config::config().await;
-        let channel_id = payload.channel.id().to_string();
+    pending
+}
 
-        // Spoiler handling
-        if (payload.body.contains("[[") || payload.body.contains("\\[\\["))
-            && (payload.body.contains("]]") || payload.body.contains("\\]\\]"))
-        {
-            payload.body = "(스포일러)".to_string();
+/// Drop a user's pending notification in a channel if the acked message covers it
+///
+/// Message ids sort by time, so a pending notification for a message newer than
+/// the acked one is kept, as the user hasn't seen it yet.
+async fn cancel_pending(user_id: &str, channel_id: &str, message_id: &str) {
+    use redis_kiss::AsyncCommands;
+
+    let Ok(mut conn) = redis_connection().await else {
+        return;
+    };
+
+    let key = pending_push_key(user_id, channel_id);
+    if let Ok(Some(pending)) = conn.get::<_, Option<String>>(&key).await {
+        if message_id >= pending.as_str() {
+            let _: Result<(), _> = conn.del(&key).await;
         }
-        if let Some(ref content) = payload.message.content {
-            if (content.contains("[[") || content.contains("\\[\\["))
-                && (content.contains("]]") || content.contains("\\]\\]"))
-            {
-                payload.message.content = Some("(스포일러)".to_string());
-            }
+    }
+}
+
+/// Message notification held back for the ack grace period
+struct DelayedPublish {
+    recipients: Vec<String>,
+    channel_id: String,
+    payload: PushNotification,
+    routing_key: String,
+}
+
+/// What became of a message notification
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PublishOutcome {
+    /// Published to this many recipients
+    Published(usize),
+    /// Would have been published to this many recipients, but this was a dry run
+    DryRun(usize),
+    /// Held back for the ack grace period for this many recipients
+    Delayed(usize),
+    /// Message is a system message listed in `pushd.excluded_system_messages`
+    Excluded,
+    /// Message only has attachments and `pushd.suppress_attachment_only` is set
+    AttachmentOnly,
+    /// Nobody was left to notify after dropping those who muted the channel,
+    /// snoozed notifications or blocked the author
+    NoRecipients,
+    /// Everyone left to notify was viewing the channel, holding how many that was
+    AllViewing(usize),
+    /// Notifications are turned off with `pushd.disabled`
+    Disabled,
+}
+
+/// Why a recipient was left out of a message notification
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SuppressionReason {
+    /// Recipient has snoozed all of their notifications
+    Snoozed,
+    /// Recipient has blocked the author
+    Blocked,
+    /// Recipient can no longer see the channel or read its history
+    NoAccess,
+    /// Recipient has muted the channel
+    Muted,
+    /// Recipient is currently viewing the channel
+    Viewing,
+}
+
+/// Who a message notification was, or would have been, published to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MessageSentReport {
+    /// What became of the notification
+    pub outcome: PublishOutcome,
+    /// Recipients left after filtering
+    pub recipients: Vec<String>,
+    /// Recipients who were dropped, and why
+    pub suppressed: HashMap<String, SuppressionReason>,
+}
+
+impl MessageSentReport {
+    /// Report a notification which didn't get as far as filtering recipients
+    fn early(outcome: PublishOutcome) -> MessageSentReport {
+        MessageSentReport {
+            outcome,
+            recipients: vec![],
+            suppressed: HashMap::new(),
         }
+    }
+}
 
-        // Filter out users who are currently viewing the channel
-        let viewer_ids = filter_viewers(&recipients, &channel_id).await;
-        let recipients = (&recipients.into_iter().collect::<HashSet<String>>() - &viewer_ids)
-            .into_iter()
-            .collect::<Vec<String>>();
+/// Kind of payload published to pushd
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NotificationKind {
+    Message,
+    Pinned,
+    MemberJoined,
+    MassMention,
+    FriendRequestAccepted,
+    FriendRequestReceived,
+    Generic,
+    Ack,
+    Typing,
+}
 
-        // If all recipients are viewing the channel, don't send notifications
-        if recipients.is_empty() {
-            debug!(
-                "Everyone is viewing channel {}, not sending notification",
-                channel_id
-            );
-            return Ok(());
+impl NotificationKind {
+    const ALL: [NotificationKind; 9] = [
+        NotificationKind::Message,
+        NotificationKind::Pinned,
+        NotificationKind::MemberJoined,
+        NotificationKind::MassMention,
+        NotificationKind::FriendRequestAccepted,
+        NotificationKind::FriendRequestReceived,
+        NotificationKind::Generic,
+        NotificationKind::Ack,
+        NotificationKind::Typing,
+    ];
+
+    /// Name of the kind, as used in configuration
+    pub fn name(self) -> &'static str {
+        match self {
+            NotificationKind::Message => "message",
+            NotificationKind::Pinned => "pinned",
+            NotificationKind::MemberJoined => "member_joined",
+            NotificationKind::MassMention => "mass_mention",
+            NotificationKind::FriendRequestAccepted => "fr_accepted",
+            NotificationKind::FriendRequestReceived => "fr_received",
+            NotificationKind::Generic => "generic",
+            NotificationKind::Ack => "ack",
+            NotificationKind::Typing => "typing",
         }
+    }
 
-        let message_payload = MessageSentPayload {
-            notification: payload,
-            users: recipients.clone(),
-        };
-        let payload = to_string(&message_payload).unwrap();
+    /// Find which kind of payload is published under a routing key
+    pub fn from_routing_key(config: &Settings, routing_key: &str) -> Option<NotificationKind> {
+        NotificationKind::ALL
+            .into_iter()
+            .find(|kind| routing_key_for(config, *kind) == routing_key)
+    }
+}
 
-        debug!(
-            "Sending message payload on channel {}: {}",
-            config.pushd.get_message_routing_key(),
-            payload
-        );
+/// Routing key a kind of payload is published under
+///
+/// Acks and typing events use the same queue in production and testing.
+pub fn routing_key_for(config: &Settings, kind: NotificationKind) -> String {
+    match kind {
+        NotificationKind::Message => config.pushd.get_message_routing_key(),
+        NotificationKind::Pinned => config.pushd.get_pinned_routing_key(),
+        NotificationKind::MemberJoined => config.pushd.get_member_joined_routing_key(),
+        NotificationKind::MassMention => config.pushd.get_mass_mention_routing_key(),
+        NotificationKind::FriendRequestAccepted => config.pushd.get_fr_accepted_routing_key(),
+        NotificationKind::FriendRequestReceived => config.pushd.get_fr_received_routing_key(),
+        NotificationKind::Generic => config.pushd.get_generic_routing_key(),
+        NotificationKind::Ack => config.pushd.ack_queue.clone(),
+        NotificationKind::Typing => config.pushd.typing_queue.clone(),
+    }
+}
 
-        self.channel
-            .basic_publish(
-                BasicProperties::default()
-                    .with_content_type("application/json")
-                    .with_persistence(true)
-                    .finish(),
-                payload.into(),
-                BasicPublishArguments::new(
-                    &config.pushd.exchange,
-                    &config.pushd.get_message_routing_key(),
-                ),
-            )
-            .await
+/// Whether the broker should persist payloads of a kind
+///
+/// Kinds listed in `pushd.transient_notifications` are worthless once they're late,
+/// so they are kept in memory only. Anything else survives a broker restart.
+pub fn is_persistent(config: &Settings, kind: NotificationKind) -> bool {
+    !config
+        .pushd
+        .transient_notifications
+        .iter()
+        .any(|name| name == kind.name())
+}
+
+/// Whether the disabled notice has been logged yet
+static DISABLED_LOGGED: AtomicBool = AtomicBool::new(false);
+
+/// Whether all notifications are turned off with `pushd.disabled`, logging this once
+fn notifications_disabled(config: &Settings) -> bool {
+    if config.pushd.disabled && !DISABLED_LOGGED.swap(true, Ordering::Relaxed) {
+        warn!("Notifications are disabled, nothing will be published to pushd");
     }
 
-    pub async fn mass_mention_message_sent(
-        &self,
-        server_id: String,
-        payload: Vec<PushNotification>,
-    ) -> Result<(), AMQPError> {
-        let config = revolt_config::config().await;
+    config.pushd.disabled
+}
+
+/// Deduplication key for acks, optionally distinguishing between messages in a channel
+fn ack_dedup_key(user_id: &str, channel_id: &str, message_id: &str, per_message: bool) -> String {
+    if per_message {
+        format!("{user_id}-{channel_id}-{message_id}")
+    } else {
+        format!("{user_id}-{channel_id}")
+    }
+}
 
-        let payload = MassMessageSentPayload {
-            notifications: payload,
+/// Split mass mention notifications into payloads of at most `batch_size`, keeping their order
+///
+/// Everything goes in a single payload if `batch_size` is zero.
+fn mass_mention_batches(
+    server_id: String,
+    notifications: Vec<PushNotification>,
+    batch_size: usize,
+) -> Vec<MassMessageSentPayload> {
+    if batch_size == 0 || notifications.len() <= batch_size {
+        return vec![MassMessageSentPayload {
+            notifications,
             server_id,
-        };
-        let payload = to_string(&payload).unwrap();
+        }];
+    }
 
-        let routing_key = config.pushd.get_mass_mention_routing_key();
+    notifications
+        .chunks(batch_size)
+        .map(|chunk| MassMessageSentPayload {
+            notifications: chunk.to_vec(),
+            server_id: server_id.clone(),
+        })
+        .collect()
+}
 
-        debug!(
-            "Sending mass mention payload on channel {}: {}",
-            routing_key, payload
-        );
+#[derive(Clone)]
+pub struct AMQP {
+    notifier: Arc<dyn Notifier>,
+    in_flight: Option<Arc<Semaphore>>,
+    breaker: Option<Arc<CircuitBreaker>>,
+    delayed: Arc<Mutex<HashMap<usize, DelayedPublish>>>,
+    next_delayed: Arc<AtomicUsize>,
+    acks: Arc<Mutex<HashMap<(String, String), String>>>,
+    typing: Arc<Mutex<HashMap<(String, String), Instant>>>,
+    trace_id: Option<String>,
+}
 
-        self.channel
-            .basic_publish(
-                BasicProperties::default()
-                    .with_content_type("application/json")
-                    .with_persistence(true)
-                    .finish(),
-                payload.into(),
-                BasicPublishArguments::new(&config.pushd.exchange, routing_key.as_str()),
-            )
-            .await
+impl AMQP {
+    pub fn new(connection: Connection, channel: Channel) -> AMQP {
+        AMQP::with_channels(connection, vec![channel])
     }
 
-    pub async fn ack_message(
-        &self,
-        user_id: String,
-        channel_id: String,
-        message_id: String,
-    ) -> Result<(), AMQPError> {
-        let config = revolt_config::config().await;
+    /// Create a publisher which spreads publishes across a pool of channels
+    pub fn with_channels(connection: Connection, channels: Vec<Channel>) -> AMQP {
+        AMQP::with_notifier(Arc::new(RabbitNotifier::new(connection, channels)))
+    }
 
-        let payload = AckPayload {
-            user_id: user_id.clone(),
-            channel_id: channel_id.clone(),
-            message_id,
-        };
-        let payload = to_string(&payload).unwrap();
+    /// Create a publisher which posts notifications to an HTTP endpoint instead of RabbitMQ
+    pub fn http(endpoint: String, timeout: Duration) -> AMQP {
+        AMQP::with_notifier(Arc::new(HttpNotifier::new(endpoint, timeout)))
+    }
 
-        info!(
-            "Sending ack payload on channel {}: {}",
-            config.pushd.ack_queue, payload
-        );
+    /// Connect to the transport configured in `pushd.transport`
+    pub async fn connect(config: &Settings) -> AMQP {
+        if config.pushd.transport == "http" {
+            return AMQP::http(
+                config.pushd.http_endpoint.clone(),
+                Duration::from_secs(config.pushd.http_timeout),
+            )
+            .with_publish_limit(config.pushd.max_in_flight_publishes)
+            .with_circuit_breaker(
+                config.pushd.breaker_threshold,
+                Duration::from_secs(config.pushd.breaker_cooldown),
+            );
+        }
+
+        let connection = Connection::open(&OpenConnectionArguments::new(
+            &config.rabbit.host,
+            config.rabbit.port,
+            &config.rabbit.username,
+            &config.rabbit.password,
+        ))
+        .await
+        .expect("Failed to connect to RabbitMQ");
+
+        let mut channels = vec![];
+        for _ in 0..config.rabbit.publish_channels.max(1) {
+            channels.push(
+                connection
+                    .open_channel(None)
+                    .await
+                    .expect("Failed to open RabbitMQ channel"),
+            );
+        }
+
+        channels[0]
+            .exchange_declare(
+                ExchangeDeclareArguments::new(&config.pushd.exchange, "direct")
+                    .durable(true)
+                    .finish(),
+            )
+            .await
+            .expect("Failed to declare exchange");
+
+        AMQP::with_channels(connection, channels)
+            .with_publish_limit(config.pushd.max_in_flight_publishes)
+            .with_circuit_breaker(
+                config.pushd.breaker_threshold,
+                Duration::from_secs(config.pushd.breaker_cooldown),
+            )
+    }
+
+    /// Create a publisher which hands notifications to the given transport
+    pub fn with_notifier(notifier: Arc<dyn Notifier>) -> AMQP {
+        AMQP {
+            notifier,
+            in_flight: None,
+            breaker: None,
+            delayed: Default::default(),
+            next_delayed: Arc::new(AtomicUsize::new(0)),
+            acks: Default::default(),
+            typing: Default::default(),
+            trace_id: None,
+        }
+    }
+
+    /// Limit how many publishes may be waiting on the transport at once
+    ///
+    /// Further publishes wait for a slot rather than piling up on the broker.
+    /// Publishes are not limited if `limit` is zero.
+    pub fn with_publish_limit(self, limit: usize) -> AMQP {
+        AMQP {
+            in_flight: (limit > 0).then(|| Arc::new(Semaphore::new(limit))),
+            ..self
+        }
+    }
+
+    /// Fail publishes straight away for `cooldown` after `threshold` of them failed in a row
+    ///
+    /// Publishes are always attempted if `threshold` is zero.
+    pub fn with_circuit_breaker(self, threshold: usize, cooldown: Duration) -> AMQP {
+        AMQP {
+            breaker: (threshold > 0).then(|| Arc::new(CircuitBreaker::new(threshold, cooldown))),
+            ..self
+        }
+    }
+
+    /// State of the circuit breaker in front of the transport
+    ///
+    /// Always closed if there is no circuit breaker.
+    pub fn health(&self) -> BreakerState {
+        self.breaker
+            .as_ref()
+            .map_or(BreakerState::Closed, |breaker| {
+                breaker.state(Instant::now())
+            })
+    }
+
+    /// Get a handle which tags everything it publishes with the given trace id
+    ///
+    /// This lets a push delivery be correlated with the request which caused it.
+    pub fn traced(&self, trace_id: String) -> AMQP {
+        AMQP {
+            trace_id: Some(trace_id),
+            ..self.clone()
+        }
+    }
+
+    /// Trace id attached to this handle, if any
+    pub fn trace_id(&self) -> Option<&str> {
+        self.trace_id.as_deref()
+    }
+
+    /// Properties for a published payload, including its trace id
+    ///
+    /// A new trace id is generated if this handle wasn't given one.
+    fn properties(&self, persistent: bool) -> BasicProperties {
+        self.properties_with_headers(persistent, FieldTable::new())
+    }
+
+    /// Message properties with additional headers, alongside the trace id
+    fn properties_with_headers(
+        &self,
+        persistent: bool,
+        mut headers: FieldTable,
+    ) -> BasicProperties {
+        let trace_id = self
+            .trace_id
+            .clone()
+            .unwrap_or_else(|| ulid::Ulid::new().to_string());
+
+        headers.insert("x-trace-id".try_into().unwrap(), trace_id.into());
+
+        BasicProperties::default()
+            .with_content_type("application/json")
+            .with_persistence(persistent)
+            .with_headers(headers)
+            .finish()
+    }
+
+    /// Hand a payload over to the transport under the given routing key
+    ///
+    /// Payloads are dropped if `pushd.disabled` is set. If the publish limit has been
+    /// reached, this waits until another publish has finished. Fails straight away
+    /// while the circuit breaker is open.
+    async fn publish(
+        &self,
+        properties: BasicProperties,
+        content: Vec<u8>,
+        routing_key: &str,
+    ) -> Result<(), AMQPError> {
+        if notifications_disabled(&revolt_config::config().await) {
+            return Ok(());
+        }
+
+        if let Some(breaker) = &self.breaker {
+            if !breaker.allow(Instant::now()) {
+                return Err(AMQPError::ChannelUseError(
+                    "circuit breaker is open".to_string(),
+                ));
+            }
+        }
+
+        let _permit = match &self.in_flight {
+            Some(semaphore) => Some(semaphore.acquire().await),
+            None => None,
+        };
+
+        let result = self
+            .notifier
+            .publish(routing_key, properties, content)
+            .await;
+
+        if let Some(breaker) = &self.breaker {
+            match &result {
+                Ok(()) => breaker.record_success(),
+                Err(_) => breaker.record_failure(Instant::now()),
+            }
+        }
+
+        result
+    }
+
+    /// Publish a payload, going through the outbox if `pushd.outbox` is enabled
+    ///
+    /// If the payload can't be written to the outbox, it is still published directly.
+    async fn publish_reliably(
+        &self,
+        properties: BasicProperties,
+        content: Vec<u8>,
+        routing_key: &str,
+    ) -> Result<(), AMQPError> {
+        let config = revolt_config::config().await;
+        if notifications_disabled(&config) {
+            return Ok(());
+        }
+
+        if !config.pushd.outbox {
+            return self.publish(properties, content, routing_key).await;
+        }
+
+        let entry = outbox::persist(routing_key, &content)
+            .await
+            .map_err(|err| warn!("Failed to write notification to outbox: {err:?}"))
+            .ok();
+
+        self.publish(properties, content, routing_key).await?;
+
+        if let Some(id) = entry {
+            if let Err(err) = outbox::mark_sent(&id).await {
+                warn!("Failed to clear notification {id} from outbox: {err:?}");
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Publish a notification left behind in the outbox
+    ///
+    /// Users who were already delivered a message notification aren't sent it again,
+    /// such as when it was published just before a crash.
+    pub async fn republish(&self, entry: &OutboxEntry) -> Result<(), AMQPError> {
+        let config = revolt_config::config().await;
+        let mut content = entry.content.clone();
+
+        if entry.routing_key == routing_key_for(&config, NotificationKind::Message) {
+            if let Ok(mut payload) = serde_json::from_str::<MessageSentPayload>(&content) {
+                match fetch_delivered(&payload.users, &payload.notification.message.id).await {
+                    Ok(delivered) if !delivered.is_empty() => {
+                        payload.users.retain(|user_id| !delivered.contains(user_id));
+                        if payload.users.is_empty() {
+                            return Ok(());
+                        }
+
+                        content = to_string(&payload).unwrap();
+                    }
+                    Ok(_) => {}
+                    Err(err) => warn!("Failed to fetch delivery receipts: {err:?}"),
+                }
+            }
+        }
+
+        let persistent = NotificationKind::from_routing_key(&config, &entry.routing_key)
+            .map_or(true, |kind| is_persistent(&config, kind));
+
+        self.publish(
+            self.properties(persistent),
+            content.into(),
+            &entry.routing_key,
+        )
+        .await
+    }
+
+    pub async fn friend_request_accepted(
+        &self,
+        accepted_request_user: &User,
+        sent_request_user: &User,
+    ) -> Result<(), AMQPError> {
+        if should_suppress_all(sent_request_user) {
+            return Ok(());
+        }
+
+        let config = revolt_config::config().await;
+        let payload = FRAcceptedPayload {
+            accepted_user: accepted_request_user.to_owned(),
+            user: sent_request_user.id.clone(),
+        };
+        let payload = to_string(&payload).unwrap();
+
+        debug!(
+            "Sending friend request accept payload on channel {}: {}",
+            routing_key_for(&config, NotificationKind::FriendRequestAccepted),
+            payload
+        );
+        self.publish_reliably(
+            self.properties(is_persistent(
+                &config,
+                NotificationKind::FriendRequestAccepted,
+            )),
+            payload.into(),
+            &routing_key_for(&config, NotificationKind::FriendRequestAccepted),
+        )
+        .await
+    }
+
+    pub async fn friend_request_received(
+        &self,
+        received_request_user: &User,
+        sent_request_user: &User,
+    ) -> Result<(), AMQPError> {
+        if should_suppress_all(received_request_user) {
+            return Ok(());
+        }
+
+        let config = revolt_config::config().await;
+        let payload = FRReceivedPayload {
+            from_user: sent_request_user.to_owned(),
+            user: received_request_user.id.clone(),
+        };
+        let payload = to_string(&payload).unwrap();
+
+        debug!(
+            "Sending friend request received payload on channel {}: {}",
+            routing_key_for(&config, NotificationKind::FriendRequestReceived),
+            payload
+        );
+
+        self.publish_reliably(
+            self.properties(is_persistent(
+                &config,
+                NotificationKind::FriendRequestReceived,
+            )),
+            payload.into(),
+            &routing_key_for(&config, NotificationKind::FriendRequestReceived),
+        )
+        .await
+    }
+
+    /// Publish many friend requests as a single payload
+    ///
+    /// Each event is a pair of the user receiving and the user sending the request.
+    pub async fn friend_requests_received_bulk(
+        &self,
+        events: Vec<(&User, &User)>,
+    ) -> Result<(), AMQPError> {
+        let events: Vec<(&User, &User)> = events
+            .into_iter()
+            .filter(|(received, _)| !should_suppress_all(received))
+            .collect();
+
+        if events.is_empty() {
+            return Ok(());
+        }
+
+        let config = revolt_config::config().await;
+        let payload = FRReceivedEvent::Bulk(FRReceivedBulkPayload {
+            requests: events
+                .into_iter()
+                .map(|(received, sent)| FRReceivedPayload {
+                    from_user: sent.to_owned(),
+                    user: received.id.clone(),
+                })
+                .collect(),
+        });
+        let payload = to_string(&payload).unwrap();
+
+        debug!(
+            "Sending bulk friend request received payload on channel {}: {}",
+            routing_key_for(&config, NotificationKind::FriendRequestReceived),
+            payload
+        );
+
+        self.publish_reliably(
+            self.properties(is_persistent(
+                &config,
+                NotificationKind::FriendRequestReceived,
+            )),
+            payload.into(),
+            &routing_key_for(&config, NotificationKind::FriendRequestReceived),
+        )
+        .await
+    }
+
+    pub async fn generic_message(
+        &self,
+        user: &User,
+        title: String,
+        body: String,
+        icon: Option<String>,
+    ) -> Result<(), AMQPError> {
+        self.generic_message_bulk(std::slice::from_ref(user), title, body, icon)
+            .await
+    }
+
+    /// Send the same generic notification to several users as a single payload
+    pub async fn generic_message_bulk(
+        &self,
+        users: &[User],
+        title: String,
+        body: String,
+        icon: Option<String>,
+    ) -> Result<(), AMQPError> {
+        let users: Vec<User> = users
+            .iter()
+            .filter(|user| !should_suppress_all(user))
+            .cloned()
+            .collect();
+
+        if users.is_empty() {
+            return Ok(());
+        }
+
+        let config = revolt_config::config().await;
+        let payload = GenericEvent::Bulk(GenericBulkPayload {
+            title,
+            body,
+            icon,
+            avatar_user: None,
+            users,
+        });
+        let payload = to_string(&payload).unwrap();
+
+        debug!(
+            "Sending bulk generic payload on channel {}: {}",
+            routing_key_for(&config, NotificationKind::Generic),
+            payload
+        );
+
+        self.publish(
+            self.properties(is_persistent(&config, NotificationKind::Generic)),
+            payload.into(),
+            &routing_key_for(&config, NotificationKind::Generic),
+        )
+        .await
+    }
+
+    /// Send a generic notification using another user's avatar as the icon
+    pub async fn generic_message_with_avatar(
+        &self,
+        user: &User,
+        title: String,
+        body: String,
+        avatar_user: &str,
+    ) -> Result<(), AMQPError> {
+        self.publish_generic(GenericPayload {
+            title,
+            body,
+            icon: None,
+            avatar_user: Some(avatar_user.to_string()),
+            user: user.to_owned(),
+        })
+        .await
+    }
+
+    async fn publish_generic(&self, payload: GenericPayload) -> Result<(), AMQPError> {
+        if should_suppress_all(&payload.user) {
+            return Ok(());
+        }
+
+        let config = revolt_config::config().await;
+        let payload = to_string(&payload).unwrap();
+
+        debug!(
+            "Sending generic payload on channel {}: {}",
+            routing_key_for(&config, NotificationKind::Generic),
+            payload
+        );
+
+        self.publish(
+            self.properties(is_persistent(&config, NotificationKind::Generic)),
+            payload.into(),
+            &routing_key_for(&config, NotificationKind::Generic),
+        )
+        .await
+    }
+
+    /// Publish a message notification to the given recipients
+    ///
+    /// The author is never notified of their own message, and nobody is if `pushd.disabled` is set.
+    /// Nothing is published for system messages listed in `pushd.excluded_system_messages`,
+    /// or for attachment-only messages if `pushd.suppress_attachment_only` is set.
+    /// Messages shorter than `pushd.min_body_length` are shown with a generic body.
+    /// Recipients who snoozed notifications, blocked the author, can no longer read the
+    /// channel, muted it or are currently viewing it are dropped.
+    /// Set `force` for notifications that must reach users even while they have the
+    /// channel open or notifications snoozed, such as admin broadcasts or incoming calls;
+    /// muted users are still skipped.
+    /// Viewers of direct messages are also notified if `pushd.filter_dm_viewers` is disabled,
+    /// as are viewers of channels listed in `pushd.broadcast_channels`. Users who muted a
+    /// broadcast channel are skipped unless `pushd.broadcast_channels_override_mute` is set.
+    ///
+    /// Set `dry_run` to run all of the filtering without publishing anything, such as
+    /// when testing notification rules. The report says who would have been notified,
+    /// and why everyone else was dropped.
+    pub async fn message_sent(
+        &self,
+        db: &Database,
+        recipients: Vec<String>,
+        mut payload: PushNotification,
+        force: bool,
+        dry_run: bool,
+    ) -> Result<MessageSentReport, AMQPError> {
+        let recipients: Vec<String> = recipients
+            .into_iter()
+            .filter(|id| id != &payload.message.author)
+            .collect();
+
+        if recipients.is_empty() {
+            return Ok(MessageSentReport::early(PublishOutcome::NoRecipients));
+        }
+
+        let config = revolt_config::config().await;
+        if notifications_disabled(&config) {
+            return Ok(MessageSentReport::early(PublishOutcome::Disabled));
+        }
+
+        if is_excluded_system_message(
+            payload.message.system.as_ref(),
+            &config.pushd.excluded_system_messages,
+        ) {
+            return Ok(MessageSentReport::early(PublishOutcome::Excluded));
+        }
+
+        if format::apply_body_rule(
+            &mut payload,
+            config.pushd.min_body_length,
+            config.pushd.suppress_attachment_only,
+        ) == format::BodyRule::Suppress
+        {
+            return Ok(MessageSentReport::early(PublishOutcome::AttachmentOnly));
+        }
+
+        let channel_id = payload.channel.id().to_string();
+
+        format::format_notification(db, &mut payload, config.pushd.max_body_length).await;
+
+        // Drop recipients who snoozed notifications or blocked the author
+        let author_id = payload.message.author.clone();
+        let (recipients, mut suppressed) = filter_users(db, recipients, |user| {
+            if has_blocked(user, &author_id) {
+                Some(SuppressionReason::Blocked)
+            } else if !force && should_suppress_all(user) {
+                Some(SuppressionReason::Snoozed)
+            } else {
+                None
+            }
+        })
+        .await;
+
+        // Drop recipients who lost access to the channel since the message was sent
+        let with_access = filter_access(db, recipients.clone(), &channel_id).await;
+        suppressed.extend(
+            recipients
+                .into_iter()
+                .filter(|id| !with_access.contains(id))
+                .map(|id| (id, SuppressionReason::NoAccess)),
+        );
+
+        let broadcast = broadcast_rules(&config, &channel_id);
+        let force = force
+            || broadcast.notify_viewers
+            || (!config.pushd.filter_dm_viewers
+                && matches!(payload.channel, v0::Channel::DirectMessage { .. }));
+
+        let (recipients, dropped) =
+            filter_recipients(db, with_access, &channel_id, force, broadcast.notify_muted).await;
+        let viewing = dropped
+            .values()
+            .filter(|reason| **reason == SuppressionReason::Viewing)
+            .count();
+        suppressed.extend(dropped);
+
+        let report = |outcome, recipients| MessageSentReport {
+            outcome,
+            recipients,
+            suppressed,
+        };
+
+        if recipients.is_empty() {
+            if viewing == 0 {
+                return Ok(report(PublishOutcome::NoRecipients, recipients));
+            }
+
+            info!(
+                "Everyone is viewing channel {}, suppressed notification for {} user(s)",
+                channel_id, viewing
+            );
+            return Ok(report(PublishOutcome::AllViewing(viewing), recipients));
+        }
+
+        if dry_run {
+            return Ok(report(PublishOutcome::DryRun(recipients.len()), recipients));
+        }
+
+        // Give other devices a chance to read the message first
+        let grace_period = config.pushd.ack_grace_period;
+        if grace_period > 0
+            && mark_pending(&recipients, &channel_id, &payload.message.id, grace_period).await
+        {
+            let outcome = report(
+                PublishOutcome::Delayed(recipients.len()),
+                recipients.clone(),
+            );
+            let id = self.next_delayed.fetch_add(1, Ordering::Relaxed);
+            self.delayed.lock().unwrap().insert(
+                id,
+                DelayedPublish {
+                    recipients,
+                    channel_id,
+                    payload,
+                    routing_key: routing_key_for(&config, NotificationKind::Message),
+                },
+            );
+
+            let amqp = self.clone();
+            let db = db.clone();
+            spawn(async move {
+                sleep(Duration::from_secs(grace_period)).await;
+
+                // Already flushed if we are shutting down
+                let delayed = amqp.delayed.lock().unwrap().remove(&id);
+                if let Some(delayed) = delayed {
+                    amqp.publish_delayed(&db, delayed).await;
+                }
+            });
+
+            return Ok(outcome);
+        }
+
+        let outcome = report(
+            PublishOutcome::Published(recipients.len()),
+            recipients.clone(),
+        );
+        self.publish_message_sent(
+            db,
+            recipients,
+            payload,
+            &routing_key_for(&config, NotificationKind::Message),
+        )
+        .await?;
+
+        Ok(outcome)
+    }
+
+    /// Publish a held back notification to recipients who haven't read the message since
+    async fn publish_delayed(&self, db: &Database, delayed: DelayedPublish) {
+        let DelayedPublish {
+            recipients,
+            channel_id,
+            payload,
+            routing_key,
+        } = delayed;
+
+        let message_id = payload.message.id.clone();
+        let recipients = take_pending(recipients, &channel_id, &message_id).await;
+        let recipients = filter_access(db, recipients, &channel_id).await;
+        if recipients.is_empty() {
+            debug!(
+                "Message {} was read before notifying anyone, not sending notification",
+                message_id
+            );
+            return;
+        }
+
+        if let Err(err) = self
+            .publish_message_sent(db, recipients, payload, &routing_key)
+            .await
+        {
+            warn!("Failed to publish delayed notification: {err:?}");
+        }
+    }
+
+    /// Flush held back notifications and close the connection
+    ///
+    /// Notifications still inside their ack grace period are published straight away
+    /// rather than dropped. Publishes are not confirmed by the broker, so once they
+    /// have been written out the transport can be closed.
+    pub async fn shutdown(&self, db: &Database) {
+        let delayed: Vec<DelayedPublish> = self
+            .delayed
+            .lock()
+            .unwrap()
+            .drain()
+            .map(|(_, delayed)| delayed)
+            .collect();
+
+        info!("Flushing {} delayed notification(s)", delayed.len());
+        for delayed in delayed {
+            self.publish_delayed(db, delayed).await;
+        }
+
+        if let Err(err) = self.flush_acks().await {
+            warn!("Failed to flush buffered acks: {err:?}");
+        }
+
+        self.notifier.close().await;
+    }
+
+    /// Publish a message notification without any further filtering
+    async fn publish_message_sent(
+        &self,
+        db: &Database,
+        recipients: Vec<String>,
+        payload: PushNotification,
+        routing_key: &str,
+    ) -> Result<(), AMQPError> {
+        let config = revolt_config::config().await;
+
+        metrics::record_published(recipients.len());
+
+        // Find sessions which only want to see that a message arrived
+        let redacted_sessions = db
+            .fetch_redacted_push_session_ids(&recipients)
+            .await
+            .unwrap_or_else(|err| {
+                warn!("Failed to fetch redacted push sessions: {err:?}");
+                vec![]
+            });
+
+        // Work out who is being replied to or mentioned
+        let reply_authors = match payload.message.replies.as_deref() {
+            Some(replies) if !replies.is_empty() => db
+                .fetch_messages_by_id(replies)
+                .await
+                .map(|messages| messages.into_iter().map(|msg| msg.author).collect())
+                .unwrap_or_else(|err| {
+                    warn!("Failed to fetch replied messages: {err:?}");
+                    vec![]
+                }),
+            _ => vec![],
+        };
+
+        let mentions = payload.message.mentions.clone().unwrap_or_default();
+        let classes = recipients
+            .iter()
+            .map(|user_id| {
+                (
+                    user_id.clone(),
+                    NotificationClass::classify(user_id, &mentions, &reply_authors),
+                )
+            })
+            .filter(|(_, class)| *class != NotificationClass::Normal)
+            .collect();
+
+        // Narrow down to a single session for users who only want one device notified
+        let primary_sessions = find_primary_sessions(db, &recipients).await;
+
+        let message_payload = MessageSentPayload {
+            notification: payload,
+            users: recipients.clone(),
+            redacted_sessions,
+            classes,
+            primary_sessions,
+        };
+        let payload = to_string(&message_payload).unwrap();
+
+        debug!(
+            "Sending message payload on channel {}: {}",
+            routing_key, payload
+        );
+
+        let persistent = NotificationKind::from_routing_key(&config, routing_key)
+            .map_or(true, |kind| is_persistent(&config, kind));
+
+        self.publish_reliably(self.properties(persistent), payload.into(), routing_key)
+            .await
+    }
+
+    /// Notify recipients that a message was pinned
+    ///
+    /// The same mute and viewer filtering as for new messages applies.
+    pub async fn message_pinned(
+        &self,
+        db: &Database,
+        pinner: &str,
+        mut payload: PushNotification,
+        recipients: Vec<String>,
+    ) -> Result<(), AMQPError> {
+        if recipients.is_empty() {
+            return Ok(());
+        }
+
+        let config = revolt_config::config().await;
+        let channel_id = payload.channel.id().to_string();
+
+        format::format_notification(db, &mut payload, config.pushd.max_body_length).await;
+        payload.body = match payload.message.content.as_deref() {
+            Some(content) if !content.is_empty() => {
+                format!("{pinner} pinned a message: {content}")
+            }
+            _ => format!("{pinner} pinned a message"),
+        };
+        payload.author = pinner.to_string();
+
+        let recipients = filter_snoozed(db, recipients).await;
+        let (recipients, _) = filter_recipients(db, recipients, &channel_id, false, false).await;
+        if recipients.is_empty() {
+            return Ok(());
+        }
+
+        self.publish_message_sent(
+            db,
+            recipients,
+            payload,
+            &routing_key_for(&config, NotificationKind::Pinned),
+        )
+        .await
+    }
+
+    /// Notify server staff that someone joined the server
+    ///
+    /// Does nothing unless the server has opted in to member join notifications.
+    /// If the server posts join messages to a system channel, staff who muted or
+    /// are currently viewing that channel are skipped.
+    pub async fn member_joined(
+        &self,
+        db: &Database,
+        server: &Server,
+        new_member: &User,
+        staff_recipients: Vec<String>,
+    ) -> Result<(), AMQPError> {
+        if !server.notify_member_joins {
+            return Ok(());
+        }
+
+        let mut recipients: Vec<String> = staff_recipients
+            .into_iter()
+            .filter(|id| id != &new_member.id)
+            .collect();
+        recipients = filter_snoozed(db, recipients).await;
+
+        if let Some(channel_id) = server
+            .system_messages
+            .as_ref()
+            .and_then(|system_messages| system_messages.user_joined.as_deref())
+        {
+            (recipients, _) = filter_recipients(db, recipients, channel_id, false, false).await;
+        }
+
+        if recipients.is_empty() {
+            return Ok(());
+        }
+
+        let config = revolt_config::config().await;
+        let payload = MemberJoinedPayload {
+            server_id: server.id.clone(),
+            server_name: server.name.clone(),
+            member: new_member.to_owned(),
+            users: recipients,
+        };
+        let payload = to_string(&payload).unwrap();
+
+        let routing_key = routing_key_for(&config, NotificationKind::MemberJoined);
+
+        debug!(
+            "Sending member joined payload on channel {}: {}",
+            routing_key, payload
+        );
+
+        self.publish(
+            self.properties(is_persistent(&config, NotificationKind::MemberJoined)),
+            payload.into(),
+            routing_key.as_str(),
+        )
+        .await
+    }
+
+    /// Publish mass mention notifications for a server
+    ///
+    /// Notifications are split into messages of at most `pushd.mass_mention_batch_size`,
+    /// published in order.
+    pub async fn mass_mention_message_sent(
+        &self,
+        server_id: String,
+        payload: Vec<PushNotification>,
+    ) -> Result<(), AMQPError> {
+        let config = revolt_config::config().await;
+        let routing_key = routing_key_for(&config, NotificationKind::MassMention);
+
+        for batch in mass_mention_batches(server_id, payload, config.pushd.mass_mention_batch_size)
+        {
+            let payload = to_string(&batch).unwrap();
+
+            debug!(
+                "Sending mass mention payload on channel {}: {}",
+                routing_key, payload
+            );
+
+            self.publish(
+                self.properties(is_persistent(&config, NotificationKind::MassMention)),
+                payload.into(),
+                routing_key.as_str(),
+            )
+            .await?;
+        }
+
+        Ok(())
+    }
+
+    /// Cancel a notification still waiting to be published to a user in a channel,
+    /// unless it is for a message newer than the one acked
+    pub async fn cancel_pending_notification(
+        &self,
+        user_id: &str,
+        channel_id: &str,
+        message_id: &str,
+    ) {
+        let config = revolt_config::config().await;
+        if config.pushd.ack_grace_period == 0 {
+            return;
+        }
+
+        cancel_pending(user_id, channel_id, message_id).await;
+    }
+
+    pub async fn ack_message(
+        &self,
+        user_id: String,
+        channel_id: String,
+        message_id: String,
+    ) -> Result<(), AMQPError> {
+        let config = revolt_config::config().await;
+
+        self.cancel_pending_notification(&user_id, &channel_id, &message_id)
+            .await;
+
+        let dedup_key = ack_dedup_key(
+            &user_id,
+            &channel_id,
+            &message_id,
+            config.pushd.ack_dedup_per_message,
+        );
+
+        let payload = AckPayload {
+            user_id,
+            channel_id,
+            message_id,
+        };
+        let payload = to_string(&payload).unwrap();
+
+        info!(
+            "Sending ack payload on channel {}: {}",
+            routing_key_for(&config, NotificationKind::Ack),
+            payload
+        );
 
         let mut headers = FieldTable::new();
         headers.insert(
             "x-deduplication-header".try_into().unwrap(),
-            format!("{}-{}", &user_id, &channel_id).into(),
+            dedup_key.into(),
         );
 
-        self.channel
-            .basic_publish(
-                BasicProperties::default()
-                    .with_content_type("application/json")
-                    .with_persistence(true)
-                    //.with_headers(headers)
-                    .finish(),
-                payload.into(),
-                BasicPublishArguments::new(&config.pushd.exchange, &config.pushd.ack_queue),
+        self.publish(
+            self.properties_with_headers(is_persistent(&config, NotificationKind::Ack), headers),
+            payload.into(),
+            &routing_key_for(&config, NotificationKind::Ack),
+        )
+        .await
+    }
+
+    /// Acknowledge a message, buffering it into the next batch if `pushd.ack_batch_interval` is set
+    ///
+    /// Only the latest ack for each user and channel is kept until the batch is flushed.
+    pub async fn buffer_ack(
+        &self,
+        user_id: String,
+        channel_id: String,
+        message_id: String,
+    ) -> Result<(), AMQPError> {
+        let config = revolt_config::config().await;
+        if config.pushd.ack_batch_interval == 0 {
+            return self.ack_message(user_id, channel_id, message_id).await;
+        }
+
+        self.cancel_pending_notification(&user_id, &channel_id, &message_id)
+            .await;
+
+        self.acks
+            .lock()
+            .unwrap()
+            .entry((user_id, channel_id))
+            .and_modify(|id| {
+                if message_id > *id {
+                    id.clone_from(&message_id);
+                }
+            })
+            .or_insert_with(|| message_id.clone());
+
+        Ok(())
+    }
+
+    /// Publish that a user started or stopped typing in a channel
+    ///
+    /// Only call this for channels the user can view, consumers don't check permissions again.
+    /// Does nothing unless `pushd.typing_events` is enabled.
+    pub async fn typing(
+        &self,
+        channel_id: String,
+        user_id: String,
+        is_typing: bool,
+    ) -> Result<(), AMQPError> {
+        let config = revolt_config::config().await;
+        if !config.pushd.typing_events {
+            return Ok(());
+        }
+
+        let throttle = Duration::from_secs(config.pushd.typing_throttle);
+        let now = Instant::now();
+        {
+            let mut typing = self.typing.lock().unwrap();
+            let key = (channel_id.clone(), user_id.clone());
+            if !should_publish_typing(typing.get(&key).copied(), is_typing, throttle, now) {
+                return Ok(());
+            }
+
+            // Forget anyone whose connection went away without them stopping
+            typing.retain(|_, started| now.duration_since(*started) < TYPING_TIMEOUT);
+
+            if is_typing {
+                typing.insert(key, now);
+            } else {
+                typing.remove(&key);
+            }
+        }
+
+        let payload = to_string(&TypingPayload {
+            channel_id,
+            user_id,
+            typing: is_typing,
+        })
+        .unwrap();
+
+        self.publish(
+            self.properties(is_persistent(&config, NotificationKind::Typing)),
+            payload.into(),
+            &routing_key_for(&config, NotificationKind::Typing),
+        )
+        .await
+    }
+
+    /// Publish all buffered acks as a single batch
+    pub async fn flush_acks(&self) -> Result<(), AMQPError> {
+        let acks: Vec<AckPayload> = self
+            .acks
+            .lock()
+            .unwrap()
+            .drain()
+            .map(|((user_id, channel_id), message_id)| AckPayload {
+                user_id,
+                channel_id,
+                message_id,
+            })
+            .collect();
+
+        if acks.is_empty() {
+            return Ok(());
+        }
+
+        let config = revolt_config::config().await;
+        info!(
+            "Sending batch of {} ack(s) on channel {}",
+            acks.len(),
+            routing_key_for(&config, NotificationKind::Ack)
+        );
+
+        let payload = to_string(&AckQueueEvent::Batch(AckBatchPayload { acks })).unwrap();
+        self.publish(
+            self.properties(is_persistent(&config, NotificationKind::Ack)),
+            payload.into(),
+            &routing_key_for(&config, NotificationKind::Ack),
+        )
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{HashMap, HashSet};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    use amqprs::{error::Error as AMQPError, BasicProperties};
+    use async_trait::async_trait;
+    use redis_kiss::{get_connection, AsyncCommands, Conn};
+    use ulid::Ulid;
+
+    use iso8601_timestamp::{Duration, Timestamp};
+
+    use revolt_models::v0;
+
+    use super::{
+        ack_dedup_key, broadcast_rules, cancel_pending, filter_access, find_viewers, has_blocked,
+        is_excluded_system_message, is_persistent, mark_pending, mass_mention_batches,
+        notification_overrides, open_channels_key, pick_primary_session, routing_key_for,
+        should_publish_typing, should_suppress_all, take_pending, unfocused_session_key,
+        BreakerState, NotificationKind, PublishOutcome, SuppressionReason, AMQP,
+    };
+    use crate::amqp::format::SPOILER_BODY;
+    use crate::amqp::notifier::Notifier;
+    use crate::amqp::outbox::OutboxEntry;
+    use crate::events::rabbit::{DeliveryReceipt, MessageSentPayload};
+    use crate::util::delivery::{fetch_delivered, record_delivery};
+    use crate::{
+        fixture, ChannelCompositeKey, ChannelNotificationSettings, Database, Message, Presence,
+        Relationship, RelationshipStatus, User, UserStatus,
+    };
+
+    /// Transport which only records the payloads it was asked to deliver
+    #[derive(Default)]
+    struct CountingNotifier {
+        publishes: AtomicUsize,
+        payloads: std::sync::Mutex<Vec<serde_json::Value>>,
+    }
+
+    #[async_trait]
+    impl Notifier for CountingNotifier {
+        async fn publish(
+            &self,
+            _routing_key: &str,
+            _properties: BasicProperties,
+            content: Vec<u8>,
+        ) -> Result<(), AMQPError> {
+            self.publishes.fetch_add(1, Ordering::SeqCst);
+            if let Ok(payload) = serde_json::from_slice(&content) {
+                self.payloads.lock().unwrap().push(payload);
+            }
+
+            Ok(())
+        }
+
+        async fn close(&self) {}
+    }
+
+    /// Transport which takes a while to deliver, recording the most deliveries at once
+    #[derive(Default)]
+    struct SlowNotifier {
+        in_flight: AtomicUsize,
+        most_in_flight: AtomicUsize,
+    }
+
+    #[async_trait]
+    impl Notifier for SlowNotifier {
+        async fn publish(
+            &self,
+            _routing_key: &str,
+            _properties: BasicProperties,
+            _content: Vec<u8>,
+        ) -> Result<(), AMQPError> {
+            let in_flight = self.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+            self.most_in_flight.fetch_max(in_flight, Ordering::SeqCst);
+            async_std::task::sleep(std::time::Duration::from_millis(20)).await;
+            self.in_flight.fetch_sub(1, Ordering::SeqCst);
+
+            Ok(())
+        }
+
+        async fn close(&self) {}
+    }
+
+    #[async_std::test]
+    async fn publish_limit_caps_concurrency() {
+        let notifier = Arc::new(SlowNotifier::default());
+        let amqp = AMQP::with_notifier(notifier.clone()).with_publish_limit(2);
+
+        futures::future::join_all(
+            (0..10).map(|_| amqp.publish(BasicProperties::default(), b"{}".to_vec(), "message")),
+        )
+        .await
+        .into_iter()
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+
+        assert_eq!(notifier.most_in_flight.load(Ordering::SeqCst), 2);
+        assert_eq!(notifier.in_flight.load(Ordering::SeqCst), 0);
+    }
+
+    /// Transport which is down, counting how often it was tried
+    #[derive(Default)]
+    struct DownNotifier {
+        attempts: AtomicUsize,
+    }
+
+    #[async_trait]
+    impl Notifier for DownNotifier {
+        async fn publish(
+            &self,
+            _routing_key: &str,
+            _properties: BasicProperties,
+            _content: Vec<u8>,
+        ) -> Result<(), AMQPError> {
+            self.attempts.fetch_add(1, Ordering::SeqCst);
+            Err(AMQPError::NetworkError("connection refused".to_string()))
+        }
+
+        async fn close(&self) {}
+    }
+
+    #[async_std::test]
+    async fn circuit_breaker_stops_trying_a_broker_which_is_down() {
+        let notifier = Arc::new(DownNotifier::default());
+        let amqp = AMQP::with_notifier(notifier.clone())
+            .with_circuit_breaker(2, std::time::Duration::from_secs(30));
+
+        for _ in 0..5 {
+            assert!(amqp
+                .publish(BasicProperties::default(), b"{}".to_vec(), "message")
+                .await
+                .is_err());
+        }
+
+        assert_eq!(notifier.attempts.load(Ordering::SeqCst), 2);
+        assert!(matches!(amqp.health(), BreakerState::Open { .. }));
+    }
+
+    /// Open channels for a session, returning the key that was written
+    async fn open_channels(user_id: &str, channels: &[&str]) -> String {
+        let key = open_channels_key(user_id, &Ulid::new().to_string());
+        let mut conn = get_connection().await.expect("Redis connection");
+        for channel in channels {
+            let _: () = conn.sadd(&key, *channel).await.unwrap();
+        }
+
+        key
+    }
+
+    #[async_std::test]
+    async fn find_viewers_checks_every_session() {
+        let channel = Ulid::new().to_string();
+        let other_channel = Ulid::new().to_string();
+        let viewing = Ulid::new().to_string();
+        let elsewhere = Ulid::new().to_string();
+        let no_sessions = Ulid::new().to_string();
+        let unfocused = Ulid::new().to_string();
+
+        let mut keys = vec![
+            open_channels(&viewing, &[&other_channel]).await,
+            open_channels(&viewing, &[&other_channel, &channel]).await,
+            open_channels(&elsewhere, &[&other_channel]).await,
+            open_channels(&unfocused, &[&channel]).await,
+        ];
+
+        // Background the session which has the channel open
+        let mut conn = get_connection().await.expect("Redis connection");
+        let session_id = keys[3].rsplit(':').next().unwrap().to_string();
+        let unfocused_key = unfocused_session_key(&unfocused, &session_id);
+        let _: () = conn.set(&unfocused_key, 1).await.unwrap();
+        keys.push(unfocused_key);
+
+        let recipients = vec![viewing.clone(), elsewhere, no_sessions, unfocused];
+        let viewers = find_viewers(get_connection().await.ok(), &recipients, &channel).await;
+
+        for key in keys {
+            let _: () = conn.del(key).await.unwrap();
+        }
+
+        assert_eq!(viewers, HashSet::from([viewing]));
+    }
+
+    #[async_std::test]
+    async fn acking_older_message_keeps_newer_pending_notification() {
+        let user = Ulid::new().to_string();
+        let channel = Ulid::new().to_string();
+        let (older, newer) = ("01HZ0000000000000000000000", "01HZ0000000000000000000001");
+
+        assert!(mark_pending(&[user.clone()], &channel, newer, 60).await);
+        cancel_pending(&user, &channel, older).await;
+        assert_eq!(
+            take_pending(vec![user.clone()], &channel, newer).await,
+            vec![user.clone()]
+        );
+
+        assert!(mark_pending(&[user.clone()], &channel, newer, 60).await);
+        cancel_pending(&user, &channel, newer).await;
+        assert!(take_pending(vec![user], &channel, newer).await.is_empty());
+    }
+
+    #[async_std::test]
+    async fn drops_recipients_who_lost_access() {
+        database_test!(|db| async move {
+            fixture!(db, "group_with_members",
+                owner user 0
+                member user 1
+                channel channel 3);
+
+            let recipients = vec![owner.id.clone(), member.id.clone()];
+
+            // Removed from the group after the message was sent
+            db.remove_user_from_group(channel.id(), &member.id)
+                .await
+                .unwrap();
+
+            assert_eq!(
+                filter_access(&db, recipients, channel.id()).await,
+                vec![owner.id.clone()]
+            );
+        });
+    }
+
+    #[async_std::test]
+    async fn dry_run_reports_recipients_without_publishing() {
+        database_test!(|db| async move {
+            fixture!(db, "group_with_members",
+                owner user 0
+                member user 1
+                outsider user 2
+                channel channel 3);
+
+            let message = Message {
+                id: Ulid::new().to_string(),
+                channel: channel.id().to_string(),
+                author: owner.id.clone(),
+                content: Some("hello there".to_string()),
+                ..Default::default()
+            };
+            let payload = v0::PushNotification::from(
+                message.into_model(None, None),
+                None,
+                channel.into(),
+                None,
+            )
+            .await;
+
+            let notifier = Arc::new(CountingNotifier::default());
+            let report = AMQP::with_notifier(notifier.clone())
+                .message_sent(
+                    &db,
+                    vec![member.id.clone(), outsider.id.clone()],
+                    payload,
+                    false,
+                    true,
+                )
+                .await
+                .unwrap();
+
+            assert_eq!(report.outcome, PublishOutcome::DryRun(1));
+            assert_eq!(report.recipients, vec![member.id.clone()]);
+            assert_eq!(
+                report.suppressed,
+                HashMap::from([(outsider.id.clone(), SuppressionReason::NoAccess)])
+            );
+            assert_eq!(notifier.publishes.load(Ordering::SeqCst), 0);
+        });
+    }
+
+    #[async_std::test]
+    async fn author_is_not_notified_of_own_message() {
+        database_test!(|db| async move {
+            fixture!(db, "group_with_members",
+                owner user 0
+                member user 1
+                channel channel 3);
+
+            let message = Message {
+                id: Ulid::new().to_string(),
+                channel: channel.id().to_string(),
+                author: owner.id.clone(),
+                content: Some("hello there".to_string()),
+                ..Default::default()
+            };
+            let payload = v0::PushNotification::from(
+                message.into_model(None, None),
+                None,
+                channel.into(),
+                None,
+            )
+            .await;
+
+            let report = AMQP::with_notifier(Arc::new(CountingNotifier::default()))
+                .message_sent(
+                    &db,
+                    vec![owner.id.clone(), member.id.clone()],
+                    payload,
+                    false,
+                    true,
+                )
+                .await
+                .unwrap();
+
+            assert_eq!(report.outcome, PublishOutcome::DryRun(1));
+            assert_eq!(report.recipients, vec![member.id.clone()]);
+            assert!(!report.suppressed.contains_key(&owner.id));
+        });
+    }
+
+    #[async_std::test]
+    async fn published_notifications_mask_spoilers() {
+        database_test!(|db| async move {
+            fixture!(db, "group_with_members",
+                owner user 0
+                member user 1
+                channel channel 3);
+
+            let notifier = Arc::new(CountingNotifier::default());
+            let amqp = AMQP::with_notifier(notifier.clone());
+
+            for content in ["the [[butler]] did it", "nothing to hide"] {
+                let message = Message {
+                    id: Ulid::new().to_string(),
+                    channel: channel.id().to_string(),
+                    author: owner.id.clone(),
+                    content: Some(content.to_string()),
+                    ..Default::default()
+                };
+                let payload = v0::PushNotification::from(
+                    message.into_model(None, None),
+                    None,
+                    channel.clone().into(),
+                    None,
+                )
+                .await;
+
+                let report = amqp
+                    .message_sent(&db, vec![member.id.clone()], payload, false, false)
+                    .await
+                    .unwrap();
+                assert_eq!(report.outcome, PublishOutcome::Published(1));
+            }
+
+            let payloads = notifier.payloads.lock().unwrap();
+            assert_eq!(payloads.len(), 2);
+
+            let spoiler = &payloads[0]["notification"];
+            assert_eq!(spoiler["body"], SPOILER_BODY);
+            assert_eq!(spoiler["message"]["content"], SPOILER_BODY);
+
+            let plain = &payloads[1]["notification"];
+            assert_eq!(plain["body"], "nothing to hide");
+            assert_eq!(plain["message"]["content"], "nothing to hide");
+        });
+    }
+
+    #[async_std::test]
+    async fn republish_skips_users_already_delivered_to() {
+        database_test!(|db| async move {
+            fixture!(db, "group_with_members",
+                owner user 0
+                member user 1
+                channel channel 3);
+
+            let message = Message {
+                id: Ulid::new().to_string(),
+                channel: channel.id().to_string(),
+                author: owner.id.clone(),
+                content: Some("are you there?".to_string()),
+                ..Default::default()
+            };
+            let notification = v0::PushNotification::from(
+                message.clone().into_model(None, None),
+                None,
+                channel.clone().into(),
+                None,
             )
+            .await;
+
+            // Published to the owner just before a crash
+            record_delivery(&DeliveryReceipt {
+                user_id: owner.id.clone(),
+                session_id: "phone".to_string(),
+                channel_id: channel.id().to_string(),
+                message_id: message.id.clone(),
+                delivered_at: 0,
+            })
+            .await
+            .unwrap();
+
+            let users = vec![owner.id.clone(), member.id.clone()];
+            assert_eq!(
+                fetch_delivered(&users, &message.id).await.unwrap(),
+                HashSet::from([owner.id.clone()])
+            );
+
+            let config = revolt_config::config().await;
+            let entry = |users: Vec<String>| OutboxEntry {
+                id: Ulid::new().to_string(),
+                routing_key: routing_key_for(&config, NotificationKind::Message),
+                content: serde_json::to_string(&MessageSentPayload {
+                    notification: notification.clone(),
+                    users,
+                    redacted_sessions: vec![],
+                    classes: HashMap::new(),
+                    primary_sessions: HashMap::new(),
+                })
+                .unwrap(),
+                created_at: 0,
+            };
+
+            let notifier = Arc::new(CountingNotifier::default());
+            let amqp = AMQP::with_notifier(notifier.clone());
+            amqp.republish(&entry(users)).await.unwrap();
+            amqp.republish(&entry(vec![owner.id.clone()]))
+                .await
+                .unwrap();
+
+            let payloads = notifier.payloads.lock().unwrap();
+            assert_eq!(payloads.len(), 1);
+            assert_eq!(payloads[0]["users"], serde_json::json!([member.id]));
+        });
+    }
+
+    /// How a group member is set up before a message is sent to the group
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    enum Member {
+        /// Nothing that would keep them from being notified
+        Idle,
+        /// Has the channel open in a focused session
+        Viewing,
+        /// Has the channel open, but asked to always be notified of it
+        ViewingAlwaysNotified,
+        /// Muted the channel
+        Muted,
+        /// Snoozed all of their notifications
+        Snoozed,
+        /// Set their presence to do not disturb
+        DoNotDisturb,
+        /// Blocked the author
+        Blocking,
+        /// Was removed from the group after the message was sent
+        Revoked,
+    }
+
+    /// What became of a message sent through the full filter chain
+    struct FilterChainResult {
+        outcome: PublishOutcome,
+        /// Members who were published to, by their index
+        notified: HashSet<usize>,
+        /// Members who were dropped and why, by their index
+        suppressed: HashMap<usize, SuppressionReason>,
+    }
+
+    /// Send a message to a group of members set up as given, through every filter
+    ///
+    /// Members are created fresh for each run, and their open channels are cleaned up
+    /// afterwards, so runs don't affect each other.
+    async fn run_filter_chain(db: &Database, members: &[Member], force: bool) -> FilterChainResult {
+        fixture!(db, "group_with_members",
+            owner user 0
+            channel channel 3);
+
+        let mut conn = get_connection().await.expect("Redis connection");
+        let mut keys = vec![];
+        let mut ids = vec![];
+
+        for (index, member) in members.iter().enumerate() {
+            let user = User {
+                id: Ulid::new().to_string(),
+                username: format!("Member{index}"),
+                discriminator: "0001".to_string(),
+                relations: (*member == Member::Blocking).then(|| {
+                    vec![Relationship {
+                        id: owner.id.clone(),
+                        status: RelationshipStatus::Blocked,
+                    }]
+                }),
+                notifications_snoozed_until: (*member == Member::Snoozed)
+                    .then(|| Timestamp::now_utc().checked_add(Duration::hours(1)))
+                    .flatten(),
+                status: (*member == Member::DoNotDisturb).then(|| UserStatus {
+                    presence: Some(Presence::Busy),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            };
+            db.insert_user(&user).await.unwrap();
+            db.add_user_to_group(channel.id(), &user.id).await.unwrap();
+
+            match member {
+                Member::Idle | Member::Snoozed | Member::DoNotDisturb | Member::Blocking => {}
+                Member::Viewing => keys.push(open_channels(&user.id, &[channel.id()]).await),
+                Member::ViewingAlwaysNotified => {
+                    db.set_always_notify(channel.id(), &user.id, true)
+                        .await
+                        .unwrap();
+                    keys.push(open_channels(&user.id, &[channel.id()]).await);
+                }
+                Member::Muted => db.mute_channel(channel.id(), &user.id, None).await.unwrap(),
+                Member::Revoked => db
+                    .remove_user_from_group(channel.id(), &user.id)
+                    .await
+                    .unwrap(),
+            }
+
+            ids.push(user.id);
+        }
+
+        let message = Message {
+            id: Ulid::new().to_string(),
+            channel: channel.id().to_string(),
+            author: owner.id.clone(),
+            content: Some("hello everyone".to_string()),
+            ..Default::default()
+        };
+        let payload = v0::PushNotification::from(
+            message.into_model(None, None),
+            None,
+            channel.clone().into(),
+            None,
+        )
+        .await;
+
+        let notifier = Arc::new(CountingNotifier::default());
+        let report = AMQP::with_notifier(notifier.clone())
+            .message_sent(db, ids.clone(), payload, force, false)
             .await
+            .unwrap();
+
+        for key in keys {
+            let _: () = conn.del(key).await.unwrap();
+        }
+
+        let index_of = |id: &str| ids.iter().position(|member| member == id).unwrap();
+        let notified: HashSet<usize> = notifier
+            .payloads
+            .lock()
+            .unwrap()
+            .iter()
+            .flat_map(|payload| payload["users"].as_array().cloned().unwrap_or_default())
+            .map(|id| index_of(id.as_str().unwrap()))
+            .collect();
+
+        assert_eq!(
+            notified,
+            report.recipients.iter().map(|id| index_of(id)).collect(),
+            "published recipients should match the report"
+        );
+
+        FilterChainResult {
+            outcome: report.outcome,
+            notified,
+            suppressed: report
+                .suppressed
+                .iter()
+                .map(|(id, reason)| (index_of(id), *reason))
+                .collect(),
+        }
+    }
+
+    #[async_std::test]
+    async fn filter_chain_only_notifies_idle_members() {
+        database_test!(|db| async move {
+            let result = run_filter_chain(
+                &db,
+                &[
+                    Member::Idle,
+                    Member::Viewing,
+                    Member::Muted,
+                    Member::Snoozed,
+                    Member::Blocking,
+                    Member::Revoked,
+                    Member::Idle,
+                ],
+                false,
+            )
+            .await;
+
+            assert_eq!(result.outcome, PublishOutcome::Published(2));
+            assert_eq!(result.notified, HashSet::from([0, 6]));
+            assert_eq!(
+                result.suppressed,
+                HashMap::from([
+                    (1, SuppressionReason::Viewing),
+                    (2, SuppressionReason::Muted),
+                    (3, SuppressionReason::Snoozed),
+                    (4, SuppressionReason::Blocked),
+                    (5, SuppressionReason::NoAccess),
+                ])
+            );
+        });
+    }
+
+    #[async_std::test]
+    async fn forced_filter_chain_notifies_viewers_and_snoozed_members() {
+        database_test!(|db| async move {
+            let result = run_filter_chain(
+                &db,
+                &[
+                    Member::Idle,
+                    Member::Viewing,
+                    Member::Muted,
+                    Member::Snoozed,
+                    Member::Blocking,
+                    Member::Revoked,
+                ],
+                true,
+            )
+            .await;
+
+            assert_eq!(result.outcome, PublishOutcome::Published(3));
+            assert_eq!(result.notified, HashSet::from([0, 1, 3]));
+            assert_eq!(
+                result.suppressed,
+                HashMap::from([
+                    (2, SuppressionReason::Muted),
+                    (4, SuppressionReason::Blocked),
+                    (5, SuppressionReason::NoAccess),
+                ])
+            );
+        });
+    }
+
+    #[async_std::test]
+    async fn filter_chain_notifies_viewers_who_always_want_notifications() {
+        database_test!(|db| async move {
+            let result = run_filter_chain(
+                &db,
+                &[Member::Viewing, Member::ViewingAlwaysNotified],
+                false,
+            )
+            .await;
+
+            assert_eq!(result.outcome, PublishOutcome::Published(1));
+            assert_eq!(result.notified, HashSet::from([1]));
+            assert_eq!(
+                result.suppressed,
+                HashMap::from([(0, SuppressionReason::Viewing)])
+            );
+        });
+    }
+
+    #[async_std::test]
+    async fn filter_chain_notifies_members_on_do_not_disturb() {
+        database_test!(|db| async move {
+            // There is no do not disturb filter yet, so it shouldn't keep anyone from being notified
+            let result = run_filter_chain(&db, &[Member::DoNotDisturb, Member::Muted], false).await;
+
+            assert_eq!(result.outcome, PublishOutcome::Published(1));
+            assert_eq!(result.notified, HashSet::from([0]));
+            assert_eq!(
+                result.suppressed,
+                HashMap::from([(1, SuppressionReason::Muted)])
+            );
+        });
+    }
+
+    #[async_std::test]
+    async fn filter_chain_publishes_nothing_when_everyone_is_viewing() {
+        database_test!(|db| async move {
+            let result = run_filter_chain(
+                &db,
+                &[Member::Viewing, Member::Muted, Member::Viewing],
+                false,
+            )
+            .await;
+
+            assert_eq!(result.outcome, PublishOutcome::AllViewing(2));
+            assert!(result.notified.is_empty());
+            assert_eq!(
+                result.suppressed,
+                HashMap::from([
+                    (0, SuppressionReason::Viewing),
+                    (1, SuppressionReason::Muted),
+                    (2, SuppressionReason::Viewing),
+                ])
+            );
+        });
+    }
+
+    #[async_std::test]
+    async fn routing_keys_match_each_kind() {
+        let config = revolt_config::config().await;
+
+        for (kind, key) in [
+            (
+                NotificationKind::Message,
+                config.pushd.get_message_routing_key(),
+            ),
+            (
+                NotificationKind::Pinned,
+                config.pushd.get_pinned_routing_key(),
+            ),
+            (
+                NotificationKind::MemberJoined,
+                config.pushd.get_member_joined_routing_key(),
+            ),
+            (
+                NotificationKind::MassMention,
+                config.pushd.get_mass_mention_routing_key(),
+            ),
+            (
+                NotificationKind::FriendRequestAccepted,
+                config.pushd.get_fr_accepted_routing_key(),
+            ),
+            (
+                NotificationKind::FriendRequestReceived,
+                config.pushd.get_fr_received_routing_key(),
+            ),
+            (
+                NotificationKind::Generic,
+                config.pushd.get_generic_routing_key(),
+            ),
+            (NotificationKind::Ack, config.pushd.ack_queue.clone()),
+            (NotificationKind::Typing, config.pushd.typing_queue.clone()),
+        ] {
+            assert_eq!(routing_key_for(&config, kind), key, "{kind:?}");
+        }
+    }
+
+    #[async_std::test]
+    async fn only_transient_kinds_are_not_persisted() {
+        let mut config = revolt_config::config().await;
+        config.pushd.transient_notifications = vec!["typing".to_string(), "ack".to_string()];
+
+        for kind in NotificationKind::ALL {
+            assert_eq!(
+                is_persistent(&config, kind),
+                !matches!(kind, NotificationKind::Typing | NotificationKind::Ack),
+                "{kind:?}"
+            );
+            assert_eq!(
+                NotificationKind::from_routing_key(&config, &routing_key_for(&config, kind)),
+                Some(kind)
+            );
+        }
+
+        assert_eq!(NotificationKind::from_routing_key(&config, "unknown"), None);
+    }
+
+    #[async_std::test]
+    async fn broadcast_channels_bypass_viewer_suppression() {
+        let mut config = revolt_config::config().await;
+        config.pushd.broadcast_channels = vec!["announcements".to_string()];
+
+        let rules = |config: &revolt_config::Settings, channel_id| {
+            let rules = broadcast_rules(config, channel_id);
+            (rules.notify_viewers, rules.notify_muted)
+        };
+
+        assert_eq!(rules(&config, "announcements"), (true, false));
+        assert_eq!(rules(&config, "general"), (false, false));
+
+        config.pushd.broadcast_channels_override_mute = true;
+        assert_eq!(rules(&config, "announcements"), (true, true));
+        assert_eq!(rules(&config, "general"), (false, false));
+    }
+
+    #[test]
+    fn mute_takes_precedence_over_always_notify() {
+        let settings =
+            |user: &str, muted: bool, muted_until: Option<Timestamp>| ChannelNotificationSettings {
+                id: ChannelCompositeKey {
+                    channel: "channel".to_string(),
+                    user: user.to_string(),
+                },
+                muted,
+                muted_until,
+                notify_pins: false,
+                always_notify: true,
+            };
+
+        let now = Timestamp::now_utc();
+        let (muted, always) = notification_overrides(vec![
+            settings("always", false, None),
+            settings("muted", true, None),
+            settings("mute expired", true, now.checked_sub(Duration::hours(1))),
+        ]);
+
+        assert_eq!(muted, HashSet::from(["muted".to_string()]));
+        assert_eq!(
+            always,
+            HashSet::from(["always".to_string(), "mute expired".to_string()])
+        );
+    }
+
+    #[async_std::test]
+    async fn splits_mass_mentions_into_batches() {
+        let channel = v0::Channel::SavedMessages {
+            id: Ulid::new().to_string(),
+            user: Ulid::new().to_string(),
+        };
+        let message = Message {
+            id: Ulid::new().to_string(),
+            channel: channel.id().to_string(),
+            author: Ulid::new().to_string(),
+            ..Default::default()
+        };
+        let notification =
+            v0::PushNotification::from(message.into_model(None, None), None, channel, None).await;
+        let notifications = vec![notification; 1201];
+
+        let batches = mass_mention_batches("server".to_string(), notifications.clone(), 500);
+        assert_eq!(
+            batches
+                .iter()
+                .map(|batch| batch.notifications.len())
+                .collect::<Vec<_>>(),
+            vec![500, 500, 201]
+        );
+        assert!(batches.iter().all(|batch| batch.server_id == "server"));
+
+        assert_eq!(
+            mass_mention_batches("server".to_string(), notifications, 0).len(),
+            1
+        );
+    }
+
+    #[async_std::test]
+    async fn find_viewers_without_redis_finds_nobody() {
+        let recipients = vec![Ulid::new().to_string()];
+        let viewers = find_viewers::<Conn>(None, &recipients, "channel").await;
+
+        assert!(viewers.is_empty());
+    }
+
+    #[test]
+    fn ack_dedup_key_includes_message_only_when_asked() {
+        assert_eq!(ack_dedup_key("user", "channel", "a", false), "user-channel");
+        assert_eq!(
+            ack_dedup_key("user", "channel", "a", false),
+            ack_dedup_key("user", "channel", "b", false)
+        );
+        assert_eq!(
+            ack_dedup_key("user", "channel", "a", true),
+            "user-channel-a"
+        );
+    }
+
+    #[test]
+    fn blocked_sender_is_detected() {
+        let user = User {
+            relations: Some(vec![
+                Relationship {
+                    id: "blocked".to_string(),
+                    status: RelationshipStatus::Blocked,
+                },
+                Relationship {
+                    id: "friend".to_string(),
+                    status: RelationshipStatus::Friend,
+                },
+            ]),
+            ..Default::default()
+        };
+
+        assert!(has_blocked(&user, "blocked"));
+        assert!(!has_blocked(&user, "friend"));
+        assert!(!has_blocked(&user, "stranger"));
+    }
+
+    #[test]
+    fn snooze_only_suppresses_until_it_expires() {
+        let snoozed = |until| User {
+            notifications_snoozed_until: until,
+            ..Default::default()
+        };
+
+        let now = Timestamp::now_utc();
+        let later = now.checked_add(Duration::hours(1));
+        let earlier = now.checked_sub(Duration::hours(1));
+
+        assert!(!should_suppress_all(&snoozed(None)));
+        assert!(should_suppress_all(&snoozed(later)));
+        assert!(!should_suppress_all(&snoozed(earlier)));
+    }
+
+    #[test]
+    fn excludes_listed_system_message_types() {
+        let excluded = vec!["user_joined".to_string(), "channel_renamed".to_string()];
+        let cases = [
+            (
+                v0::SystemMessage::UserJoined {
+                    id: "user".to_string(),
+                },
+                true,
+            ),
+            (
+                v0::SystemMessage::ChannelRenamed {
+                    name: "name".to_string(),
+                    by: "user".to_string(),
+                },
+                true,
+            ),
+            (
+                v0::SystemMessage::UserLeft {
+                    id: "user".to_string(),
+                },
+                false,
+            ),
+            (
+                v0::SystemMessage::Text {
+                    content: "content".to_string(),
+                },
+                false,
+            ),
+        ];
+
+        for (system, expected) in cases {
+            let type_name = system.type_name();
+            assert_eq!(
+                is_excluded_system_message(Some(&system), &excluded),
+                expected,
+                "{type_name}"
+            );
+        }
+
+        assert!(!is_excluded_system_message(None, &excluded));
+    }
+
+    #[test]
+    fn picks_most_recently_active_session() {
+        let sessions = vec!["a".to_string(), "b".to_string(), "c".to_string()];
+        let activity = HashMap::from([
+            ("a".to_string(), 100),
+            ("b".to_string(), 300),
+            ("other".to_string(), 500),
+        ]);
+
+        assert_eq!(
+            pick_primary_session(&sessions, &activity),
+            Some("b".to_string())
+        );
+        assert_eq!(pick_primary_session(&sessions, &HashMap::new()), None);
+    }
+
+    #[test]
+    fn throttles_typing_events() {
+        let throttle = std::time::Duration::from_secs(3);
+        let started = std::time::Instant::now();
+        let publish = |last_started, is_typing, secs| {
+            let now = started + std::time::Duration::from_secs(secs);
+            should_publish_typing(last_started, is_typing, throttle, now)
+        };
+
+        assert!(publish(None, true, 0));
+        assert!(!publish(Some(started), true, 1));
+        assert!(publish(Some(started), true, 3));
+
+        assert!(publish(Some(started), false, 1));
+        assert!(!publish(None, false, 1));
     }
 }
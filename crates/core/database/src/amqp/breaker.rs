@@ -0,0 +1,179 @@
+//! Failing publishes fast while the broker is unreachable
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+/// State of the circuit breaker in front of the broker
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BreakerState {
+    /// Publishing as usual
+    Closed,
+    /// Publishes fail straight away until the cooldown is over
+    Open {
+        /// Time left until the next publish is let through to probe the broker
+        retry_after: Duration,
+    },
+    /// Cooldown is over, a single publish is let through to probe the broker
+    HalfOpen,
+}
+
+#[derive(Default)]
+struct Inner {
+    /// Publishes which failed in a row
+    failures: usize,
+    /// When the circuit was last opened, if it is open
+    opened_at: Option<Instant>,
+    /// Whether a publish probing the broker is in progress
+    probing: bool,
+}
+
+/// Stops publishing for a while after too many publishes failed in a row
+///
+/// Notifications aren't worth holding up requests for while the broker is down,
+/// so once `threshold` publishes have failed in a row, the rest fail straight away
+/// for `cooldown`. After that, one publish is let through: the circuit closes again
+/// if it goes through, and stays open for another cooldown otherwise.
+pub struct CircuitBreaker {
+    threshold: usize,
+    cooldown: Duration,
+    inner: Mutex<Inner>,
+}
+
+impl CircuitBreaker {
+    pub fn new(threshold: usize, cooldown: Duration) -> CircuitBreaker {
+        CircuitBreaker {
+            threshold: threshold.max(1),
+            cooldown,
+            inner: Default::default(),
+        }
+    }
+
+    /// Whether a publish may be attempted at the given time
+    pub fn allow(&self, now: Instant) -> bool {
+        let mut inner = self.inner.lock().unwrap();
+        let Some(opened_at) = inner.opened_at else {
+            return true;
+        };
+
+        if inner.probing || now.duration_since(opened_at) < self.cooldown {
+            return false;
+        }
+
+        inner.probing = true;
+        true
+    }
+
+    /// Record a publish going through, closing the circuit
+    pub fn record_success(&self) {
+        let mut inner = self.inner.lock().unwrap();
+        if inner.opened_at.is_some() {
+            info!("Publishing to the broker succeeded again, closing the circuit");
+        }
+
+        *inner = Inner::default();
+    }
+
+    /// Record a publish failing at the given time, opening the circuit if it failed too often
+    pub fn record_failure(&self, now: Instant) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.failures += 1;
+
+        if inner.probing || (inner.opened_at.is_none() && inner.failures >= self.threshold) {
+            warn!(
+                "{} publish(es) to the broker failed in a row, failing fast for {:?}",
+                inner.failures, self.cooldown
+            );
+
+            inner.opened_at = Some(now);
+            inner.probing = false;
+        }
+    }
+
+    /// State of the circuit at the given time
+    pub fn state(&self, now: Instant) -> BreakerState {
+        let inner = self.inner.lock().unwrap();
+        match inner.opened_at {
+            None => BreakerState::Closed,
+            Some(opened_at) => {
+                let elapsed = now.duration_since(opened_at);
+                if elapsed >= self.cooldown {
+                    BreakerState::HalfOpen
+                } else {
+                    BreakerState::Open {
+                        retry_after: self.cooldown - elapsed,
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::{BreakerState, CircuitBreaker};
+
+    const COOLDOWN: Duration = Duration::from_secs(30);
+
+    #[test]
+    fn trips_after_consecutive_failures() {
+        let breaker = CircuitBreaker::new(3, COOLDOWN);
+        let now = Instant::now();
+
+        breaker.record_failure(now);
+        breaker.record_failure(now);
+        breaker.record_success();
+        breaker.record_failure(now);
+        breaker.record_failure(now);
+        assert_eq!(breaker.state(now), BreakerState::Closed);
+        assert!(breaker.allow(now));
+
+        breaker.record_failure(now);
+        assert_eq!(
+            breaker.state(now + Duration::from_secs(10)),
+            BreakerState::Open {
+                retry_after: Duration::from_secs(20)
+            }
+        );
+        assert!(!breaker.allow(now + Duration::from_secs(10)));
+    }
+
+    #[test]
+    fn recovers_once_a_probe_goes_through() {
+        let breaker = CircuitBreaker::new(1, COOLDOWN);
+        let now = Instant::now();
+        breaker.record_failure(now);
+
+        // Only one publish probes the broker at a time
+        let later = now + COOLDOWN;
+        assert_eq!(breaker.state(later), BreakerState::HalfOpen);
+        assert!(breaker.allow(later));
+        assert!(!breaker.allow(later));
+
+        breaker.record_success();
+        assert_eq!(breaker.state(later), BreakerState::Closed);
+        assert!(breaker.allow(later));
+    }
+
+    #[test]
+    fn failed_probe_opens_the_circuit_again() {
+        let breaker = CircuitBreaker::new(1, COOLDOWN);
+        let now = Instant::now();
+        breaker.record_failure(now);
+
+        let later = now + COOLDOWN;
+        assert!(breaker.allow(later));
+        breaker.record_failure(later);
+
+        assert_eq!(
+            breaker.state(later),
+            BreakerState::Open {
+                retry_after: COOLDOWN
+            }
+        );
+        assert!(!breaker.allow(later + Duration::from_secs(1)));
+        assert!(breaker.allow(later + COOLDOWN));
+    }
+}
@@ -0,0 +1,514 @@
+use std::{collections::HashMap, num::NonZeroUsize};
+
+use async_std::sync::Mutex;
+use log::warn;
+use once_cell::sync::Lazy;
+use regex::{Captures, Regex};
+use revolt_models::v0::{Channel, File, PushNotification};
+use revolt_result::{Error, ErrorType, Result};
+
+use crate::events::rabbit::REDACTED_BODY;
+use crate::Database;
+
+/// Body shown in place of message content containing spoilers
+pub static SPOILER_BODY: &str = "(스포일러)";
+
+/// Body shown for a single attachment sent without any content
+pub static ATTACHMENT_BODY: &str = "(파일)";
+
+/// Shown in place of custom emoji which no longer exist
+pub static UNKNOWN_EMOJI: &str = "❔";
+
+static RE_CUSTOM_EMOJI: Lazy<Regex> =
+    Lazy::new(|| Regex::new(":([0-9A-HJKMNP-TV-Z]{26}):").unwrap());
+
+/// Names of recently seen custom emoji, `None` if the emoji doesn't exist
+static EMOJI_NAMES: Lazy<Mutex<lru::LruCache<String, Option<String>>>> =
+    Lazy::new(|| Mutex::new(lru::LruCache::new(NonZeroUsize::new(1000).unwrap())));
+
+/// Check whether text contains a spoiler block
+fn contains_spoiler(text: &str) -> bool {
+    (text.contains("[[") || text.contains("\\[\\["))
+        && (text.contains("]]") || text.contains("\\]\\]"))
+}
+
+/// Truncate text to at most `max` characters, appending an ellipsis if anything was cut
+pub fn truncate_text(text: &str, max: usize) -> String {
+    if text.chars().count() <= max {
+        return text.to_string();
+    }
+
+    let mut truncated: String = text.chars().take(max).collect();
+    truncated.push('…');
+    truncated
+}
+
+/// Scrub spoilers from and truncate text shown outside of its message
+pub fn snippet(text: &str, max: usize) -> String {
+    if contains_spoiler(text) {
+        SPOILER_BODY.to_string()
+    } else {
+        truncate_text(text, max)
+    }
+}
+
+/// Whether spoilers should be masked in notifications from a channel
+pub fn masks_spoilers(channel: &Channel) -> bool {
+    !matches!(
+        channel,
+        Channel::TextChannel {
+            unmask_spoilers: true,
+            ..
+        }
+    )
+}
+
+/// Truncate text shown in a notification, masking spoilers unless the channel allows them
+fn notification_text(channel: &Channel, text: &str, max: usize) -> String {
+    if masks_spoilers(channel) {
+        snippet(text, max)
+    } else {
+        truncate_text(text, max)
+    }
+}
+
+/// Replace custom emoji in text with their names, using the given lookup
+fn render_custom_emoji(text: &str, names: &HashMap<String, Option<String>>) -> String {
+    RE_CUSTOM_EMOJI
+        .replace_all(text, |captures: &Captures| match names.get(&captures[1]) {
+            Some(Some(name)) => format!(":{name}:"),
+            Some(None) => UNKNOWN_EMOJI.to_string(),
+            None => captures[0].to_string(),
+        })
+        .into_owned()
+}
+
+/// Replace custom emoji in text with their names
+///
+/// Emoji which don't exist are shown as [`UNKNOWN_EMOJI`], any other lookup failure is an error.
+pub async fn resolve_custom_emoji(db: &Database, text: &str) -> Result<String> {
+    let mut names = HashMap::new();
+    for captures in RE_CUSTOM_EMOJI.captures_iter(text) {
+        let id = &captures[1];
+        if names.contains_key(id) {
+            continue;
+        }
+
+        let cached = EMOJI_NAMES.lock().await.get(id).cloned();
+        let name = match cached {
+            Some(name) => name,
+            None => {
+                let name = match db.fetch_emoji(id).await {
+                    Ok(emoji) => Some(emoji.name),
+                    Err(Error {
+                        error_type: ErrorType::NotFound,
+                        ..
+                    }) => None,
+                    Err(error) => return Err(error),
+                };
+                EMOJI_NAMES.lock().await.put(id.to_string(), name.clone());
+                name
+            }
+        };
+
+        names.insert(id.to_string(), name);
+    }
+
+    Ok(if names.is_empty() {
+        text.to_string()
+    } else {
+        render_custom_emoji(text, &names)
+    })
+}
+
+/// Summarise attachments sent without any content, such as `(파일 3개) photo.png`
+///
+/// Names the first attachment which isn't a spoiler, if there is one.
+/// Returns `None` if there are no attachments.
+pub fn attachment_summary(attachments: &[File]) -> Option<String> {
+    let label = match attachments.len() {
+        0 => return None,
+        1 => ATTACHMENT_BODY.to_string(),
+        count => format!("(파일 {count}개)"),
+    };
+
+    Some(match attachments.iter().find(|file| !file.is_spoiler()) {
+        Some(file) => format!("{label} {}", file.filename),
+        None => label,
+    })
+}
+
+/// Body shown when a notification couldn't be formatted, such as `#general에 새 메시지`
+pub fn fallback_body(channel: &Channel) -> String {
+    match channel {
+        Channel::Group { name, .. }
+        | Channel::TextChannel { name, .. }
+        | Channel::VoiceChannel { name, .. } => format!("#{name}에 새 메시지"),
+        _ => REDACTED_BODY.to_string(),
+    }
+}
+
+/// Format the body and message content of a notification
+async fn format_body(
+    db: &Database,
+    notification: &PushNotification,
+    max_body_length: usize,
+) -> Result<(String, Option<String>)> {
+    let message = &notification.message;
+    let mut body = notification.body.clone();
+    if message.system.is_none()
+        && message
+            .content
+            .as_deref()
+            .map_or(true, |content| content.trim().is_empty())
+    {
+        if let Some(attachments) = message.attachments.as_deref() {
+            if let Some(summary) = attachment_summary(attachments) {
+                body = summary;
+            }
+        }
+    }
+
+    let body = resolve_custom_emoji(db, &body).await?;
+    let body = notification_text(&notification.channel, &body, max_body_length);
+
+    let content = match message.content {
+        Some(ref content) => {
+            let content = resolve_custom_emoji(db, content).await?;
+            Some(notification_text(
+                &notification.channel,
+                &content,
+                max_body_length,
+            ))
+        }
+        None => None,
+    };
+
+    Ok((body, content))
+}
+
+/// How much of a message to show in its notification
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BodyRule {
+    /// Show the content as usual
+    Content,
+    /// Show a generic body in place of the content
+    Generic,
+    /// Don't notify anyone
+    Suppress,
+}
+
+/// Decide how to show a message based on how much content it has
+///
+/// Content shorter than `min_length` characters is replaced with a generic body.
+/// Messages with attachments but no content are suppressed if `suppress_attachment_only`
+/// is set, and shown as usual otherwise.
+pub fn body_rule(
+    content: Option<&str>,
+    attachments: usize,
+    min_length: usize,
+    suppress_attachment_only: bool,
+) -> BodyRule {
+    let length = content.map_or(0, |content| content.trim().chars().count());
+    if length == 0 {
+        if attachments > 0 && suppress_attachment_only {
+            BodyRule::Suppress
+        } else {
+            BodyRule::Content
+        }
+    } else if length < min_length {
+        BodyRule::Generic
+    } else {
+        BodyRule::Content
+    }
+}
+
+/// Apply [`body_rule`] to a notification, returning the rule which applied
+///
+/// Content replaced by a generic body is dropped from the message as well.
+pub fn apply_body_rule(
+    notification: &mut PushNotification,
+    min_length: usize,
+    suppress_attachment_only: bool,
+) -> BodyRule {
+    let rule = body_rule(
+        notification.message.content.as_deref(),
+        notification
+            .message
+            .attachments
+            .as_ref()
+            .map_or(0, Vec::len),
+        min_length,
+        suppress_attachment_only,
+    );
+
+    if rule == BodyRule::Generic {
+        notification.body = REDACTED_BODY.to_string();
+        notification.message.content = None;
+    }
+
+    rule
+}
+
+/// Prepare a notification for display before it is sent
+///
+/// Messages with only attachments are summarised by how many files were shared.
+/// Custom emoji are resolved to their names, then spoilers are masked and the body truncated.
+/// Spoilers are left as they are in channels which allow them.
+///
+/// If the message can't be formatted, such as when emoji can't be looked up,
+/// a generic body is shown and the message content is dropped.
+pub async fn format_notification(
+    db: &Database,
+    notification: &mut PushNotification,
+    max_body_length: usize,
+) {
+    let formatted = format_body(db, notification, max_body_length).await;
+    apply_formatted(notification, formatted);
+}
+
+/// Use the formatted body and content, or fall back to a generic body if formatting failed
+fn apply_formatted(
+    notification: &mut PushNotification,
+    formatted: Result<(String, Option<String>)>,
+) {
+    match formatted {
+        Ok((body, content)) => {
+            notification.body = body;
+            notification.message.content = content;
+        }
+        Err(error) => {
+            warn!(
+                "Failed to format notification for message {}: {error:?}",
+                notification.message.id
+            );
+
+            notification.body = fallback_body(&notification.channel);
+            notification.message.content = None;
+        }
+    }
+}
+
+/// Title displayed alongside a message notification
+pub fn notification_title(notification: &PushNotification) -> String {
+    // ideally this changes depending on context
+    // in a server, it would look like "Sendername, #channelname in servername"
+    // in a group, it would look like "Sendername in groupname"
+    // in a dm it should just be "Sendername".
+    // not sure how feasible all those are given the PushNotification object as it currently stands.
+
+    match &notification.channel {
+        Channel::DirectMessage { .. } => notification.author.clone(),
+        Channel::Group { name, .. } => format!("{}, #{}", notification.author, name),
+        Channel::TextChannel { name, .. } | Channel::VoiceChannel { name, .. } => {
+            format!("{} in #{}", notification.author, name)
+        }
+        _ => "Unknown".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use revolt_models::v0::{self, Channel, File};
+    use ulid::Ulid;
+
+    use super::{
+        apply_body_rule, attachment_summary, body_rule, contains_spoiler, fallback_body,
+        notification_text, render_custom_emoji, snippet, truncate_text, BodyRule, ATTACHMENT_BODY,
+        SPOILER_BODY, UNKNOWN_EMOJI,
+    };
+    use crate::events::rabbit::REDACTED_BODY;
+    use crate::Message;
+
+    fn text_channel(unmask_spoilers: bool) -> Channel {
+        Channel::TextChannel {
+            id: "channel".to_string(),
+            server: "server".to_string(),
+            name: "spoilers".to_string(),
+            description: None,
+            icon: None,
+            last_message_id: None,
+            default_permissions: None,
+            role_permissions: HashMap::new(),
+            nsfw: false,
+            unmask_spoilers,
+        }
+    }
+
+    fn file(filename: &str) -> File {
+        File {
+            id: filename.to_string(),
+            tag: "attachments".to_string(),
+            filename: filename.to_string(),
+            content_type: "application/octet-stream".to_string(),
+            size: 1024,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn truncate_text_keeps_short_text() {
+        assert_eq!(truncate_text("hello", 5), "hello");
+        assert_eq!(truncate_text("", 5), "");
+    }
+
+    #[test]
+    fn truncate_text_cuts_on_character_boundary() {
+        assert_eq!(truncate_text("hello world", 5), "hello…");
+        assert_eq!(truncate_text("스포일러입니다", 4), "스포일러…");
+    }
+
+    #[test]
+    fn detects_spoilers() {
+        assert!(contains_spoiler("look [[here]]"));
+        assert!(contains_spoiler("escaped \\[\\[here\\]\\]"));
+        assert!(!contains_spoiler("nothing [to] see"));
+    }
+
+    #[test]
+    fn snippet_masks_spoilers_before_truncating() {
+        assert_eq!(snippet("a long [[secret]] message", 4), SPOILER_BODY);
+        assert_eq!(snippet("a long message", 4), "a lo…");
+    }
+
+    #[test]
+    fn spoilers_allowed_channel_sends_unmasked_text() {
+        let text = "the [[butler]] did it";
+
+        assert_eq!(
+            notification_text(&text_channel(false), text, 100),
+            SPOILER_BODY
+        );
+        assert_eq!(notification_text(&text_channel(true), text, 100), text);
+    }
+
+    #[test]
+    fn renders_custom_emoji_names() {
+        let names = HashMap::from([
+            (
+                "01GX773A8JPQ0VP64NWGEBMQ1E".to_string(),
+                Some("party".to_string()),
+            ),
+            ("01GX773A8JPQ0VP64NWGEBMQ1F".to_string(), None),
+        ]);
+
+        assert_eq!(
+            render_custom_emoji(
+                "hi :01GX773A8JPQ0VP64NWGEBMQ1E: :01GX773A8JPQ0VP64NWGEBMQ1F: :smile: 🎉",
+                &names
+            ),
+            format!("hi :party: {UNKNOWN_EMOJI} :smile: 🎉")
+        );
+    }
+
+    #[test]
+    fn summarises_a_single_attachment() {
+        assert_eq!(attachment_summary(&[]), None);
+        assert_eq!(
+            attachment_summary(&[file("photo.png")]).unwrap(),
+            format!("{ATTACHMENT_BODY} photo.png")
+        );
+        assert_eq!(
+            attachment_summary(&[file("SPOILER_photo.png")]).unwrap(),
+            ATTACHMENT_BODY
+        );
+    }
+
+    #[test]
+    fn summarises_multiple_attachments() {
+        assert_eq!(
+            attachment_summary(&[file("SPOILER_a.png"), file("b.png"), file("c.png")]).unwrap(),
+            "(파일 3개) b.png"
+        );
+        assert_eq!(
+            attachment_summary(&[file("SPOILER_a.png"), file("SPOILER_b.png")]).unwrap(),
+            "(파일 2개)"
+        );
+    }
+
+    #[test]
+    fn short_messages_get_a_generic_body() {
+        assert_eq!(body_rule(Some("hello"), 0, 0, false), BodyRule::Content);
+        assert_eq!(body_rule(Some("k"), 0, 0, false), BodyRule::Content);
+        assert_eq!(body_rule(Some("k"), 0, 3, false), BodyRule::Generic);
+        assert_eq!(body_rule(Some(" 👍 "), 0, 3, false), BodyRule::Generic);
+        assert_eq!(body_rule(Some("hey"), 0, 3, false), BodyRule::Content);
+
+        // System messages have no content or attachments
+        assert_eq!(body_rule(None, 0, 3, true), BodyRule::Content);
+    }
+
+    #[test]
+    fn attachment_only_messages_are_suppressed_if_configured() {
+        assert_eq!(body_rule(None, 2, 3, false), BodyRule::Content);
+        assert_eq!(body_rule(Some(""), 1, 3, true), BodyRule::Suppress);
+        assert_eq!(body_rule(Some("look"), 1, 3, true), BodyRule::Content);
+    }
+
+    #[async_std::test]
+    async fn short_messages_lose_their_content() {
+        let channel = text_channel(false);
+        let message = Message {
+            id: Ulid::new().to_string(),
+            channel: channel.id().to_string(),
+            author: Ulid::new().to_string(),
+            content: Some("k".to_string()),
+            ..Default::default()
+        };
+
+        let mut notification =
+            v0::PushNotification::from(message.into_model(None, None), None, channel, None).await;
+
+        assert_eq!(
+            apply_body_rule(&mut notification, 3, false),
+            BodyRule::Generic
+        );
+        assert_eq!(notification.body, REDACTED_BODY);
+        assert_eq!(notification.message.content, None);
+    }
+
+    #[cfg(feature = "mongodb")]
+    #[async_std::test]
+    async fn falls_back_to_generic_body_if_emoji_lookup_fails() {
+        use super::format_notification;
+
+        database_test!(|db| async move {
+            // Only MongoDB can hold an emoji which fails to load
+            let crate::Database::MongoDb(mongo) = &db else {
+                return;
+            };
+
+            let emoji_id = Ulid::new().to_string();
+            mongo
+                .col::<bson::Document>("emojis")
+                .insert_one(bson::doc! { "_id": &emoji_id, "name": 42 })
+                .await
+                .unwrap();
+
+            let channel = text_channel(false);
+            let message = Message {
+                id: Ulid::new().to_string(),
+                channel: channel.id().to_string(),
+                author: Ulid::new().to_string(),
+                content: Some(format!("nice :{emoji_id}:")),
+                ..Default::default()
+            };
+
+            let mut notification = v0::PushNotification::from(
+                message.into_model(None, None),
+                None,
+                channel.clone(),
+                None,
+            )
+            .await;
+
+            format_notification(&db, &mut notification, 100).await;
+
+            assert_eq!(notification.body, "#spoilers에 새 메시지");
+            assert_eq!(notification.body, fallback_body(&channel));
+            assert_eq!(notification.message.content, None);
+        });
+    }
+}
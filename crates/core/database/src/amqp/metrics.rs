@@ -0,0 +1,102 @@
+//! Counters describing what happens to outgoing notifications and channel activity
+//!
+//! These are exported through whichever Prometheus registry is passed to [`register`].
+use once_cell::sync::Lazy;
+use prometheus::{IntCounter, IntCounterVec, Opts, Registry};
+
+/// Recipients dropped because they were viewing the channel
+static NOTIFICATIONS_SUPPRESSED_VIEWING: Lazy<IntCounter> = Lazy::new(|| {
+    IntCounter::new(
+        "notifications_suppressed_viewing",
+        "Recipients dropped because they were viewing the channel",
+    )
+    .expect("valid metric")
+});
+
+/// Recipients a message notification was published for
+static NOTIFICATIONS_PUBLISHED: Lazy<IntCounter> = Lazy::new(|| {
+    IntCounter::new(
+        "notifications_published",
+        "Recipients a message notification was published for",
+    )
+    .expect("valid metric")
+});
+
+/// Failed attempts at removing duplicate push subscriptions
+static PUSH_DEDUP_FAILURES: Lazy<IntCounter> = Lazy::new(|| {
+    IntCounter::new(
+        "push_dedup_failures",
+        "Failed attempts at removing duplicate push subscriptions",
+    )
+    .expect("valid metric")
+});
+
+/// Channels opened by sessions, by channel type
+static CHANNELS_OPENED: Lazy<IntCounterVec> = Lazy::new(|| {
+    IntCounterVec::new(
+        Opts::new("channels_opened", "Channels opened by sessions"),
+        &["channel_type"],
+    )
+    .expect("valid metric")
+});
+
+/// Channels closed by sessions, by channel type
+///
+/// Channels which lapse after their TTL aren't counted, as Redis expires them silently.
+static CHANNELS_CLOSED: Lazy<IntCounterVec> = Lazy::new(|| {
+    IntCounterVec::new(
+        Opts::new("channels_closed", "Channels closed by sessions"),
+        &["channel_type"],
+    )
+    .expect("valid metric")
+});
+
+/// Channels closed to make room for another, as a session had too many open
+static CHANNELS_EVICTED: Lazy<IntCounter> = Lazy::new(|| {
+    IntCounter::new(
+        "channels_evicted",
+        "Channels closed to make room for another, as a session had too many open",
+    )
+    .expect("valid metric")
+});
+
+/// Register the counters with a Prometheus registry so they can be scraped
+pub fn register(registry: &Registry) -> prometheus::Result<()> {
+    registry.register(Box::new(NOTIFICATIONS_SUPPRESSED_VIEWING.clone()))?;
+    registry.register(Box::new(NOTIFICATIONS_PUBLISHED.clone()))?;
+    registry.register(Box::new(PUSH_DEDUP_FAILURES.clone()))?;
+    registry.register(Box::new(CHANNELS_OPENED.clone()))?;
+    registry.register(Box::new(CHANNELS_CLOSED.clone()))?;
+    registry.register(Box::new(CHANNELS_EVICTED.clone()))?;
+    Ok(())
+}
+
+/// Record recipients that were dropped because they were viewing the channel
+pub fn record_suppressed_viewing(count: usize) {
+    NOTIFICATIONS_SUPPRESSED_VIEWING.inc_by(count as u64);
+}
+
+/// Record recipients that a notification was published for
+pub fn record_published(count: usize) {
+    NOTIFICATIONS_PUBLISHED.inc_by(count as u64);
+}
+
+/// Record a failure to remove duplicate push subscriptions
+pub fn record_push_dedup_failure() {
+    PUSH_DEDUP_FAILURES.inc();
+}
+
+/// Record a session opening a channel of the given type
+pub fn record_channel_opened(channel_type: &'static str) {
+    CHANNELS_OPENED.with_label_values(&[channel_type]).inc();
+}
+
+/// Record a session closing a channel of the given type
+pub fn record_channel_closed(channel_type: &'static str) {
+    CHANNELS_CLOSED.with_label_values(&[channel_type]).inc();
+}
+
+/// Record a channel being closed to make room for another
+pub fn record_channel_evicted() {
+    CHANNELS_EVICTED.inc();
+}
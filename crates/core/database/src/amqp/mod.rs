@@ -1,2 +1,8 @@
 #[allow(clippy::module_inception)]
 pub mod amqp;
+pub mod breaker;
+pub mod format;
+pub mod metrics;
+pub mod notifier;
+pub mod outbox;
+pub mod publish;
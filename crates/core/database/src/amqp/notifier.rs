@@ -0,0 +1,236 @@
+//! Transports which deliver notification payloads to pushd
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::RwLock;
+use std::time::Duration;
+
+use amqprs::channel::{BasicPublishArguments, Channel};
+use amqprs::connection::Connection;
+use amqprs::error::Error as AMQPError;
+use amqprs::{BasicProperties, FieldName, FieldValue};
+use async_trait::async_trait;
+use isahc::{config::Configurable, AsyncReadResponseExt, Request, RequestExt};
+
+use super::publish::publish_with_retries;
+
+/// Base delay between attempts at publishing a message
+const PUBLISH_RETRY_BACKOFF: Duration = Duration::from_millis(100);
+
+/// Headers carried over from message properties to HTTP requests
+const FORWARDED_HEADERS: [&str; 2] = ["x-trace-id", "x-deduplication-header"];
+
+/// Pick the next index out of `len` in round-robin order
+fn next_index(counter: &AtomicUsize, len: usize) -> usize {
+    counter.fetch_add(1, Ordering::Relaxed) % len
+}
+
+/// Transport used to hand notification payloads over to pushd
+#[async_trait]
+pub trait Notifier: Send + Sync {
+    /// Deliver a serialised payload under the given routing key
+    async fn publish(
+        &self,
+        routing_key: &str,
+        properties: BasicProperties,
+        content: Vec<u8>,
+    ) -> Result<(), AMQPError>;
+
+    /// Release any connections held by the transport
+    async fn close(&self);
+}
+
+/// Publishes payloads to the pushd exchange on RabbitMQ
+pub struct RabbitNotifier {
+    connection: Connection,
+    channels: Vec<RwLock<Channel>>,
+    next_channel: AtomicUsize,
+}
+
+impl RabbitNotifier {
+    /// Create a notifier which spreads publishes across a pool of channels
+    pub fn new(connection: Connection, channels: Vec<Channel>) -> RabbitNotifier {
+        assert!(!channels.is_empty(), "at least one channel is required");
+
+        RabbitNotifier {
+            connection,
+            channels: channels.into_iter().map(RwLock::new).collect(),
+            next_channel: AtomicUsize::new(0),
+        }
+    }
+
+    /// Replace a channel in the pool with a new one on the same connection
+    async fn reopen_channel(&self, index: usize) -> Result<(), AMQPError> {
+        let channel = self.connection.open_channel(None).await?;
+        *self.channels[index].write().unwrap() = channel;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Notifier for RabbitNotifier {
+    /// Publish on the next channel in the pool, retrying up to `pushd.publish_retries` times
+    ///
+    /// Each attempt goes out on the channel that was selected here. If the channel fails,
+    /// it is reopened before trying again.
+    async fn publish(
+        &self,
+        routing_key: &str,
+        properties: BasicProperties,
+        content: Vec<u8>,
+    ) -> Result<(), AMQPError> {
+        let config = revolt_config::config().await;
+        let index = next_index(&self.next_channel, self.channels.len());
+        let args = BasicPublishArguments::new(&config.pushd.exchange, routing_key);
+
+        publish_with_retries(
+            config.pushd.publish_retries,
+            PUBLISH_RETRY_BACKOFF,
+            || {
+                let channel = self.channels[index].read().unwrap().clone();
+                let (properties, content, args) =
+                    (properties.clone(), content.clone(), args.clone());
+                async move { channel.basic_publish(properties, content, args).await }
+            },
+            || self.reopen_channel(index),
+        )
+        .await
+    }
+
+    async fn close(&self) {
+        for channel in self.channels.iter() {
+            let channel = channel.read().unwrap().clone();
+            if let Err(err) = channel.close().await {
+                warn!("Failed to close AMQP channel: {err:?}");
+            }
+        }
+
+        if let Err(err) = self.connection.clone().close().await {
+            warn!("Failed to close AMQP connection: {err:?}");
+        }
+    }
+}
+
+/// Posts payloads to an HTTP endpoint, for deployments without RabbitMQ
+///
+/// Each payload is sent as JSON to `{endpoint}/{routing_key}`.
+pub struct HttpNotifier {
+    endpoint: String,
+    timeout: Duration,
+}
+
+impl HttpNotifier {
+    pub fn new(endpoint: String, timeout: Duration) -> HttpNotifier {
+        HttpNotifier {
+            endpoint: endpoint.trim_end_matches('/').to_string(),
+            timeout,
+        }
+    }
+
+    /// Make a single attempt at posting a payload
+    async fn post(
+        &self,
+        routing_key: &str,
+        properties: &BasicProperties,
+        content: Vec<u8>,
+    ) -> Result<(), AMQPError> {
+        let mut request = Request::post(format!("{}/{}", self.endpoint, routing_key))
+            .header("content-type", "application/json")
+            .timeout(self.timeout);
+
+        for name in FORWARDED_HEADERS {
+            if let Some(value) = header(properties, name) {
+                request = request.header(name, value);
+            }
+        }
+
+        let mut response = request
+            .body(content)
+            .map_err(|err| AMQPError::NetworkError(err.to_string()))?
+            .send_async()
+            .await
+            .map_err(|err| AMQPError::NetworkError(err.to_string()))?;
+
+        if response.status().is_success() {
+            Ok(())
+        } else {
+            let body = response.text().await.unwrap_or_default();
+            Err(AMQPError::NetworkError(format!(
+                "notification endpoint responded with {}: {body}",
+                response.status()
+            )))
+        }
+    }
+}
+
+#[async_trait]
+impl Notifier for HttpNotifier {
+    /// Post the payload, retrying up to `pushd.publish_retries` times
+    async fn publish(
+        &self,
+        routing_key: &str,
+        properties: BasicProperties,
+        content: Vec<u8>,
+    ) -> Result<(), AMQPError> {
+        let config = revolt_config::config().await;
+
+        let mut attempt = 0;
+        loop {
+            match self.post(routing_key, &properties, content.clone()).await {
+                Err(err) if attempt < config.pushd.publish_retries => {
+                    attempt += 1;
+                    warn!("Posting notification failed on attempt {attempt}, retrying: {err:?}");
+                    async_std::task::sleep(PUBLISH_RETRY_BACKOFF * attempt as u32).await;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    async fn close(&self) {}
+}
+
+/// Read a string header from message properties
+fn header(properties: &BasicProperties, name: &str) -> Option<String> {
+    let key: FieldName = name.try_into().ok()?;
+    match properties.headers()?.get(&key)? {
+        FieldValue::S(value) => Some(String::from(value.clone())),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::Arc;
+
+    use super::next_index;
+
+    #[test]
+    fn next_index_spreads_concurrent_publishes_evenly() {
+        const THREADS: usize = 8;
+        const PICKS: usize = 1000;
+        const POOL: usize = 4;
+
+        let counter = Arc::new(AtomicUsize::new(0));
+        let handles: Vec<_> = (0..THREADS)
+            .map(|_| {
+                let counter = counter.clone();
+                std::thread::spawn(move || {
+                    let mut picks = [0usize; POOL];
+                    for _ in 0..PICKS {
+                        picks[next_index(&counter, POOL)] += 1;
+                    }
+                    picks
+                })
+            })
+            .collect();
+
+        let mut totals = [0usize; POOL];
+        for handle in handles {
+            for (total, picks) in totals.iter_mut().zip(handle.join().unwrap()) {
+                *total += picks;
+            }
+        }
+
+        assert_eq!(totals, [THREADS * PICKS / POOL; POOL]);
+    }
+}
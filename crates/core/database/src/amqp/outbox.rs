@@ -0,0 +1,178 @@
+//! Notifications written down before publishing, so they survive a failed publish or a crash
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use redis_kiss::{redis::Script, AsyncCommands};
+use revolt_result::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::util::retry::redis_connection;
+
+/// Sorted set of outbox entry ids, scored by the unix time they were written or last claimed at
+static OUTBOX_KEY: &str = "notification_outbox";
+
+/// Hash of outbox entries by their id
+static OUTBOX_ENTRIES_KEY: &str = "notification_outbox_entries";
+
+/// Notification which has not been confirmed as published yet
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct OutboxEntry {
+    pub id: String,
+    pub routing_key: String,
+    pub content: String,
+    pub created_at: u64,
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("time went backwards")
+        .as_secs()
+}
+
+/// Claim an entry if its score is still the one it was fetched with
+///
+/// Bumping the score to the time of the claim leases the entry to the claimant,
+/// so other workers leave it alone until it is due for another retry.
+static CLAIM_SCRIPT: &str = r#"
+local score = redis.call('ZSCORE', KEYS[1], ARGV[1])
+if score and tonumber(score) == tonumber(ARGV[2]) then
+    redis.call('ZADD', KEYS[1], ARGV[3], ARGV[1])
+    return 1
+end
+return 0
+"#;
+
+/// Write a notification to the outbox, returning the id of its entry
+pub async fn persist(routing_key: &str, content: &[u8]) -> Result<String> {
+    let entry = OutboxEntry {
+        id: ulid::Ulid::new().to_string(),
+        routing_key: routing_key.to_string(),
+        content: String::from_utf8_lossy(content).into_owned(),
+        created_at: now(),
+    };
+
+    write(&entry).await?;
+    Ok(entry.id)
+}
+
+/// Write an entry to the outbox, due for a retry once it is old enough
+async fn write(entry: &OutboxEntry) -> Result<()> {
+    let mut conn = redis_connection().await?;
+
+    let _: () = conn
+        .hset(
+            OUTBOX_ENTRIES_KEY,
+            &entry.id,
+            serde_json::to_string(&entry).unwrap(),
+        )
+        .await
+        .map_err(|_| create_database_error!("hset", OUTBOX_ENTRIES_KEY))?;
+
+    let _: () = conn
+        .zadd(OUTBOX_KEY, &entry.id, entry.created_at)
+        .await
+        .map_err(|_| create_database_error!("zadd", OUTBOX_KEY))?;
+
+    Ok(())
+}
+
+/// Remove an entry once its notification has been published
+pub async fn mark_sent(id: &str) -> Result<()> {
+    let mut conn = redis_connection().await?;
+
+    let _: () = conn
+        .zrem(OUTBOX_KEY, id)
+        .await
+        .map_err(|_| create_database_error!("zrem", OUTBOX_KEY))?;
+
+    conn.hdel::<_, _, ()>(OUTBOX_ENTRIES_KEY, id)
+        .await
+        .map_err(|_| create_database_error!("hdel", OUTBOX_ENTRIES_KEY))
+}
+
+/// Claim up to `limit` of the oldest entries written or last claimed at least `min_age` seconds ago
+///
+/// Younger entries may still be in the middle of being published. Each entry is
+/// claimed by a single worker, which has `min_age` seconds to publish it before
+/// it can be claimed again.
+pub async fn claim_unsent(min_age: u64, limit: isize) -> Result<Vec<OutboxEntry>> {
+    let mut conn = redis_connection().await?;
+
+    let due: Vec<(String, u64)> = conn
+        .zrangebyscore_limit_withscores(OUTBOX_KEY, 0, now().saturating_sub(min_age), 0, limit)
+        .await
+        .map_err(|_| create_database_error!("zrangebyscore", OUTBOX_KEY))?;
+
+    let script = Script::new(CLAIM_SCRIPT);
+    let mut entries = vec![];
+    for (id, score) in due {
+        let claimed: bool = script
+            .key(OUTBOX_KEY)
+            .arg(&id)
+            .arg(score)
+            .arg(now())
+            .invoke_async(&mut conn)
+            .await
+            .map_err(|_| create_database_error!("eval", OUTBOX_KEY))?;
+
+        // Another worker got to it first
+        if !claimed {
+            continue;
+        }
+
+        let entry: Option<String> = conn
+            .hget(OUTBOX_ENTRIES_KEY, &id)
+            .await
+            .map_err(|_| create_database_error!("hget", OUTBOX_ENTRIES_KEY))?;
+
+        match entry.and_then(|entry| serde_json::from_str(&entry).ok()) {
+            Some(entry) => entries.push(entry),
+            // Entry is gone or unreadable, so there's nothing left to retry
+            None => mark_sent(&id).await?,
+        }
+    }
+
+    Ok(entries)
+}
+
+/// Whether an entry has been waiting for longer than `max_age` seconds
+pub fn is_expired(entry: &OutboxEntry, max_age: u64) -> bool {
+    now().saturating_sub(entry.created_at) > max_age
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{claim_unsent, is_expired, mark_sent, now, write, OutboxEntry};
+
+    #[async_std::test]
+    async fn entries_are_claimed_once_until_due_again() {
+        let entry = OutboxEntry {
+            id: ulid::Ulid::new().to_string(),
+            routing_key: "key".to_string(),
+            content: "{}".to_string(),
+            created_at: now() - 120,
+        };
+        write(&entry).await.unwrap();
+
+        // Two workers polling at once only republish the entry once
+        let first = claim_unsent(60, 1000).await.unwrap();
+        let second = claim_unsent(60, 1000).await.unwrap();
+        mark_sent(&entry.id).await.unwrap();
+
+        assert!(first.contains(&entry));
+        assert!(!second.contains(&entry));
+    }
+
+    #[test]
+    fn entries_expire_after_max_age() {
+        let entry = |age: u64| OutboxEntry {
+            id: "id".to_string(),
+            routing_key: "key".to_string(),
+            content: "{}".to_string(),
+            created_at: now() - age,
+        };
+
+        assert!(!is_expired(&entry(10), 60));
+        assert!(is_expired(&entry(120), 60));
+    }
+}
@@ -0,0 +1,114 @@
+//! Retrying publishes which did not go through
+use std::{future::Future, time::Duration};
+
+use amqprs::error::Error as AMQPError;
+
+/// Publish a message, retrying up to `retries` more times if it does not go through
+///
+/// Publishes are not confirmed by the broker, so a failure always means the channel
+/// failed, and it is reopened before trying again. Waits `backoff` multiplied by the
+/// attempt number between tries.
+pub async fn publish_with_retries<P, PFut, R, RFut>(
+    retries: usize,
+    backoff: Duration,
+    mut publish: P,
+    mut reopen: R,
+) -> Result<(), AMQPError>
+where
+    P: FnMut() -> PFut,
+    PFut: Future<Output = Result<(), AMQPError>>,
+    R: FnMut() -> RFut,
+    RFut: Future<Output = Result<(), AMQPError>>,
+{
+    let mut attempt = 0;
+    loop {
+        let err = match publish().await {
+            Ok(()) => return Ok(()),
+            Err(err) if attempt >= retries => return Err(err),
+            Err(err) => err,
+        };
+
+        attempt += 1;
+        warn!("Publish failed on attempt {attempt}, retrying: {err:?}");
+        async_std::task::sleep(backoff * attempt as u32).await;
+
+        if let Err(err) = reopen().await {
+            warn!("Failed to reopen AMQP channel: {err:?}");
+            return Err(err);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::VecDeque,
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Mutex,
+        },
+        time::Duration,
+    };
+
+    use amqprs::error::Error as AMQPError;
+
+    use super::publish_with_retries;
+
+    /// Channel which fails in a predetermined way before accepting messages
+    struct FlakyChannel {
+        failures: Mutex<VecDeque<AMQPError>>,
+        publishes: AtomicUsize,
+        reopens: AtomicUsize,
+    }
+
+    impl FlakyChannel {
+        fn new(failures: Vec<AMQPError>) -> FlakyChannel {
+            FlakyChannel {
+                failures: Mutex::new(failures.into()),
+                publishes: AtomicUsize::new(0),
+                reopens: AtomicUsize::new(0),
+            }
+        }
+
+        async fn publish(&self) -> Result<(), AMQPError> {
+            self.publishes.fetch_add(1, Ordering::SeqCst);
+            match self.failures.lock().unwrap().pop_front() {
+                Some(failure) => Err(failure),
+                None => Ok(()),
+            }
+        }
+
+        async fn reopen(&self) -> Result<(), AMQPError> {
+            self.reopens.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        }
+
+        async fn publish_with_retries(&self, retries: usize) -> Result<(), AMQPError> {
+            publish_with_retries(retries, Duration::ZERO, || self.publish(), || self.reopen()).await
+        }
+    }
+
+    fn channel_error() -> AMQPError {
+        AMQPError::ChannelUseError("closed".to_string())
+    }
+
+    #[async_std::test]
+    async fn reopens_channel_after_channel_error() {
+        let channel = FlakyChannel::new(vec![channel_error()]);
+        let result = channel.publish_with_retries(3).await;
+
+        assert!(result.is_ok());
+        assert_eq!(channel.publishes.load(Ordering::SeqCst), 2);
+        assert_eq!(channel.reopens.load(Ordering::SeqCst), 1);
+    }
+
+    #[async_std::test]
+    async fn gives_up_after_retries() {
+        let channel = FlakyChannel::new(vec![channel_error(), channel_error(), channel_error()]);
+        let result = channel.publish_with_retries(2).await;
+
+        assert!(matches!(result, Err(AMQPError::ChannelUseError(_))));
+        assert_eq!(channel.publishes.load(Ordering::SeqCst), 3);
+        assert_eq!(channel.reopens.load(Ordering::SeqCst), 2);
+    }
+}
@@ -3,9 +3,9 @@ use std::{collections::HashMap, sync::Arc};
 use futures::lock::Mutex;
 
 use crate::{
-    Bot, Channel, ChannelCompositeKey, ChannelUnread, Emoji, File, FileHash, Invite, Member,
-    MemberCompositeKey, Message, PolicyChange, RatelimitEvent, Report, Server, ServerBan, Snapshot,
-    User, UserSettings, Webhook,
+    Bot, Channel, ChannelCompositeKey, ChannelNotificationSettings, ChannelUnread, Emoji, File,
+    FileHash, Invite, Member, MemberCompositeKey, Message, PolicyChange, RatelimitEvent, Report,
+    Server, ServerBan, Snapshot, User, UserSettings, Webhook,
 };
 
 database_derived!(
@@ -15,6 +15,8 @@ database_derived!(
         pub bots: Arc<Mutex<HashMap<String, Bot>>>,
         pub channels: Arc<Mutex<HashMap<String, Channel>>>,
         pub channel_invites: Arc<Mutex<HashMap<String, Invite>>>,
+        pub channel_notification_settings:
+            Arc<Mutex<HashMap<ChannelCompositeKey, ChannelNotificationSettings>>>,
         pub channel_unreads: Arc<Mutex<HashMap<ChannelCompositeKey, ChannelUnread>>>,
         pub channel_webhooks: Arc<Mutex<HashMap<String, Webhook>>>,
         pub emojis: Arc<Mutex<HashMap<String, Emoji>>>,
@@ -1,4 +1,5 @@
 use authifier::AuthifierEvent;
+use iso8601_timestamp::Timestamp;
 use revolt_result::Error;
 use serde::{Deserialize, Serialize};
 
@@ -214,6 +215,22 @@ pub enum EventV1 {
     ///
     /// User flags are specified to explain why a wipe is occurring though not all reasons will necessarily ever appear.
     UserPlatformWipe { user_id: String, flags: i32 },
+
+    /// Push subscription was added to or removed from one of the user's sessions
+    ///
+    /// Only the endpoint and platform are included, never the subscription keys.
+    PushSubscriptionUpdate {
+        user_id: String,
+        session_id: String,
+        subscribed: bool,
+        endpoint: String,
+        platform: String,
+        timestamp: Timestamp,
+        /// Session which took over the subscription, if it was removed because
+        /// the same device subscribed on another session
+        #[serde(skip_serializing_if = "Option::is_none")]
+        replaced_by: Option<String>,
+    },
     /// New emoji
     EmojiCreate(Emoji),
 
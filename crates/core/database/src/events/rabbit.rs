@@ -1,14 +1,135 @@
 use std::collections::HashMap;
+use std::time::{SystemTime, UNIX_EPOCH};
 
 use revolt_models::v0::PushNotification;
 use serde::{Deserialize, Serialize};
 
 use crate::User;
 
+/// Body shown in place of message content for sessions that want redacted notifications
+pub static REDACTED_BODY: &str = "(새 메시지)";
+
+/// How relevant a message notification is to its recipient
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum NotificationClass {
+    /// Regular message
+    #[default]
+    Normal,
+    /// Message mentions the recipient
+    Mention,
+    /// Message replies to one of the recipient's messages
+    Reply,
+}
+
+impl NotificationClass {
+    /// Classify a message for a recipient, picking the highest matching class
+    pub fn classify(user_id: &str, mentions: &[String], reply_authors: &[String]) -> Self {
+        if reply_authors.iter().any(|id| id == user_id) {
+            NotificationClass::Reply
+        } else if mentions.iter().any(|id| id == user_id) {
+            NotificationClass::Mention
+        } else {
+            NotificationClass::Normal
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            NotificationClass::Normal => "normal",
+            NotificationClass::Mention => "mention",
+            NotificationClass::Reply => "reply",
+        }
+    }
+}
+
+/// Kind of notification, which clients use to pick sounds and iOS notification categories
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum NotificationCategory {
+    /// Regular message
+    #[default]
+    Message,
+    /// Message mentioning the recipient
+    Mention,
+    /// Reply to one of the recipient's messages
+    Reply,
+    /// Friend request received or accepted
+    FriendRequest,
+    /// Incoming call
+    Call,
+    /// Anything sent by the platform rather than another user
+    System,
+}
+
+impl NotificationCategory {
+    /// Work out the category of a notification for a recipient of the given class
+    pub fn categorise(kind: &PayloadKind, class: NotificationClass) -> Self {
+        match kind {
+            PayloadKind::MessageNotification(_) => match class {
+                NotificationClass::Normal => NotificationCategory::Message,
+                NotificationClass::Mention => NotificationCategory::Mention,
+                NotificationClass::Reply => NotificationCategory::Reply,
+            },
+            PayloadKind::FRAccepted(_) | PayloadKind::FRReceived(_) => {
+                NotificationCategory::FriendRequest
+            }
+            PayloadKind::BadgeUpdate(_) | PayloadKind::Generic(_) => NotificationCategory::System,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            NotificationCategory::Message => "message",
+            NotificationCategory::Mention => "mention",
+            NotificationCategory::Reply => "reply",
+            NotificationCategory::FriendRequest => "friend_request",
+            NotificationCategory::Call => "call",
+            NotificationCategory::System => "system",
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct MessageSentPayload {
     pub notification: PushNotification,
     pub users: Vec<String>,
+    /// Sessions which should only receive a redacted notification
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub redacted_sessions: Vec<String>,
+    /// Class of the notification for each user, users not present are `Normal`
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub classes: HashMap<String, NotificationClass>,
+    /// Only session that should be notified for each user, users not present are notified on all sessions
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub primary_sessions: HashMap<String, String>,
+}
+
+impl MessageSentPayload {
+    /// Build the notification that should be delivered to a given session
+    pub fn notification_for_session(&self, session_id: &str) -> PushNotification {
+        let mut notification = self.notification.clone();
+        if self.redacted_sessions.iter().any(|id| id == session_id) {
+            notification.body = REDACTED_BODY.to_string();
+            notification.image = None;
+            notification.message.content = None;
+            notification.message.attachments = None;
+            notification.message.embeds = None;
+        }
+
+        notification
+    }
+
+    /// Class of the notification for a given user
+    pub fn class_for_user(&self, user_id: &str) -> NotificationClass {
+        self.classes.get(user_id).copied().unwrap_or_default()
+    }
+
+    /// Whether a given session of a user should be notified
+    pub fn should_notify_session(&self, user_id: &str, session_id: &str) -> bool {
+        self.primary_sessions
+            .get(user_id)
+            .map_or(true, |primary| primary == session_id)
+    }
 }
 
 #[derive(Serialize, Deserialize)]
@@ -29,14 +150,88 @@ pub struct FRReceivedPayload {
     pub user: String,
 }
 
+/// Several friend requests published at once, fanned out by the consumer
+#[derive(Serialize, Deserialize, Clone)]
+pub struct FRReceivedBulkPayload {
+    pub requests: Vec<FRReceivedPayload>,
+}
+
+/// Any payload published on the friend request received queue
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum FRReceivedEvent {
+    Bulk(FRReceivedBulkPayload),
+    Single(FRReceivedPayload),
+}
+
+impl FRReceivedEvent {
+    /// Individual friend requests contained in this event
+    pub fn into_requests(self) -> Vec<FRReceivedPayload> {
+        match self {
+            FRReceivedEvent::Bulk(bulk) => bulk.requests,
+            FRReceivedEvent::Single(request) => vec![request],
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Clone)]
 pub struct GenericPayload {
     pub title: String,
     pub body: String,
     pub icon: Option<String>,
+    /// User whose avatar should be used as the icon, resolved by the push service
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub avatar_user: Option<String>,
     pub user: User,
 }
 
+/// Generic notification published once for several users, fanned out by the consumer
+#[derive(Serialize, Deserialize, Clone)]
+pub struct GenericBulkPayload {
+    pub title: String,
+    pub body: String,
+    pub icon: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub avatar_user: Option<String>,
+    pub users: Vec<User>,
+}
+
+/// Any payload published on the generic queue
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum GenericEvent {
+    Bulk(GenericBulkPayload),
+    Single(GenericPayload),
+}
+
+impl GenericEvent {
+    /// Individual notifications contained in this event
+    pub fn into_payloads(self) -> Vec<GenericPayload> {
+        match self {
+            GenericEvent::Bulk(bulk) => bulk
+                .users
+                .into_iter()
+                .map(|user| GenericPayload {
+                    title: bulk.title.clone(),
+                    body: bulk.body.clone(),
+                    icon: bulk.icon.clone(),
+                    avatar_user: bulk.avatar_user.clone(),
+                    user,
+                })
+                .collect(),
+            GenericEvent::Single(payload) => vec![payload],
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct MemberJoinedPayload {
+    pub server_id: String,
+    pub server_name: String,
+    pub member: User,
+    pub users: Vec<String>,
+}
+
 #[derive(Serialize, Deserialize)]
 #[serde(tag = "type", content = "data")]
 #[allow(clippy::large_enum_variant)]
@@ -54,12 +249,229 @@ pub struct PayloadToService {
     pub user_id: String,
     pub session_id: String,
     pub token: String,
+    /// Category of the notification, see [`NotificationCategory::categorise`]
+    #[serde(default)]
+    pub category: NotificationCategory,
     pub extras: HashMap<String, String>,
 }
 
+impl PayloadToService {
+    /// Address a notification to a session, categorising it for the recipient
+    pub fn new(
+        notification: PayloadKind,
+        class: NotificationClass,
+        user_id: String,
+        session_id: String,
+        token: String,
+    ) -> Self {
+        PayloadToService {
+            category: NotificationCategory::categorise(&notification, class),
+            notification,
+            user_id,
+            session_id,
+            token,
+            extras: HashMap::new(),
+        }
+    }
+}
+
+/// Receipt for a message notification which was handed to a push service
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct DeliveryReceipt {
+    pub user_id: String,
+    pub session_id: String,
+    pub channel_id: String,
+    pub message_id: String,
+    /// Unix time at which the notification was delivered
+    pub delivered_at: u64,
+}
+
+impl DeliveryReceipt {
+    /// Receipt for delivering a payload just now, if it is a message notification
+    pub fn for_payload(payload: &PayloadToService) -> Option<Self> {
+        match &payload.notification {
+            PayloadKind::MessageNotification(notification) => Some(DeliveryReceipt {
+                user_id: payload.user_id.clone(),
+                session_id: payload.session_id.clone(),
+                channel_id: notification.channel.id().to_string(),
+                message_id: notification.message.id.clone(),
+                delivered_at: SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .expect("Time went backwards")
+                    .as_secs(),
+            }),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct AckPayload {
     pub user_id: String,
     pub channel_id: String,
     pub message_id: String,
 }
+
+/// User started or stopped typing in a channel
+#[derive(Serialize, Deserialize)]
+pub struct TypingPayload {
+    pub channel_id: String,
+    pub user_id: String,
+    pub typing: bool,
+}
+
+/// Acks buffered by the publisher and sent together
+#[derive(Serialize, Deserialize)]
+pub struct AckBatchPayload {
+    pub acks: Vec<AckPayload>,
+}
+
+/// Any payload published on the ack queue
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum AckQueueEvent {
+    Batch(AckBatchPayload),
+    Single(AckPayload),
+}
+
+impl AckQueueEvent {
+    /// Individual acks contained in this event
+    pub fn into_acks(self) -> Vec<AckPayload> {
+        match self {
+            AckQueueEvent::Batch(batch) => batch.acks,
+            AckQueueEvent::Single(ack) => vec![ack],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        AckBatchPayload, AckPayload, AckQueueEvent, FRReceivedEvent, FRReceivedPayload,
+        GenericBulkPayload, GenericEvent, GenericPayload, NotificationCategory, NotificationClass,
+        PayloadKind,
+    };
+    use crate::User;
+
+    #[test]
+    fn classifies_by_highest_signal() {
+        let mentions = vec!["mentioned".to_string(), "both".to_string()];
+        let reply_authors = vec!["replied".to_string(), "both".to_string()];
+
+        let classify =
+            |user_id: &str| NotificationClass::classify(user_id, &mentions, &reply_authors);
+
+        assert_eq!(classify("someone"), NotificationClass::Normal);
+        assert_eq!(classify("mentioned"), NotificationClass::Mention);
+        assert_eq!(classify("replied"), NotificationClass::Reply);
+        assert_eq!(classify("both"), NotificationClass::Reply);
+    }
+
+    #[test]
+    fn classes_are_ordered_by_priority() {
+        assert!(NotificationClass::Reply > NotificationClass::Mention);
+        assert!(NotificationClass::Mention > NotificationClass::Normal);
+    }
+
+    #[test]
+    fn categorises_by_payload_and_class() {
+        let friend_request = PayloadKind::FRReceived(FRReceivedPayload {
+            from_user: User::default(),
+            user: "user".to_string(),
+        });
+        let badge = PayloadKind::BadgeUpdate(1);
+
+        assert_eq!(
+            NotificationCategory::categorise(&friend_request, NotificationClass::Mention),
+            NotificationCategory::FriendRequest
+        );
+        assert_eq!(
+            NotificationCategory::categorise(&badge, NotificationClass::Normal),
+            NotificationCategory::System
+        );
+        assert_eq!(
+            serde_json::to_string(&NotificationCategory::FriendRequest).unwrap(),
+            format!("\"{}\"", NotificationCategory::FriendRequest.as_str())
+        );
+    }
+
+    #[test]
+    fn friend_request_events_fan_out() {
+        let request = |user: &str| FRReceivedPayload {
+            from_user: User {
+                id: "sender".to_string(),
+                ..Default::default()
+            },
+            user: user.to_string(),
+        };
+
+        let single = serde_json::to_string(&request("a")).unwrap();
+        let event: FRReceivedEvent = serde_json::from_str(&single).unwrap();
+        assert_eq!(event.into_requests().len(), 1);
+
+        let bulk = serde_json::to_string(&FRReceivedEvent::Bulk(super::FRReceivedBulkPayload {
+            requests: vec![request("a"), request("b")],
+        }))
+        .unwrap();
+        let event: FRReceivedEvent = serde_json::from_str(&bulk).unwrap();
+        let users: Vec<String> = event.into_requests().into_iter().map(|r| r.user).collect();
+        assert_eq!(users, vec!["a", "b"]);
+    }
+
+    #[test]
+    fn generic_events_fan_out() {
+        let user = |id: &str| User {
+            id: id.to_string(),
+            ..Default::default()
+        };
+
+        let single = serde_json::to_string(&GenericPayload {
+            title: "title".to_string(),
+            body: "body".to_string(),
+            icon: None,
+            avatar_user: None,
+            user: user("a"),
+        })
+        .unwrap();
+        let event: GenericEvent = serde_json::from_str(&single).unwrap();
+        assert_eq!(event.into_payloads().len(), 1);
+
+        let bulk = serde_json::to_string(&GenericEvent::Bulk(GenericBulkPayload {
+            title: "title".to_string(),
+            body: "body".to_string(),
+            icon: None,
+            avatar_user: None,
+            users: vec![user("a"), user("b")],
+        }))
+        .unwrap();
+        let event: GenericEvent = serde_json::from_str(&bulk).unwrap();
+        let payloads = event.into_payloads();
+        let users: Vec<&str> = payloads.iter().map(|p| p.user.id.as_str()).collect();
+        assert_eq!(users, vec!["a", "b"]);
+        assert!(payloads.iter().all(|p| p.title == "title"));
+    }
+    #[test]
+    fn ack_events_fan_out() {
+        let ack = |channel: &str| AckPayload {
+            user_id: "user".to_string(),
+            channel_id: channel.to_string(),
+            message_id: "message".to_string(),
+        };
+
+        let single = serde_json::to_string(&ack("a")).unwrap();
+        let event: AckQueueEvent = serde_json::from_str(&single).unwrap();
+        assert_eq!(event.into_acks().len(), 1);
+
+        let batch = serde_json::to_string(&AckQueueEvent::Batch(AckBatchPayload {
+            acks: vec![ack("a"), ack("b")],
+        }))
+        .unwrap();
+        let event: AckQueueEvent = serde_json::from_str(&batch).unwrap();
+        let channels: Vec<String> = event
+            .into_acks()
+            .into_iter()
+            .map(|a| a.channel_id)
+            .collect();
+        assert_eq!(channels, vec!["a", "b"]);
+    }
+}
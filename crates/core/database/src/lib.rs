@@ -110,7 +110,16 @@ pub mod events;
 pub mod tasks;
 
 mod amqp;
-pub use amqp::amqp::AMQP;
+pub use amqp::amqp::{
+    is_persistent, routing_key_for, MessageSentReport, NotificationKind, PublishOutcome,
+    SuppressionReason, AMQP,
+};
+pub use amqp::breaker::BreakerState;
+pub use amqp::format;
+pub use amqp::metrics;
+pub use amqp::notifier;
+pub use amqp::outbox;
+pub use amqp::publish;
 
 /// Utility function to check if a boolean value is false
 pub fn if_false(t: &bool) -> bool {
@@ -40,6 +40,10 @@ pub async fn create_database(db: &MongoDb) {
         .await
         .expect("Failed to create channel_invites collection.");
 
+    db.create_collection("channel_notification_settings")
+        .await
+        .expect("Failed to create channel_notification_settings collection.");
+
     db.create_collection("channel_unreads")
         .await
         .expect("Failed to create channel_unreads collection.");
@@ -184,6 +188,21 @@ pub async fn create_database(db: &MongoDb) {
     .await
     .expect("Failed to create channel_unreads index.");
 
+    db.run_command(doc! {
+        "createIndexes": "channel_notification_settings",
+        "indexes": [
+            {
+                "key": {
+                    "_id.channel": 1_i32,
+                    "_id.user": 1_i32,
+                },
+                "name": "compound_id"
+            }
+        ]
+    })
+    .await
+    .expect("Failed to create channel_notification_settings index.");
+
     db.run_command(doc! {
         "createIndexes": "server_members",
         "indexes": [
@@ -26,7 +26,7 @@ struct MigrationInfo {
     revision: i32,
 }
 
-pub const LATEST_REVISION: i32 = 42; // MUST BE +1 to last migration
+pub const LATEST_REVISION: i32 = 43; // MUST BE +1 to last migration
 
 pub async fn migrate_database(db: &MongoDb) {
     let migrations = db.col::<Document>("migrations");
@@ -1226,6 +1226,33 @@ pub async fn run_migrations(db: &MongoDb, revision: i32) -> i32 {
         }
     }
 
+    if revision <= 42 {
+        info!(
+            "Running migration [revision 42 / 15-10-2026]: create channel notification settings collection."
+        );
+
+        db.db()
+            .create_collection("channel_notification_settings")
+            .await
+            .expect("Failed to create channel_notification_settings collection.");
+
+        db.db()
+            .run_command(doc! {
+                "createIndexes": "channel_notification_settings",
+                "indexes": [
+                    {
+                        "key": {
+                            "_id.channel": 1_i32,
+                            "_id.user": 1_i32,
+                        },
+                        "name": "compound_id"
+                    }
+                ]
+            })
+            .await
+            .expect("Failed to create channel_notification_settings index.");
+    }
+
     // Reminder to update LATEST_REVISION when adding new migrations.
     LATEST_REVISION.max(revision)
 }
@@ -0,0 +1,50 @@
+use iso8601_timestamp::Timestamp;
+
+use crate::ChannelCompositeKey;
+
+auto_derived!(
+    /// User's notification settings for a channel
+    pub struct ChannelNotificationSettings {
+        /// Composite key pointing to a user's view of a channel
+        #[serde(rename = "_id")]
+        pub id: ChannelCompositeKey,
+
+        /// Whether the user has muted this channel
+        #[serde(skip_serializing_if = "crate::if_false", default)]
+        pub muted: bool,
+        /// Time at which the mute lifts, indefinite if not present
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub muted_until: Option<Timestamp>,
+
+        /// Whether the user wants to be notified when messages are pinned
+        #[serde(skip_serializing_if = "crate::if_false", default)]
+        pub notify_pins: bool,
+
+        /// Whether the user wants to be notified of every message, even while viewing the channel
+        #[serde(skip_serializing_if = "crate::if_false", default)]
+        pub always_notify: bool,
+    }
+);
+
+impl ChannelNotificationSettings {
+    /// Check whether the channel is currently muted
+    pub fn is_muted(&self) -> bool {
+        self.muted
+            && match &self.muted_until {
+                Some(until) => Timestamp::now_utc() < *until,
+                None => true,
+            }
+    }
+
+    /// Check whether the user should be notified even while viewing the channel
+    ///
+    /// Muting the channel takes precedence.
+    pub fn always_notifies(&self) -> bool {
+        self.always_notify && !self.is_muted()
+    }
+
+    /// Check whether any of these settings currently differ from the defaults
+    pub fn is_default(&self) -> bool {
+        !self.is_muted() && !self.notify_pins && !self.always_notify
+    }
+}
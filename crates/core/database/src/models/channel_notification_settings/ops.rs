@@ -0,0 +1,50 @@
+use iso8601_timestamp::Timestamp;
+use revolt_result::Result;
+
+use crate::ChannelNotificationSettings;
+
+#[cfg(feature = "mongodb")]
+mod mongodb;
+mod reference;
+
+#[async_trait]
+pub trait AbstractChannelNotificationSettings: Sync + Send {
+    /// Fetch notification settings for many users in a channel
+    async fn fetch_channel_notification_settings(
+        &self,
+        channel_id: &str,
+        user_ids: &[String],
+    ) -> Result<Vec<ChannelNotificationSettings>>;
+
+    /// Mute a channel for a user, optionally until a given time
+    async fn mute_channel(
+        &self,
+        channel_id: &str,
+        user_id: &str,
+        until: Option<Timestamp>,
+    ) -> Result<()>;
+
+    /// Unmute a channel for a user
+    async fn unmute_channel(&self, channel_id: &str, user_id: &str) -> Result<()>;
+
+    /// Set whether a user is notified about messages pinned in a channel
+    async fn set_pin_notifications(
+        &self,
+        channel_id: &str,
+        user_id: &str,
+        enabled: bool,
+    ) -> Result<()>;
+
+    /// Set whether a user is notified of every message in a channel, even while viewing it
+    async fn set_always_notify(&self, channel_id: &str, user_id: &str, enabled: bool)
+        -> Result<()>;
+
+    /// Fetch ids of users who want to be notified about messages pinned in a channel
+    async fn fetch_pin_notification_subscribers(&self, channel_id: &str) -> Result<Vec<String>>;
+
+    /// Fetch notification settings for every channel a user has configured
+    async fn fetch_user_channel_notification_settings(
+        &self,
+        user_id: &str,
+    ) -> Result<Vec<ChannelNotificationSettings>>;
+}
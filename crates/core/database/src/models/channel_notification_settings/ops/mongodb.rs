@@ -0,0 +1,196 @@
+use bson::to_bson;
+use iso8601_timestamp::Timestamp;
+use mongodb::options::UpdateOptions;
+use revolt_result::Result;
+
+use crate::ChannelNotificationSettings;
+use crate::MongoDb;
+
+use super::AbstractChannelNotificationSettings;
+
+static COL: &str = "channel_notification_settings";
+
+#[async_trait]
+impl AbstractChannelNotificationSettings for MongoDb {
+    /// Fetch notification settings for many users in a channel
+    async fn fetch_channel_notification_settings(
+        &self,
+        channel_id: &str,
+        user_ids: &[String],
+    ) -> Result<Vec<ChannelNotificationSettings>> {
+        query!(
+            self,
+            find,
+            COL,
+            doc! {
+                "_id.channel": channel_id,
+                "_id.user": {
+                    "$in": user_ids
+                }
+            }
+        )
+    }
+
+    /// Mute a channel for a user, optionally until a given time
+    async fn mute_channel(
+        &self,
+        channel_id: &str,
+        user_id: &str,
+        until: Option<Timestamp>,
+    ) -> Result<()> {
+        let update = if let Some(until) = until {
+            doc! {
+                "$set": {
+                    "muted": true,
+                    "muted_until": to_bson(&until)
+                        .map_err(|_| create_database_error!("to_bson", "timestamp"))?
+                }
+            }
+        } else {
+            doc! {
+                "$set": {
+                    "muted": true
+                },
+                "$unset": {
+                    "muted_until": 1
+                }
+            }
+        };
+
+        self.col::<ChannelNotificationSettings>(COL)
+            .update_one(
+                doc! {
+                    "_id.channel": channel_id,
+                    "_id.user": user_id,
+                },
+                update,
+            )
+            .with_options(UpdateOptions::builder().upsert(true).build())
+            .await
+            .map(|_| ())
+            .map_err(|_| create_database_error!("update_one", COL))
+    }
+
+    /// Unmute a channel for a user
+    async fn unmute_channel(&self, channel_id: &str, user_id: &str) -> Result<()> {
+        self.col::<ChannelNotificationSettings>(COL)
+            .update_one(
+                doc! {
+                    "_id.channel": channel_id,
+                    "_id.user": user_id,
+                },
+                doc! {
+                    "$unset": {
+                        "muted": 1,
+                        "muted_until": 1
+                    }
+                },
+            )
+            .await
+            .map(|_| ())
+            .map_err(|_| create_database_error!("update_one", COL))
+    }
+
+    /// Set whether a user is notified about messages pinned in a channel
+    async fn set_pin_notifications(
+        &self,
+        channel_id: &str,
+        user_id: &str,
+        enabled: bool,
+    ) -> Result<()> {
+        let update = if enabled {
+            doc! {
+                "$set": {
+                    "notify_pins": true
+                }
+            }
+        } else {
+            doc! {
+                "$unset": {
+                    "notify_pins": 1
+                }
+            }
+        };
+
+        self.col::<ChannelNotificationSettings>(COL)
+            .update_one(
+                doc! {
+                    "_id.channel": channel_id,
+                    "_id.user": user_id,
+                },
+                update,
+            )
+            .with_options(UpdateOptions::builder().upsert(enabled).build())
+            .await
+            .map(|_| ())
+            .map_err(|_| create_database_error!("update_one", COL))
+    }
+
+    /// Set whether a user is notified of every message in a channel, even while viewing it
+    async fn set_always_notify(
+        &self,
+        channel_id: &str,
+        user_id: &str,
+        enabled: bool,
+    ) -> Result<()> {
+        let update = if enabled {
+            doc! {
+                "$set": {
+                    "always_notify": true
+                }
+            }
+        } else {
+            doc! {
+                "$unset": {
+                    "always_notify": 1
+                }
+            }
+        };
+
+        self.col::<ChannelNotificationSettings>(COL)
+            .update_one(
+                doc! {
+                    "_id.channel": channel_id,
+                    "_id.user": user_id,
+                },
+                update,
+            )
+            .with_options(UpdateOptions::builder().upsert(enabled).build())
+            .await
+            .map(|_| ())
+            .map_err(|_| create_database_error!("update_one", COL))
+    }
+
+    /// Fetch ids of users who want to be notified about messages pinned in a channel
+    async fn fetch_pin_notification_subscribers(&self, channel_id: &str) -> Result<Vec<String>> {
+        let settings: Vec<ChannelNotificationSettings> = query!(
+            self,
+            find,
+            COL,
+            doc! {
+                "_id.channel": channel_id,
+                "notify_pins": true
+            }
+        )?;
+
+        Ok(settings
+            .into_iter()
+            .map(|settings| settings.id.user)
+            .collect())
+    }
+
+    /// Fetch notification settings for every channel a user has configured
+    async fn fetch_user_channel_notification_settings(
+        &self,
+        user_id: &str,
+    ) -> Result<Vec<ChannelNotificationSettings>> {
+        query!(
+            self,
+            find,
+            COL,
+            doc! {
+                "_id.user": user_id
+            }
+        )
+    }
+}
@@ -0,0 +1,142 @@
+use iso8601_timestamp::Timestamp;
+use revolt_result::Result;
+
+use crate::{ChannelCompositeKey, ChannelNotificationSettings, ReferenceDb};
+
+use super::AbstractChannelNotificationSettings;
+
+#[async_trait]
+impl AbstractChannelNotificationSettings for ReferenceDb {
+    /// Fetch notification settings for many users in a channel
+    async fn fetch_channel_notification_settings(
+        &self,
+        channel_id: &str,
+        user_ids: &[String],
+    ) -> Result<Vec<ChannelNotificationSettings>> {
+        let settings = self.channel_notification_settings.lock().await;
+        Ok(settings
+            .values()
+            .filter(|entry| entry.id.channel == channel_id && user_ids.contains(&entry.id.user))
+            .cloned()
+            .collect())
+    }
+
+    /// Mute a channel for a user, optionally until a given time
+    async fn mute_channel(
+        &self,
+        channel_id: &str,
+        user_id: &str,
+        until: Option<Timestamp>,
+    ) -> Result<()> {
+        let mut settings = self.channel_notification_settings.lock().await;
+        let key = ChannelCompositeKey {
+            channel: channel_id.to_string(),
+            user: user_id.to_string(),
+        };
+
+        let entry = settings
+            .entry(key.clone())
+            .or_insert_with(|| ChannelNotificationSettings {
+                id: key,
+                muted: false,
+                muted_until: None,
+                notify_pins: false,
+                always_notify: false,
+            });
+
+        entry.muted = true;
+        entry.muted_until = until;
+        Ok(())
+    }
+
+    /// Unmute a channel for a user
+    async fn unmute_channel(&self, channel_id: &str, user_id: &str) -> Result<()> {
+        let mut settings = self.channel_notification_settings.lock().await;
+        if let Some(entry) = settings.get_mut(&ChannelCompositeKey {
+            channel: channel_id.to_string(),
+            user: user_id.to_string(),
+        }) {
+            entry.muted = false;
+            entry.muted_until = None;
+        }
+
+        Ok(())
+    }
+
+    /// Set whether a user is notified about messages pinned in a channel
+    async fn set_pin_notifications(
+        &self,
+        channel_id: &str,
+        user_id: &str,
+        enabled: bool,
+    ) -> Result<()> {
+        let mut settings = self.channel_notification_settings.lock().await;
+        let key = ChannelCompositeKey {
+            channel: channel_id.to_string(),
+            user: user_id.to_string(),
+        };
+
+        let entry = settings
+            .entry(key.clone())
+            .or_insert_with(|| ChannelNotificationSettings {
+                id: key,
+                muted: false,
+                muted_until: None,
+                notify_pins: false,
+                always_notify: false,
+            });
+
+        entry.notify_pins = enabled;
+        Ok(())
+    }
+
+    /// Set whether a user is notified of every message in a channel, even while viewing it
+    async fn set_always_notify(
+        &self,
+        channel_id: &str,
+        user_id: &str,
+        enabled: bool,
+    ) -> Result<()> {
+        let mut settings = self.channel_notification_settings.lock().await;
+        let key = ChannelCompositeKey {
+            channel: channel_id.to_string(),
+            user: user_id.to_string(),
+        };
+
+        let entry = settings
+            .entry(key.clone())
+            .or_insert_with(|| ChannelNotificationSettings {
+                id: key,
+                muted: false,
+                muted_until: None,
+                notify_pins: false,
+                always_notify: false,
+            });
+
+        entry.always_notify = enabled;
+        Ok(())
+    }
+
+    /// Fetch ids of users who want to be notified about messages pinned in a channel
+    async fn fetch_pin_notification_subscribers(&self, channel_id: &str) -> Result<Vec<String>> {
+        let settings = self.channel_notification_settings.lock().await;
+        Ok(settings
+            .values()
+            .filter(|entry| entry.id.channel == channel_id && entry.notify_pins)
+            .map(|entry| entry.id.user.clone())
+            .collect())
+    }
+
+    /// Fetch notification settings for every channel a user has configured
+    async fn fetch_user_channel_notification_settings(
+        &self,
+        user_id: &str,
+    ) -> Result<Vec<ChannelNotificationSettings>> {
+        let settings = self.channel_notification_settings.lock().await;
+        Ok(settings
+            .values()
+            .filter(|entry| entry.id.user == user_id)
+            .cloned()
+            .collect())
+    }
+}
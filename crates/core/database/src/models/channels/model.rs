@@ -106,6 +106,9 @@ auto_derived!(
             /// Whether this channel is marked as not safe for work
             #[serde(skip_serializing_if = "crate::if_false", default)]
             nsfw: bool,
+            /// Whether notifications from this channel show spoilers instead of masking them
+            #[serde(skip_serializing_if = "crate::if_false", default)]
+            unmask_spoilers: bool,
         },
         /// Voice channel belonging to a server
         VoiceChannel {
@@ -155,6 +158,8 @@ auto_derived!(
         #[serde(skip_serializing_if = "Option::is_none")]
         pub nsfw: Option<bool>,
         #[serde(skip_serializing_if = "Option::is_none")]
+        pub unmask_spoilers: Option<bool>,
+        #[serde(skip_serializing_if = "Option::is_none")]
         pub active: Option<bool>,
         #[serde(skip_serializing_if = "Option::is_none")]
         pub permissions: Option<i64>,
@@ -222,6 +227,7 @@ impl Channel {
                 default_permissions: None,
                 role_permissions: HashMap::new(),
                 nsfw: data.nsfw.unwrap_or(false),
+                unmask_spoilers: false,
             },
             v0::LegacyServerChannelType::Voice => Channel::VoiceChannel {
                 id: id.clone(),
@@ -554,6 +560,15 @@ impl Channel {
 
     /// Apply partial channel to channel
     pub fn apply_options(&mut self, partial: PartialChannel) {
+        if let Self::TextChannel {
+            unmask_spoilers, ..
+        } = self
+        {
+            if let Some(v) = partial.unmask_spoilers {
+                *unmask_spoilers = v;
+            }
+        }
+
         match self {
             Self::SavedMessages { .. } => {}
             Self::DirectMessage { active, .. } => {
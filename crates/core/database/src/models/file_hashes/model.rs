@@ -90,3 +90,16 @@ impl FileHash {
         }
     }
 }
+
+impl Metadata {
+    /// Name of this kind of metadata, as stored in the `type` field
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Metadata::File => "File",
+            Metadata::Text => "Text",
+            Metadata::Image { .. } => "Image",
+            Metadata::Video { .. } => "Video",
+            Metadata::Audio => "Audio",
+        }
+    }
+}
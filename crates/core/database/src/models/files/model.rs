@@ -37,6 +37,7 @@ auto_derived_partial!(
         /// Parsed metadata of this file
         pub metadata: Metadata,
         /// Raw content type of this file
+        #[serde(default)]
         pub content_type: String,
         /// Size of this file (in bytes)
         pub size: isize,
@@ -82,7 +83,49 @@ auto_derived!(
     }
 );
 
+/// Content type used when nothing more specific is known
+const GENERIC_CONTENT_TYPE: &str = "application/octet-stream";
+
+/// Guess a content type from a file name's extension
+fn content_type_from_filename(filename: &str) -> Option<&'static str> {
+    let (_, extension) = filename.rsplit_once('.')?;
+    Some(match extension.to_ascii_lowercase().as_str() {
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "avif" => "image/avif",
+        "svg" => "image/svg+xml",
+        "mp4" | "m4v" => "video/mp4",
+        "webm" => "video/webm",
+        "mov" => "video/quicktime",
+        "mp3" => "audio/mpeg",
+        "ogg" => "audio/ogg",
+        "wav" => "audio/wav",
+        "flac" => "audio/flac",
+        "m4a" => "audio/mp4",
+        "txt" => "text/plain",
+        "pdf" => "application/pdf",
+        "zip" => "application/zip",
+        _ => return None,
+    })
+}
+
 impl File {
+    /// Make sure this file has a usable content type
+    ///
+    /// Older files may be stored without one, in which case it is inferred
+    /// from the file extension, falling back to a generic binary type.
+    pub fn ensure_content_type(&mut self) {
+        if !self.content_type.is_empty() && self.content_type != GENERIC_CONTENT_TYPE {
+            return;
+        }
+
+        self.content_type = content_type_from_filename(&self.filename)
+            .unwrap_or(GENERIC_CONTENT_TYPE)
+            .to_string();
+    }
+
     /// Get the hash entry for this file
     pub async fn as_hash(&self, db: &Database) -> Result<FileHash> {
         db.fetch_attachment_hash(self.hash.as_ref().unwrap()).await
@@ -240,3 +283,56 @@ impl File {
         .await
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::File;
+
+    fn file(filename: &str, content_type: &str) -> File {
+        File {
+            id: "file".to_string(),
+            tag: "attachments".to_string(),
+            filename: filename.to_string(),
+            hash: None,
+            uploaded_at: None,
+            uploader_id: None,
+            used_for: None,
+            deleted: None,
+            reported: None,
+            metadata: Default::default(),
+            content_type: content_type.to_string(),
+            size: 0,
+            message_id: None,
+            user_id: None,
+            server_id: None,
+            object_id: None,
+        }
+    }
+
+    #[test]
+    fn infers_missing_content_type() {
+        let mut legacy: File = serde_json::from_value(serde_json::json!({
+            "_id": "file",
+            "tag": "attachments",
+            "filename": "Holiday.JPG",
+            "metadata": { "type": "Image", "width": 1, "height": 1 },
+            "size": 1
+        }))
+        .unwrap();
+
+        legacy.ensure_content_type();
+        assert_eq!(legacy.content_type, "image/jpeg");
+
+        let mut generic = file("clip.webm", "application/octet-stream");
+        generic.ensure_content_type();
+        assert_eq!(generic.content_type, "video/webm");
+
+        let mut unknown = file("data.bin", "");
+        unknown.ensure_content_type();
+        assert_eq!(unknown.content_type, "application/octet-stream");
+
+        let mut known = file("image.png", "image/png");
+        known.ensure_content_type();
+        assert_eq!(known.content_type, "image/png");
+    }
+}
@@ -179,6 +179,8 @@ auto_derived!(
     pub struct MessageFilter {
         /// Parent channel ID
         pub channel: Option<String>,
+        /// Any of these parent channel IDs
+        pub channels: Option<Vec<String>>,
         /// Message author ID
         pub author: Option<String>,
         /// Search query
@@ -187,6 +189,8 @@ auto_derived!(
         pub pinned: Option<bool>,
         /// Filter messages with attachments
         pub has_attachments: Option<bool>,
+        /// Filter messages with attachments of any of these metadata types
+        pub attachment_types: Option<Vec<String>>,
     }
 
     /// Message Query
@@ -203,6 +207,15 @@ auto_derived!(
         pub time_period: MessageTimePeriod,
     }
 
+    /// Storage used by attachments in a channel
+    #[derive(Default)]
+    pub struct AttachmentUsage {
+        /// Number of attachments
+        pub count: usize,
+        /// Total size of the attachments in bytes
+        pub size: usize,
+    }
+
     /// Optional fields on message
     pub enum FieldsMessage {
         Pinned,
@@ -635,6 +648,7 @@ impl Message {
                             self.clone(),
                             mentions.clone(),
                             self.has_suppressed_notifications(),
+                            None,
                         )],
                     },
                 )
@@ -735,6 +749,9 @@ impl Message {
                             _ => vec![],
                         },
                         false, // branch already dictates this
+                        _amqp
+                            .and_then(|amqp| amqp.trace_id())
+                            .map(ToString::to_string),
                     )],
                 },
             )
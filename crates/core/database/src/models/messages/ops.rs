@@ -1,6 +1,9 @@
 use revolt_result::Result;
 
-use crate::{AppendMessage, FieldsMessage, Message, MessageQuery, PartialMessage};
+use crate::{
+    AppendMessage, AttachmentUsage, FieldsMessage, Message, MessageFilter, MessageQuery,
+    PartialMessage,
+};
 
 #[cfg(feature = "mongodb")]
 mod mongodb;
@@ -40,4 +43,23 @@ pub trait AbstractMessages: Sync + Send {
 
     /// Delete messages from a channel by their ids and corresponding channel id
     async fn delete_messages(&self, channel: &str, ids: &[String]) -> Result<()>;
+
+    /// Sum up the attachments of a channel, optionally only those of the given metadata types
+    async fn fetch_attachment_usage(
+        &self,
+        channel: &str,
+        attachment_types: Option<&[String]>,
+    ) -> Result<AttachmentUsage>;
+
+    /// Count the attachments of messages matching the given filter
+    ///
+    /// Only attachments of the filter's metadata types and, if given, with a matching
+    /// spoiler state and at least the minimum size and dimensions are counted.
+    async fn count_attachments(
+        &self,
+        filter: &MessageFilter,
+        spoiler: Option<bool>,
+        min_size: Option<usize>,
+        min_dimension: Option<usize>,
+    ) -> Result<usize>;
 }
@@ -1,12 +1,12 @@
-use bson::{to_bson, Document};
-use futures::try_join;
+use bson::{to_bson, Bson, Document, Regex};
+use futures::{try_join, StreamExt};
 use mongodb::options::FindOptions;
 use revolt_models::v0::MessageSort;
 use revolt_result::Result;
 
 use crate::{
-    AppendMessage, DocumentId, FieldsMessage, IntoDocumentPath, Message, MessageQuery,
-    MessageTimePeriod, MongoDb, PartialMessage,
+    AppendMessage, AttachmentUsage, DocumentId, FieldsMessage, IntoDocumentPath, Message,
+    MessageFilter, MessageQuery, MessageTimePeriod, MongoDb, PartialMessage,
 };
 
 use super::AbstractMessages;
@@ -27,43 +27,9 @@ impl AbstractMessages for MongoDb {
 
     /// Fetch multiple messages by given query
     async fn fetch_messages(&self, query: MessageQuery) -> Result<Vec<Message>> {
-        let mut filter = doc! {};
-
         // 1. Apply message filters
-        if let Some(channel) = query.filter.channel {
-            filter.insert("channel", channel);
-        }
-
-        if let Some(author) = query.filter.author {
-            filter.insert("author", author);
-        }
-
-        let is_search_query = if let Some(query) = query.filter.query {
-            filter.insert(
-                "$text",
-                doc! {
-                    "$search": query
-                },
-            );
-
-            true
-        } else {
-            false
-        };
-
-        if let Some(pinned) = query.filter.pinned {
-            filter.insert("pinned", pinned);
-        };
-
-        if let Some(true) = query.filter.has_attachments {
-            filter.insert(
-                "attachments",
-                doc! {
-                    "$exists": true,
-                    "$ne": []
-                },
-            );
-        }
+        let is_search_query = query.filter.query.is_some();
+        let mut filter = message_filter_document(&query.filter);
 
         // 2. Find query limit
         let limit = query.limit.unwrap_or(50);
@@ -316,6 +282,204 @@ impl AbstractMessages for MongoDb {
             .map(|_| ())
             .map_err(|_| create_database_error!("delete_many", COL))
     }
+
+    /// Sum up the attachments of a channel, optionally only those of the given metadata types
+    async fn fetch_attachment_usage(
+        &self,
+        channel: &str,
+        attachment_types: Option<&[String]>,
+    ) -> Result<AttachmentUsage> {
+        let mut pipeline = vec![
+            doc! {
+                "$match": {
+                    "channel": channel,
+                    "attachments": {
+                        "$exists": true,
+                        "$ne": []
+                    }
+                }
+            },
+            doc! {
+                "$unwind": "$attachments"
+            },
+        ];
+
+        if let Some(types) = attachment_types {
+            pipeline.push(doc! {
+                "$match": {
+                    "attachments.metadata.type": {
+                        "$in": types
+                    }
+                }
+            });
+        }
+
+        pipeline.push(doc! {
+            "$group": {
+                "_id": null,
+                "count": { "$sum": 1_i32 },
+                "size": { "$sum": "$attachments.size" }
+            }
+        });
+
+        let mut cursor = self
+            .col::<Document>(COL)
+            .aggregate(pipeline)
+            .await
+            .map_err(|_| create_database_error!("aggregate", COL))?;
+
+        let Some(doc) = cursor.next().await else {
+            return Ok(AttachmentUsage::default());
+        };
+
+        let doc = doc.map_err(|_| create_database_error!("aggregate", COL))?;
+        let number = |key: &str| match doc.get(key) {
+            Some(Bson::Int32(value)) => *value as usize,
+            Some(Bson::Int64(value)) => *value as usize,
+            Some(Bson::Double(value)) => *value as usize,
+            _ => 0,
+        };
+
+        Ok(AttachmentUsage {
+            count: number("count"),
+            size: number("size"),
+        })
+    }
+
+    /// Count the attachments of messages matching the given filter
+    async fn count_attachments(
+        &self,
+        filter: &MessageFilter,
+        spoiler: Option<bool>,
+        min_size: Option<usize>,
+        min_dimension: Option<usize>,
+    ) -> Result<usize> {
+        let mut pipeline = vec![
+            doc! {
+                "$match": message_filter_document(&MessageFilter {
+                    has_attachments: Some(true),
+                    ..filter.clone()
+                })
+            },
+            doc! {
+                "$unwind": "$attachments"
+            },
+        ];
+
+        let mut attachment_filter = doc! {};
+        if let Some(types) = &filter.attachment_types {
+            attachment_filter.insert("attachments.metadata.type", doc! { "$in": types });
+        }
+
+        if let Some(spoiler) = spoiler {
+            let prefix = Bson::RegularExpression(Regex {
+                pattern: "^SPOILER_".to_string(),
+                options: String::new(),
+            });
+
+            attachment_filter.insert(
+                "attachments.filename",
+                if spoiler {
+                    prefix
+                } else {
+                    Bson::Document(doc! { "$not": prefix })
+                },
+            );
+        }
+
+        if let Some(min_size) = min_size {
+            attachment_filter.insert("attachments.size", doc! { "$gte": min_size as i64 });
+        }
+
+        // Only images and videos with known dimensions have to meet the minimum
+        if let Some(min_dimension) = min_dimension {
+            let min_dimension = min_dimension as i64;
+            attachment_filter.insert(
+                "$nor",
+                [doc! {
+                    "attachments.metadata.width": { "$gt": 0 },
+                    "attachments.metadata.height": { "$gt": 0 },
+                    "$or": [
+                        { "attachments.metadata.width": { "$lt": min_dimension } },
+                        { "attachments.metadata.height": { "$lt": min_dimension } }
+                    ]
+                }],
+            );
+        }
+
+        if !attachment_filter.is_empty() {
+            pipeline.push(doc! {
+                "$match": attachment_filter
+            });
+        }
+
+        pipeline.push(doc! {
+            "$count": "count"
+        });
+
+        let mut cursor = self
+            .col::<Document>(COL)
+            .aggregate(pipeline)
+            .await
+            .map_err(|_| create_database_error!("aggregate", COL))?;
+
+        let Some(doc) = cursor.next().await else {
+            return Ok(0);
+        };
+
+        let doc = doc.map_err(|_| create_database_error!("aggregate", COL))?;
+        Ok(match doc.get("count") {
+            Some(Bson::Int32(value)) => *value as usize,
+            Some(Bson::Int64(value)) => *value as usize,
+            _ => 0,
+        })
+    }
+}
+
+/// Build the document matching messages for the given filter
+fn message_filter_document(filter: &MessageFilter) -> Document {
+    let mut document = doc! {};
+
+    if let Some(channel) = &filter.channel {
+        document.insert("channel", channel);
+    }
+
+    if let Some(channels) = &filter.channels {
+        document.insert("channel", doc! { "$in": channels });
+    }
+
+    if let Some(author) = &filter.author {
+        document.insert("author", author);
+    }
+
+    if let Some(query) = &filter.query {
+        document.insert(
+            "$text",
+            doc! {
+                "$search": query
+            },
+        );
+    }
+
+    if let Some(pinned) = filter.pinned {
+        document.insert("pinned", pinned);
+    };
+
+    if let Some(true) = filter.has_attachments {
+        document.insert(
+            "attachments",
+            doc! {
+                "$exists": true,
+                "$ne": []
+            },
+        );
+    }
+
+    if let Some(types) = &filter.attachment_types {
+        document.insert("attachments.metadata.type", doc! { "$in": types });
+    }
+
+    document
 }
 
 impl IntoDocumentPath for FieldsMessage {
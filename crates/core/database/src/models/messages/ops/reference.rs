@@ -1,8 +1,12 @@
 use futures::future::try_join_all;
 use indexmap::IndexSet;
+use revolt_models::v0;
 use revolt_result::Result;
 
-use crate::{AppendMessage, FieldsMessage, Message, MessageQuery, PartialMessage, ReferenceDb};
+use crate::{
+    AppendMessage, AttachmentUsage, FieldsMessage, Message, MessageFilter, MessageQuery,
+    MessageTimePeriod, PartialMessage, ReferenceDb,
+};
 
 use super::AbstractMessages;
 
@@ -40,6 +44,12 @@ impl AbstractMessages for ReferenceDb {
                     }
                 }
 
+                if let Some(channels) = &query.filter.channels {
+                    if !channels.contains(&message.channel) {
+                        return false;
+                    }
+                }
+
                 if let Some(author) = &query.filter.author {
                     if &message.author != author {
                         return false;
@@ -68,6 +78,17 @@ impl AbstractMessages for ReferenceDb {
                     }
                 }
 
+                if let Some(types) = &query.filter.attachment_types {
+                    if !message
+                        .attachments
+                        .iter()
+                        .flatten()
+                        .any(|file| types.iter().any(|kind| kind == file.metadata.type_name()))
+                    {
+                        return false;
+                    }
+                }
+
                 true
             })
             .cloned()
@@ -292,4 +313,67 @@ impl AbstractMessages for ReferenceDb {
 
         Ok(())
     }
+
+    /// Sum up the attachments of a channel, optionally only those of the given metadata types
+    async fn fetch_attachment_usage(
+        &self,
+        channel: &str,
+        attachment_types: Option<&[String]>,
+    ) -> Result<AttachmentUsage> {
+        let messages = self.messages.lock().await;
+        let mut usage = AttachmentUsage::default();
+        for file in messages
+            .values()
+            .filter(|message| message.channel == channel)
+            .flat_map(|message| message.attachments.iter().flatten())
+            .filter(|file| {
+                attachment_types.map_or(true, |types| {
+                    types.iter().any(|kind| kind == file.metadata.type_name())
+                })
+            })
+        {
+            usage.count += 1;
+            usage.size += file.size.max(0) as usize;
+        }
+
+        Ok(usage)
+    }
+
+    /// Count the attachments of messages matching the given filter
+    async fn count_attachments(
+        &self,
+        filter: &MessageFilter,
+        spoiler: Option<bool>,
+        min_size: Option<usize>,
+        min_dimension: Option<usize>,
+    ) -> Result<usize> {
+        let messages = self
+            .fetch_messages(MessageQuery {
+                limit: None,
+                filter: MessageFilter {
+                    has_attachments: Some(true),
+                    ..filter.clone()
+                },
+                time_period: MessageTimePeriod::Absolute {
+                    before: None,
+                    after: None,
+                    sort: None,
+                },
+            })
+            .await?;
+
+        Ok(messages
+            .iter()
+            .flat_map(|message| message.attachments.iter().flatten())
+            .filter(|file| {
+                filter.attachment_types.as_ref().map_or(true, |types| {
+                    types.iter().any(|kind| kind == file.metadata.type_name())
+                })
+            })
+            .filter(|file| {
+                spoiler.map_or(true, |spoiler| file.filename.starts_with("SPOILER_") == spoiler)
+            })
+            .filter(|file| v0::File::from(file.clone()).is_large_enough(min_size, min_dimension))
+            .count())
+    }
 }
@@ -1,6 +1,7 @@
 mod admin_migrations;
 mod bots;
 mod channel_invites;
+mod channel_notification_settings;
 mod channel_unreads;
 mod channel_webhooks;
 mod channels;
@@ -21,6 +22,7 @@ mod users;
 pub use admin_migrations::*;
 pub use bots::*;
 pub use channel_invites::*;
+pub use channel_notification_settings::*;
 pub use channel_unreads::*;
 pub use channel_webhooks::*;
 pub use channels::*;
@@ -50,6 +52,7 @@ pub trait AbstractDatabase:
     + bots::AbstractBots
     + channels::AbstractChannels
     + channel_invites::AbstractChannelInvites
+    + channel_notification_settings::AbstractChannelNotificationSettings
     + channel_unreads::AbstractChannelUnreads
     + channel_webhooks::AbstractWebhooks
     + emojis::AbstractEmojis
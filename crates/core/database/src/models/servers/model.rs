@@ -1,11 +1,16 @@
 use std::collections::{HashMap, HashSet};
 
 use revolt_models::v0::{self, DataCreateServerChannel, ServerFlags};
-use revolt_permissions::{OverrideField, DEFAULT_PERMISSION_SERVER};
+use revolt_permissions::{
+    calculate_server_permissions, ChannelPermission, OverrideField, DEFAULT_PERMISSION_SERVER,
+};
 use revolt_result::Result;
 use ulid::Ulid;
 
-use crate::{events::client::EventV1, Channel, Database, File, User};
+use crate::{
+    events::client::EventV1, util::permissions::DatabasePermissionQuery, Channel, Database, File,
+    User,
+};
 
 auto_derived_partial!(
     /// Server
@@ -61,6 +66,9 @@ auto_derived_partial!(
         /// Whether this server should be publicly discoverable
         #[serde(skip_serializing_if = "crate::if_false", default)]
         pub discoverable: bool,
+        /// Whether staff should be notified when someone joins
+        #[serde(skip_serializing_if = "crate::if_false", default)]
+        pub notify_member_joins: bool,
     },
     "PartialServer"
 );
@@ -153,6 +161,7 @@ impl Server {
             discoverable: false,
             flags: None,
             icon: None,
+            notify_member_joins: false,
             roles: HashMap::new(),
             system_messages: None,
         };
@@ -200,6 +209,45 @@ impl Server {
         }
     }
 
+    /// Find the ids of staff members holding a given server permission
+    ///
+    /// Only the owner and members with a role granting the permission are considered,
+    /// so a permission which everyone has by default doesn't make everyone staff.
+    pub async fn fetch_staff_ids(
+        &self,
+        db: &Database,
+        permission: ChannelPermission,
+    ) -> Result<Vec<String>> {
+        let roles: Vec<String> = self
+            .roles
+            .iter()
+            .filter(|(_, role)| role.permissions.a as u64 & permission as u64 != 0)
+            .map(|(id, _)| id.clone())
+            .collect();
+
+        let mut candidates = vec![self.owner.clone()];
+        if !roles.is_empty() {
+            for member in db.fetch_all_members_with_roles(&self.id, &roles).await? {
+                if member.id.user != self.owner {
+                    candidates.push(member.id.user);
+                }
+            }
+        }
+
+        let mut staff = vec![];
+        for user in db.fetch_users(&candidates).await? {
+            let mut query = DatabasePermissionQuery::new(db, &user).server(self);
+            if calculate_server_permissions(&mut query)
+                .await
+                .has_channel_permission(permission)
+            {
+                staff.push(user.id);
+            }
+        }
+
+        Ok(staff)
+    }
+
     /// Update server data
     pub async fn update(
         &mut self,
@@ -471,4 +519,23 @@ mod tests {
                 .has_channel_permission(ChannelPermission::BanMembers));
         });
     }
+    #[async_std::test]
+    async fn staff_ids() {
+        database_test!(|db| async move {
+            fixture!(db, "server_with_roles",
+                owner user 0
+                moderator user 1
+                user user 2
+                server server 4);
+
+            let staff = server
+                .fetch_staff_ids(&db, ChannelPermission::BanMembers)
+                .await
+                .unwrap();
+
+            assert!(staff.contains(&owner.id));
+            assert!(staff.contains(&moderator.id));
+            assert!(!staff.contains(&user.id));
+        });
+    }
 }
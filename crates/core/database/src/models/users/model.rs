@@ -57,6 +57,12 @@ auto_derived_partial!(
         /// Time until user is unsuspended
         #[serde(skip_serializing_if = "Option::is_none")]
         pub suspended_until: Option<Timestamp>,
+        /// Time until which all push notifications are held back
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub notifications_snoozed_until: Option<Timestamp>,
+        /// Whether push notifications only go to the most recently active session
+        #[serde(skip_serializing_if = "crate::if_false", default)]
+        pub push_primary_session_only: bool,
         /// Last acknowledged policy change
         pub last_acknowledged_policy_change: Timestamp,
     },
@@ -75,6 +81,7 @@ auto_derived!(
 
         // internal fields
         Suspension,
+        NotificationSnooze,
         None,
     }
 
@@ -180,6 +187,8 @@ impl Default for User {
             privileged: Default::default(),
             bot: Default::default(),
             suspended_until: Default::default(),
+            notifications_snoozed_until: Default::default(),
+            push_primary_session_only: Default::default(),
             last_acknowledged_policy_change: Timestamp::UNIX_EPOCH,
         }
     }
@@ -691,6 +700,7 @@ impl User {
             }
             FieldsUser::DisplayName => self.display_name = None,
             FieldsUser::Suspension => self.suspended_until = None,
+            FieldsUser::NotificationSnooze => self.notifications_snoozed_until = None,
             FieldsUser::None => {}
         }
     }
@@ -782,6 +792,42 @@ impl User {
         unimplemented!()
     }
 
+    /// Hold back all push notifications until the given time
+    pub async fn snooze_notifications(&mut self, db: &Database, until: Timestamp) -> Result<()> {
+        self.update(
+            db,
+            PartialUser {
+                notifications_snoozed_until: Some(until),
+                ..Default::default()
+            },
+            vec![],
+        )
+        .await
+    }
+
+    /// Resume push notifications
+    pub async fn unsnooze_notifications(&mut self, db: &Database) -> Result<()> {
+        self.update(db, Default::default(), vec![FieldsUser::NotificationSnooze])
+            .await
+    }
+
+    /// Set whether push notifications go to every session or only the most recently active one
+    pub async fn set_push_primary_session_only(
+        &mut self,
+        db: &Database,
+        enabled: bool,
+    ) -> Result<()> {
+        self.update(
+            db,
+            PartialUser {
+                push_primary_session_only: Some(enabled),
+                ..Default::default()
+            },
+            vec![],
+        )
+        .await
+    }
+
     /// Permanently ban the user
     ///
     /// - If a reason is specified, an email will be sent.
@@ -1,6 +1,7 @@
-use authifier::models::Session;
+use authifier::models::{Session, WebPushSubscription};
 use iso8601_timestamp::Timestamp;
 use revolt_result::Result;
+use serde::Deserialize;
 
 use crate::{FieldsUser, PartialUser, RelationshipStatus, User};
 
@@ -64,12 +65,168 @@ pub trait AbstractUsers: Sync + Send {
     /// Remove push subscription for a session by session id (TODO: remove)
     async fn remove_push_subscription_by_session_id(&self, session_id: &str) -> Result<()>;
 
-    /// Remove duplicate FCM subscriptions for a user (keep only the new one)
-    async fn remove_duplicate_fcm_subscriptions(
+    /// Remove the given push subscription from the user's other sessions
+    ///
+    /// Returns the ids of the sessions which had their subscription removed.
+    async fn remove_duplicate_push_subscriptions(
         &self,
         user_id: &str,
-        fcm_token: &str,
-    ) -> Result<()>;
+        session_id: &str,
+        subscription: &WebPushSubscription,
+    ) -> Result<Vec<String>>;
+
+    /// Remove the push subscriptions of the given sessions
+    async fn remove_push_subscriptions(&self, session_ids: &[String]) -> Result<()>;
 
     async fn update_session_last_seen(&self, session_id: &str, when: Timestamp) -> Result<()>;
+
+    /// Fetch ids of sessions belonging to the given users that want redacted push notifications
+    async fn fetch_redacted_push_session_ids(&self, user_ids: &[String]) -> Result<Vec<String>>;
+
+    /// Set whether push notifications for a session should have their content redacted
+    async fn set_session_push_redacted(&self, session_id: &str, redacted: bool) -> Result<()>;
+
+    /// Set when the push subscription of a session expires, if it is known
+    async fn set_session_push_expiry(
+        &self,
+        session_id: &str,
+        expires_at: Option<Timestamp>,
+    ) -> Result<()>;
+
+    /// Remove push subscriptions which are past their expiration
+    ///
+    /// Subscriptions without an expiration are removed if their session
+    /// hasn't been seen since `stale_before`, if given.
+    /// Returns the number of sessions which had their subscription removed.
+    async fn prune_push_subscriptions(
+        &self,
+        now: Timestamp,
+        stale_before: Option<Timestamp>,
+    ) -> Result<u64>;
+
+    /// Fetch sessions belonging to the given users that have a push subscription
+    async fn fetch_push_subscribed_sessions(
+        &self,
+        user_ids: &[String],
+    ) -> Result<Vec<PushSubscriptionSession>>;
+}
+
+/// Session projection used when looking for duplicate push subscriptions
+#[derive(Deserialize, Debug, Clone)]
+pub struct PushSubscriptionSession {
+    #[serde(rename = "_id")]
+    pub id: String,
+    pub user_id: String,
+    pub subscription: Option<WebPushSubscription>,
+}
+
+/// Whether a subscription is to a native push service identified by its token
+fn is_token_subscription(subscription: &WebPushSubscription) -> bool {
+    subscription.endpoint == "fcm" || subscription.endpoint == "apn"
+}
+
+/// Find sessions holding the same push subscription as the given session
+///
+/// Only sessions belonging to the same user are considered duplicates, the given
+/// session is always kept. FCM and APN subscriptions must have an identical token
+/// and key, while Web Push subscriptions only need the same endpoint, as browsers
+/// rotate their keys when re-subscribing the same device.
+pub fn duplicate_push_session_ids(
+    sessions: &[PushSubscriptionSession],
+    user_id: &str,
+    session_id: &str,
+    subscription: &WebPushSubscription,
+) -> Vec<String> {
+    sessions
+        .iter()
+        .filter(|session| session.id != session_id && session.user_id == user_id)
+        .filter(|session| {
+            session.subscription.as_ref().is_some_and(|existing| {
+                existing.endpoint == subscription.endpoint
+                    && (!is_token_subscription(subscription)
+                        || (existing.auth == subscription.auth
+                            && existing.p256dh == subscription.p256dh))
+            })
+        })
+        .map(|session| session.id.clone())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use authifier::models::WebPushSubscription;
+
+    use super::{duplicate_push_session_ids, PushSubscriptionSession};
+
+    fn fcm(token: &str) -> WebPushSubscription {
+        WebPushSubscription {
+            endpoint: "fcm".to_string(),
+            p256dh: String::new(),
+            auth: token.to_string(),
+        }
+    }
+
+    fn session(id: &str, user_id: &str, token: &str) -> PushSubscriptionSession {
+        PushSubscriptionSession {
+            id: id.to_string(),
+            user_id: user_id.to_string(),
+            subscription: Some(fcm(token)),
+        }
+    }
+
+    #[test]
+    fn distinct_tokens_coexist() {
+        let sessions = vec![
+            session("a", "user", "token-a"),
+            session("b", "user", "token-b"),
+        ];
+
+        assert!(duplicate_push_session_ids(&sessions, "user", "a", &fcm("token-a")).is_empty());
+        assert!(duplicate_push_session_ids(&sessions, "user", "b", &fcm("token-b")).is_empty());
+    }
+
+    #[test]
+    fn identical_tokens_collapse_to_newest_session() {
+        let sessions = vec![
+            session("old", "user", "token"),
+            session("older", "user", "token"),
+            session("new", "user", "token"),
+            session("other", "another user", "token"),
+        ];
+
+        assert_eq!(
+            duplicate_push_session_ids(&sessions, "user", "new", &fcm("token")),
+            vec!["old".to_string(), "older".to_string()]
+        );
+    }
+
+    #[test]
+    fn rotated_web_push_keys_replace_the_old_subscription() {
+        let web_push = |p256dh: &str, auth: &str| WebPushSubscription {
+            endpoint: "https://push.example.com/device".to_string(),
+            p256dh: p256dh.to_string(),
+            auth: auth.to_string(),
+        };
+
+        let sessions = vec![
+            PushSubscriptionSession {
+                id: "old".to_string(),
+                user_id: "user".to_string(),
+                subscription: Some(web_push("old-key", "old-auth")),
+            },
+            PushSubscriptionSession {
+                id: "elsewhere".to_string(),
+                user_id: "user".to_string(),
+                subscription: Some(WebPushSubscription {
+                    endpoint: "https://push.example.com/other-device".to_string(),
+                    ..web_push("old-key", "old-auth")
+                }),
+            },
+        ];
+
+        assert_eq!(
+            duplicate_push_session_ids(&sessions, "user", "new", &web_push("new-key", "new-auth")),
+            vec!["old".to_string()]
+        );
+    }
 }
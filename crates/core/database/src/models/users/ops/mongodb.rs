@@ -1,5 +1,5 @@
 use ::mongodb::options::{Collation, CollationStrength, FindOneOptions, FindOptions};
-use authifier::models::Session;
+use authifier::models::{Session, WebPushSubscription};
 use futures::StreamExt;
 use iso8601_timestamp::Timestamp;
 use revolt_result::Result;
@@ -9,7 +9,7 @@ use crate::IntoDocumentPath;
 use crate::MongoDb;
 use crate::{FieldsUser, PartialUser, RelationshipStatus, User};
 
-use super::AbstractUsers;
+use super::{duplicate_push_session_ids, AbstractUsers, PushSubscriptionSession};
 
 static COL: &str = "users";
 
@@ -339,15 +339,60 @@ impl AbstractUsers for MongoDb {
             .map_err(|_| create_database_error!("update_one", "sessions"))
     }
 
-    /// Remove duplicate FCM subscriptions for a user (keep only the new one)
-    async fn remove_duplicate_fcm_subscriptions(&self, user_id: &str, fcm_token: &str) -> Result<()> {
-        // Find all sessions with the same FCM token for this user
-        self.col::<User>("sessions")
+    /// Remove duplicate push subscriptions for a user (keep only the new one)
+    async fn remove_duplicate_push_subscriptions(
+        &self,
+        user_id: &str,
+        session_id: &str,
+        subscription: &WebPushSubscription,
+    ) -> Result<Vec<String>> {
+        // Find other sessions of this user subscribed to the same endpoint
+        let sessions: Vec<PushSubscriptionSession> = self
+            .col::<PushSubscriptionSession>("sessions")
+            .find(doc! {
+                "_id": {
+                    "$ne": session_id
+                },
+                "user_id": user_id,
+                "subscription.endpoint": &subscription.endpoint
+            })
+            .await
+            .map_err(|_| create_database_error!("find", "sessions"))?
+            .filter_map(|s| async { s.ok() })
+            .collect()
+            .await;
+
+        let duplicates = duplicate_push_session_ids(&sessions, user_id, session_id, subscription);
+        if duplicates.is_empty() {
+            return Ok(duplicates);
+        }
+
+        self.col::<Session>("sessions")
+            .update_many(
+                doc! {
+                    "_id": {
+                        "$in": &duplicates
+                    }
+                },
+                doc! {
+                    "$unset": {
+                        "subscription": 1
+                    }
+                },
+            )
+            .await
+            .map(|_| duplicates)
+            .map_err(|_| create_database_error!("update_many", "sessions"))
+    }
+
+    /// Remove the push subscriptions of the given sessions
+    async fn remove_push_subscriptions(&self, session_ids: &[String]) -> Result<()> {
+        self.col::<Session>("sessions")
             .update_many(
                 doc! {
-                    "user_id": user_id,
-                    "subscription.auth": fcm_token,
-                    "subscription.endpoint": "fcm"
+                    "_id": {
+                        "$in": session_ids
+                    }
                 },
                 doc! {
                     "$unset": {
@@ -378,6 +423,144 @@ impl AbstractUsers for MongoDb {
             .map(|_| ())
             .map_err(|_| create_database_error!("update_one", "sessions"))
     }
+
+    /// Fetch ids of sessions belonging to the given users that want redacted push notifications
+    async fn fetch_redacted_push_session_ids(&self, user_ids: &[String]) -> Result<Vec<String>> {
+        Ok(self
+            .col::<DocumentId>("sessions")
+            .find(doc! {
+                "user_id": {
+                    "$in": user_ids
+                },
+                "push_redacted": true
+            })
+            .with_options(FindOptions::builder().projection(doc! { "_id": 1 }).build())
+            .await
+            .map_err(|_| create_database_error!("find", "sessions"))?
+            .filter_map(|s| async { s.ok() })
+            .map(|session| session.id)
+            .collect()
+            .await)
+    }
+
+    /// Set whether push notifications for a session should have their content redacted
+    async fn set_session_push_redacted(&self, session_id: &str, redacted: bool) -> Result<()> {
+        self.col::<Session>("sessions")
+            .update_one(
+                doc! {
+                    "_id": session_id
+                },
+                doc! {
+                    "$set": {
+                        "push_redacted": redacted
+                    }
+                },
+            )
+            .await
+            .map(|_| ())
+            .map_err(|_| create_database_error!("update_one", "sessions"))
+    }
+
+    /// Set when the push subscription of a session expires, if it is known
+    async fn set_session_push_expiry(
+        &self,
+        session_id: &str,
+        expires_at: Option<Timestamp>,
+    ) -> Result<()> {
+        let update = match expires_at {
+            Some(expires_at) => {
+                let formatted: &str = &expires_at.format();
+                doc! {
+                    "$set": {
+                        "push_expires_at": formatted
+                    }
+                }
+            }
+            None => doc! {
+                "$unset": {
+                    "push_expires_at": 1
+                }
+            },
+        };
+
+        self.col::<Session>("sessions")
+            .update_one(
+                doc! {
+                    "_id": session_id
+                },
+                update,
+            )
+            .await
+            .map(|_| ())
+            .map_err(|_| create_database_error!("update_one", "sessions"))
+    }
+
+    /// Remove push subscriptions which are past their expiration
+    async fn prune_push_subscriptions(
+        &self,
+        now: Timestamp,
+        stale_before: Option<Timestamp>,
+    ) -> Result<u64> {
+        let now: &str = &now.format();
+        let mut conditions = vec![doc! {
+            "push_expires_at": {
+                "$lt": now
+            }
+        }];
+
+        if let Some(stale_before) = stale_before {
+            let stale_before: &str = &stale_before.format();
+            conditions.push(doc! {
+                "push_expires_at": {
+                    "$exists": false
+                },
+                "last_seen": {
+                    "$lt": stale_before
+                }
+            });
+        }
+
+        self.col::<Session>("sessions")
+            .update_many(
+                doc! {
+                    "subscription": {
+                        "$exists": true
+                    },
+                    "$or": conditions
+                },
+                doc! {
+                    "$unset": {
+                        "subscription": 1,
+                        "push_expires_at": 1
+                    }
+                },
+            )
+            .await
+            .map(|result| result.modified_count)
+            .map_err(|_| create_database_error!("update_many", "sessions"))
+    }
+
+    /// Fetch sessions belonging to the given users that have a push subscription
+    async fn fetch_push_subscribed_sessions(
+        &self,
+        user_ids: &[String],
+    ) -> Result<Vec<PushSubscriptionSession>> {
+        Ok(self
+            .col::<PushSubscriptionSession>("sessions")
+            .find(doc! {
+                "user_id": {
+                    "$in": user_ids
+                },
+                "subscription": {
+                    "$exists": true
+                }
+            })
+            .await
+            .map_err(|_| create_database_error!("find", "sessions"))?
+            .filter_map(|s| async { s.ok() })
+            .collect()
+            .await)
+    }
 }
 
 impl IntoDocumentPath for FieldsUser {
@@ -390,6 +573,7 @@ impl IntoDocumentPath for FieldsUser {
             FieldsUser::StatusText => "status.text",
             FieldsUser::DisplayName => "display_name",
             FieldsUser::Suspension => "suspended_until",
+            FieldsUser::NotificationSnooze => "notifications_snoozed_until",
             FieldsUser::None => "none",
         })
     }
@@ -1,11 +1,11 @@
-use authifier::models::Session;
+use authifier::models::{Session, WebPushSubscription};
 use iso8601_timestamp::Timestamp;
 use revolt_result::Result;
 
 use crate::{FieldsUser, PartialUser, RelationshipStatus, User};
 use crate::{ReferenceDb, Relationship};
 
-use super::AbstractUsers;
+use super::{AbstractUsers, PushSubscriptionSession};
 
 #[async_trait]
 impl AbstractUsers for ReferenceDb {
@@ -170,12 +170,58 @@ impl AbstractUsers for ReferenceDb {
         todo!()
     }
 
-    /// Remove duplicate FCM subscriptions for a user (keep only the new one)
-    async fn remove_duplicate_fcm_subscriptions(&self, _user_id: &str, _fcm_token: &str) -> Result<()> {
+    /// Remove the given push subscription from the user's other sessions
+    async fn remove_duplicate_push_subscriptions(
+        &self,
+        _user_id: &str,
+        _session_id: &str,
+        _subscription: &WebPushSubscription,
+    ) -> Result<Vec<String>> {
+        Ok(vec![])
+    }
+
+    /// Remove the push subscriptions of the given sessions
+    async fn remove_push_subscriptions(&self, _session_ids: &[String]) -> Result<()> {
         Ok(())
     }
 
     async fn update_session_last_seen(&self, _session_id: &str, _when: Timestamp) -> Result<()> {
         todo!()
     }
+
+    /// Fetch ids of sessions belonging to the given users that want redacted push notifications
+    async fn fetch_redacted_push_session_ids(&self, _user_ids: &[String]) -> Result<Vec<String>> {
+        Ok(vec![])
+    }
+
+    /// Set whether push notifications for a session should have their content redacted
+    async fn set_session_push_redacted(&self, _session_id: &str, _redacted: bool) -> Result<()> {
+        Ok(())
+    }
+
+    /// Set when the push subscription of a session expires, if it is known
+    async fn set_session_push_expiry(
+        &self,
+        _session_id: &str,
+        _expires_at: Option<Timestamp>,
+    ) -> Result<()> {
+        Ok(())
+    }
+
+    /// Remove push subscriptions which are past their expiration
+    async fn prune_push_subscriptions(
+        &self,
+        _now: Timestamp,
+        _stale_before: Option<Timestamp>,
+    ) -> Result<u64> {
+        Ok(0)
+    }
+
+    /// Fetch sessions belonging to the given users that have a push subscription
+    async fn fetch_push_subscribed_sessions(
+        &self,
+        _user_ids: &[String],
+    ) -> Result<Vec<PushSubscriptionSession>> {
+        Ok(vec![])
+    }
 }
@@ -21,8 +21,15 @@ use crate::Channel::{TextChannel, VoiceChannel};
 pub enum AckEvent {
     /// Add mentions for a channel
     ProcessMessage {
-        /// push notification, message, recipients, push silenced
-        messages: Vec<(Option<PushNotification>, Message, Vec<String>, bool)>,
+        /// push notification, message, recipients, push silenced, trace id
+        #[allow(clippy::type_complexity)]
+        messages: Vec<(
+            Option<PushNotification>,
+            Message,
+            Vec<String>,
+            bool,
+            Option<String>,
+        )>,
     },
 
     /// Acknowledge message in a channel for a user
@@ -105,12 +112,16 @@ pub async fn handle_ack_event(
 
                 if mentions_acked > 0 {
                     if let Err(err) = amqp
-                        .ack_message(user.to_string(), channel.to_string(), id.to_owned())
+                        .buffer_ack(user.to_string(), channel.to_string(), id.to_owned())
                         .await
                     {
                         revolt_config::capture_error(&err);
                     }
-                };
+                } else {
+                    amqp.cancel_pending_notification(user, channel, id).await;
+                }
+            } else {
+                amqp.cancel_pending_notification(user, channel, id).await;
             }
         }
         AckEvent::ProcessMessage { messages } => {
@@ -122,7 +133,7 @@ pub async fn handle_ack_event(
             );
 
             // find all the users we'll be notifying
-            messages.iter().for_each(|(_, _, recipents, _)| {
+            messages.iter().for_each(|(_, _, recipents, _, _)| {
                 users.extend(recipents.iter());
             });
 
@@ -131,7 +142,7 @@ pub async fn handle_ack_event(
             for user in users {
                 let message_ids: Vec<String> = messages
                     .iter()
-                    .filter_map(|(_, message, recipients, _)| {
+                    .filter_map(|(_, message, recipients, _, _)| {
                         if recipients.contains(user) {
                             Some(message.id.clone())
                         } else {
@@ -149,7 +160,7 @@ pub async fn handle_ack_event(
 
             let mut mass_mentions = vec![];
 
-            for (push, message, recipients, silenced) in messages {
+            for (push, message, recipients, silenced, trace_id) in messages {
                 if *silenced
                     || push.is_none()
                     || (recipients.is_empty() && !message.contains_mass_push_mention())
@@ -168,8 +179,13 @@ pub async fn handle_ack_event(
                     push.as_ref().unwrap().message.id,
                     recipients.len()
                 );
+                let amqp = match trace_id {
+                    Some(trace_id) => amqp.traced(trace_id.clone()),
+                    None => amqp.clone(),
+                };
+
                 if let Err(err) = amqp
-                    .message_sent(recipients.clone(), push.clone().unwrap())
+                    .message_sent(db, recipients.clone(), push.clone().unwrap(), false, false)
                     .await
                 {
                     revolt_config::capture_error(&err);
@@ -0,0 +1,26 @@
+// Queue Type: Polling
+use std::time::Duration;
+
+use async_std::task::sleep;
+
+use crate::AMQP;
+
+/// Interval to check back at while ack batching is disabled
+const IDLE_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Start a new worker which periodically publishes buffered acks
+pub async fn worker(amqp: AMQP) {
+    loop {
+        let config = revolt_config::config().await;
+        let interval = match config.pushd.ack_batch_interval {
+            0 => IDLE_INTERVAL,
+            interval => Duration::from_millis(interval),
+        };
+
+        sleep(interval).await;
+
+        if let Err(err) = amqp.flush_acks().await {
+            warn!("Failed to publish batch of acks: {err:?}");
+        }
+    }
+}
@@ -3,6 +3,7 @@ use authifier::AuthifierEvent;
 use once_cell::sync::Lazy;
 
 use crate::events::client::EventV1;
+use crate::util::activity::{clear_session_activity, clear_user_activity};
 
 static Q: Lazy<(Sender<AuthifierEvent>, Receiver<AuthifierEvent>)> = Lazy::new(unbounded);
 
@@ -19,8 +20,27 @@ pub async fn worker() {
             AuthifierEvent::CreateSession { .. } | AuthifierEvent::CreateAccount { .. } => {
                 EventV1::Auth(event).global().await
             }
-            AuthifierEvent::DeleteSession { user_id, .. }
-            | AuthifierEvent::DeleteAllSessions { user_id, .. } => {
+            AuthifierEvent::DeleteSession {
+                user_id,
+                session_id,
+            } => {
+                // Stop a logged out session from suppressing notifications
+                if let Err(err) = clear_session_activity(user_id, session_id).await {
+                    revolt_config::capture_error(&err);
+                }
+
+                let id = user_id.to_string();
+                EventV1::Auth(event).private(id).await
+            }
+            AuthifierEvent::DeleteAllSessions {
+                user_id,
+                exclude_session_id,
+            } => {
+                if let Err(err) = clear_user_activity(user_id, exclude_session_id.as_deref()).await
+                {
+                    revolt_config::capture_error(&err);
+                }
+
                 let id = user_id.to_string();
                 EventV1::Auth(event).private(id).await
             }
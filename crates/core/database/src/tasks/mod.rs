@@ -8,13 +8,17 @@ use std::time::Instant;
 const WORKER_COUNT: usize = 5;
 
 pub mod ack;
+pub mod ack_batch;
 pub mod authifier_relay;
 pub mod last_message_id;
+pub mod outbox;
 pub mod process_embeds;
 
 /// Spawn background workers
 pub fn start_workers(db: Database, amqp: AMQP) {
     task::spawn(authifier_relay::worker());
+    task::spawn(outbox::worker(amqp.clone()));
+    task::spawn(ack_batch::worker(amqp.clone()));
 
     for _ in 0..WORKER_COUNT {
         task::spawn(ack::worker(db.clone(), amqp.clone()));
@@ -0,0 +1,52 @@
+// Queue Type: Polling
+use std::time::Duration;
+
+use async_std::task::sleep;
+
+use crate::{amqp::outbox, AMQP};
+
+/// Number of entries retried on each pass over the outbox
+const BATCH_SIZE: isize = 100;
+
+/// Start a new worker which republishes notifications left in the outbox
+///
+/// Entries are claimed before being republished, so instances each running a
+/// worker don't republish the same notification.
+pub async fn worker(amqp: AMQP) {
+    loop {
+        let config = revolt_config::config().await;
+        sleep(Duration::from_secs(config.pushd.outbox_retry_after)).await;
+
+        if !config.pushd.outbox {
+            continue;
+        }
+
+        let entries = match outbox::claim_unsent(config.pushd.outbox_retry_after, BATCH_SIZE).await
+        {
+            Ok(entries) => entries,
+            Err(err) => {
+                error!("Failed to fetch notification outbox: {err:?}");
+                continue;
+            }
+        };
+
+        for entry in entries {
+            if outbox::is_expired(&entry, config.pushd.outbox_max_age) {
+                warn!(
+                    "Dropping notification {} on {} after {} seconds",
+                    entry.id, entry.routing_key, config.pushd.outbox_max_age
+                );
+            } else if let Err(err) = amqp.republish(&entry).await {
+                warn!("Failed to republish notification {}: {err:?}", entry.id);
+                continue;
+            }
+
+            if let Err(err) = outbox::mark_sent(&entry.id).await {
+                error!(
+                    "Failed to clear notification {} from outbox: {err:?}",
+                    entry.id
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,285 @@
+use std::{
+    collections::{HashMap, HashSet},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use redis_kiss::{redis::AsyncIter, AsyncCommands, Conn};
+use revolt_result::Result;
+
+use crate::util::retry::redis_connection;
+
+/// How long a channel stays open for a session without being refreshed (in seconds)
+pub const ACTIVITY_TTL: u64 = 300;
+
+/// Key of the set of channels a session currently has open
+pub fn open_channels_key(user_id: &str, session_id: &str) -> String {
+    format!("open_channels:{}:{}", user_id, session_id)
+}
+
+/// Key marking a session whose window is not focused
+///
+/// Channels open in such a session don't count as being viewed.
+pub fn unfocused_session_key(user_id: &str, session_id: &str) -> String {
+    format!("unfocused_session:{}:{}", user_id, session_id)
+}
+
+/// Key of the sorted set of a user's sessions, scored by the unix time they were last active
+pub fn session_activity_key(user_id: &str) -> String {
+    format!("session_activity:{}", user_id)
+}
+
+/// Key of the sorted set indexing which sessions have a channel open
+///
+/// Members are `{user_id}:{session_id}`, scored by the unix time at which they expire.
+pub fn channel_viewers_key(channel_id: &str) -> String {
+    format!("channel_viewers:{}", channel_id)
+}
+
+/// Key of the last channel a user opened in any session
+///
+/// Holds `{channel_id}:{unix time}`.
+pub fn last_channel_key(user_id: &str) -> String {
+    format!("last_channel:{}", user_id)
+}
+
+/// Keep a session's open channels for at least `ttl` more seconds
+///
+/// The set is shared by every channel the session has open, so its expiry is only ever extended.
+pub async fn extend_session_expiry(conn: &mut Conn, session_key: &str, ttl: u64) -> Result<()> {
+    let current: i64 = conn
+        .ttl(session_key)
+        .await
+        .map_err(|_| create_database_error!("ttl", "open_channels"))?;
+
+    if current < ttl as i64 {
+        let _: () = conn
+            .expire(session_key, ttl as usize)
+            .await
+            .map_err(|_| create_database_error!("expire", "open_channels"))?;
+    }
+
+    Ok(())
+}
+
+/// How long a user's session activity is kept around without any session being active (in seconds)
+const SESSION_ACTIVITY_TTL: u64 = 30 * 24 * 60 * 60;
+
+/// How long the last channel a user opened is remembered (in seconds)
+const LAST_CHANNEL_TTL: u64 = 30 * 24 * 60 * 60;
+
+/// Record that a session was just active
+pub async fn touch_session(user_id: &str, session_id: &str) -> Result<()> {
+    let mut conn = redis_connection().await?;
+
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("Time went backwards")
+        .as_secs();
+
+    let key = session_activity_key(user_id);
+    let _: () = conn
+        .zadd(&key, session_id, now)
+        .await
+        .map_err(|_| create_database_error!("zadd", "session_activity"))?;
+
+    conn.expire::<_, ()>(&key, SESSION_ACTIVITY_TTL as usize)
+        .await
+        .map_err(|_| create_database_error!("expire", "session_activity"))
+}
+
+/// Remember that a user just opened a channel
+pub async fn record_last_channel(user_id: &str, channel_id: &str) -> Result<()> {
+    let mut conn = redis_connection().await?;
+
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("Time went backwards")
+        .as_secs();
+
+    conn.set_ex::<_, _, ()>(
+        last_channel_key(user_id),
+        format!("{channel_id}:{now}"),
+        LAST_CHANNEL_TTL as usize,
+    )
+    .await
+    .map_err(|_| create_database_error!("set_ex", "last_channel"))
+}
+
+/// Fetch the last channel a user opened in any session, and when as unix time
+pub async fn fetch_last_channel(user_id: &str) -> Result<Option<(String, u64)>> {
+    let mut conn = redis_connection().await?;
+
+    let value: Option<String> = conn
+        .get(last_channel_key(user_id))
+        .await
+        .map_err(|_| create_database_error!("get", "last_channel"))?;
+
+    Ok(value.and_then(|value| {
+        let (channel_id, opened_at) = value.split_once(':')?;
+        Some((channel_id.to_string(), opened_at.parse().ok()?))
+    }))
+}
+
+/// Fetch when each of a user's sessions was last active, as unix time
+///
+/// Sessions which haven't been active since activity was last cleared are left out.
+pub async fn fetch_session_activity(user_id: &str) -> Result<HashMap<String, u64>> {
+    let mut conn = redis_connection().await?;
+
+    let sessions: Vec<(String, u64)> = conn
+        .zrange_withscores(session_activity_key(user_id), 0, -1)
+        .await
+        .map_err(|_| create_database_error!("zrange", "session_activity"))?;
+
+    Ok(sessions.into_iter().collect())
+}
+
+/// Extend how long a session keeps a channel open, if it has it open at all
+///
+/// Used when the session shows it is still reading, such as by acking a message.
+/// Channels opened for longer than [`ACTIVITY_TTL`] through a TTL hint are left alone.
+pub async fn refresh_session_activity(
+    user_id: &str,
+    session_id: &str,
+    channel_id: &str,
+) -> Result<()> {
+    let mut conn = redis_connection().await?;
+
+    let session_key = open_channels_key(user_id, session_id);
+    let open: bool = conn
+        .sismember(&session_key, channel_id)
+        .await
+        .map_err(|_| create_database_error!("sismember", "open_channels"))?;
+
+    if !open {
+        return Ok(());
+    }
+
+    let expires_at = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("Time went backwards")
+        .as_secs()
+        + ACTIVITY_TTL;
+
+    let viewers_key = channel_viewers_key(channel_id);
+    let viewer = format!("{}:{}", user_id, session_id);
+    let current: Option<u64> = conn
+        .zscore(&viewers_key, &viewer)
+        .await
+        .map_err(|_| create_database_error!("zscore", "channel_viewers"))?;
+
+    if current.is_some_and(|current| current >= expires_at) {
+        return Ok(());
+    }
+
+    extend_session_expiry(&mut conn, &session_key, ACTIVITY_TTL).await?;
+
+    let _: () = conn
+        .zadd(&viewers_key, &viewer, expires_at)
+        .await
+        .map_err(|_| create_database_error!("zadd", "channel_viewers"))?;
+
+    // Other sessions may have the channel open for longer
+    let config = revolt_config::config().await;
+    conn.expire::<_, ()>(
+        &viewers_key,
+        ACTIVITY_TTL.max(config.api.users.max_activity_ttl) as usize,
+    )
+    .await
+    .map_err(|_| create_database_error!("expire", "channel_viewers"))
+}
+
+/// Clear everything a session has open, such as when it is logged out
+///
+/// Does nothing if the session has no channels open.
+pub async fn clear_session_activity(user_id: &str, session_id: &str) -> Result<()> {
+    let mut conn = redis_connection().await?;
+
+    let session_key = open_channels_key(user_id, session_id);
+    let channels: Vec<String> = conn
+        .smembers(&session_key)
+        .await
+        .map_err(|_| create_database_error!("smembers", "open_channels"))?;
+
+    let viewer = format!("{}:{}", user_id, session_id);
+    for channel_id in channels {
+        let _: () = conn
+            .zrem(channel_viewers_key(&channel_id), &viewer)
+            .await
+            .map_err(|_| create_database_error!("zrem", "channel_viewers"))?;
+    }
+
+    let _: () = conn
+        .zrem(session_activity_key(user_id), session_id)
+        .await
+        .map_err(|_| create_database_error!("zrem", "session_activity"))?;
+
+    conn.del::<_, ()>(&[session_key, unfocused_session_key(user_id, session_id)])
+        .await
+        .map_err(|_| create_database_error!("del", "open_channels"))
+}
+
+/// Fetch the channels a single session currently has open
+pub async fn fetch_session_open_channels(
+    user_id: &str,
+    session_id: &str,
+) -> Result<HashSet<String>> {
+    let mut conn = redis_connection().await?;
+
+    conn.smembers(open_channels_key(user_id, session_id))
+        .await
+        .map_err(|_| create_database_error!("smembers", "open_channels"))
+}
+
+/// Fetch every channel the user has open in any of their sessions
+pub async fn fetch_open_channels(user_id: &str) -> Result<HashSet<String>> {
+    let mut conn = redis_connection().await?;
+
+    let mut keys = vec![];
+    {
+        let mut iter: AsyncIter<String> = conn
+            .scan_match(open_channels_key(user_id, "*"))
+            .await
+            .map_err(|_| create_database_error!("scan", "open_channels"))?;
+
+        while let Some(key) = iter.next_item().await {
+            keys.push(key);
+        }
+    }
+
+    if keys.is_empty() {
+        return Ok(HashSet::new());
+    }
+
+    conn.sunion(keys)
+        .await
+        .map_err(|_| create_database_error!("sunion", "open_channels"))
+}
+
+/// Clear activity of all of a user's sessions, optionally sparing one
+pub async fn clear_user_activity(user_id: &str, except_session_id: Option<&str>) -> Result<()> {
+    let mut conn = redis_connection().await?;
+
+    let prefix = open_channels_key(user_id, "");
+    let mut session_ids = vec![];
+    {
+        let mut iter: AsyncIter<String> = conn
+            .scan_match(format!("{prefix}*"))
+            .await
+            .map_err(|_| create_database_error!("scan", "open_channels"))?;
+
+        while let Some(key) = iter.next_item().await {
+            if let Some(session_id) = key.strip_prefix(&prefix) {
+                session_ids.push(session_id.to_string());
+            }
+        }
+    }
+
+    for session_id in session_ids {
+        if Some(session_id.as_str()) != except_session_id {
+            clear_session_activity(user_id, &session_id).await?;
+        }
+    }
+
+    Ok(())
+}
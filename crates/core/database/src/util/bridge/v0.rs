@@ -188,6 +188,7 @@ impl From<crate::Channel> for Channel {
                 default_permissions,
                 role_permissions,
                 nsfw,
+                unmask_spoilers,
             } => Channel::TextChannel {
                 id,
                 server,
@@ -198,6 +199,7 @@ impl From<crate::Channel> for Channel {
                 default_permissions,
                 role_permissions,
                 nsfw,
+                unmask_spoilers,
             },
             crate::Channel::VoiceChannel {
                 id,
@@ -268,6 +270,7 @@ impl From<Channel> for crate::Channel {
                 default_permissions,
                 role_permissions,
                 nsfw,
+                unmask_spoilers,
             } => crate::Channel::TextChannel {
                 id,
                 server,
@@ -278,6 +281,7 @@ impl From<Channel> for crate::Channel {
                 default_permissions,
                 role_permissions,
                 nsfw,
+                unmask_spoilers,
             },
             Channel::VoiceChannel {
                 id,
@@ -310,6 +314,7 @@ impl From<crate::PartialChannel> for PartialChannel {
             description: value.description,
             icon: value.icon.map(|file| file.into()),
             nsfw: value.nsfw,
+            unmask_spoilers: value.unmask_spoilers,
             active: value.active,
             permissions: value.permissions,
             role_permissions: value.role_permissions,
@@ -327,6 +332,7 @@ impl From<PartialChannel> for crate::PartialChannel {
             description: value.description,
             icon: value.icon.map(|file| file.into()),
             nsfw: value.nsfw,
+            unmask_spoilers: value.unmask_spoilers,
             active: value.active,
             permissions: value.permissions,
             role_permissions: value.role_permissions,
@@ -402,6 +408,11 @@ impl From<crate::File> for File {
             user_id: value.user_id,
             server_id: value.server_id,
             object_id: value.object_id,
+            snippet: None,
+            url: None,
+            width: None,
+            height: None,
+            index: None,
         }
     }
 }
@@ -758,6 +769,7 @@ impl From<crate::Server> for Server {
             nsfw: value.nsfw,
             analytics: value.analytics,
             discoverable: value.discoverable,
+            notify_member_joins: value.notify_member_joins,
         }
     }
 }
@@ -786,6 +798,7 @@ impl From<Server> for crate::Server {
             nsfw: value.nsfw,
             analytics: value.analytics,
             discoverable: value.discoverable,
+            notify_member_joins: value.notify_member_joins,
         }
     }
 }
@@ -812,6 +825,7 @@ impl From<crate::PartialServer> for PartialServer {
             nsfw: value.nsfw,
             analytics: value.analytics,
             discoverable: value.discoverable,
+            notify_member_joins: value.notify_member_joins,
         }
     }
 }
@@ -838,6 +852,7 @@ impl From<PartialServer> for crate::PartialServer {
             nsfw: value.nsfw,
             analytics: value.analytics,
             discoverable: value.discoverable,
+            notify_member_joins: value.notify_member_joins,
         }
     }
 }
@@ -1201,6 +1216,8 @@ impl From<User> for crate::User {
             privileged: value.privileged,
             bot: value.bot.map(Into::into),
             suspended_until: None,
+            notifications_snoozed_until: None,
+            push_primary_session_only: false,
             last_acknowledged_policy_change: Timestamp::UNIX_EPOCH,
         }
     }
@@ -1257,6 +1274,7 @@ impl From<crate::FieldsUser> for FieldsUser {
             crate::FieldsUser::DisplayName => FieldsUser::DisplayName,
 
             crate::FieldsUser::Suspension => FieldsUser::Internal,
+            crate::FieldsUser::NotificationSnooze => FieldsUser::Internal,
             crate::FieldsUser::None => FieldsUser::Internal,
         }
     }
@@ -55,6 +55,24 @@ impl<'z, 'x> BulkDatabasePermissionQuery<'x> {
         };
         member_perms
     }
+
+    /// Whether each member can both see the channel and read its message history
+    pub async fn members_can_read_channel(&'z mut self) -> HashMap<String, bool>
+    where
+        'z: 'x,
+    {
+        calculate_members_permissions(self)
+            .await
+            .iter()
+            .map(|(m, p)| {
+                (
+                    m.clone(),
+                    p.has_channel_permission(ChannelPermission::ViewChannel)
+                        && p.has_channel_permission(ChannelPermission::ReadMessageHistory),
+                )
+            })
+            .collect()
+    }
 }
 
 impl<'z> BulkDatabasePermissionQuery<'z> {
@@ -0,0 +1,67 @@
+use std::collections::HashSet;
+
+use redis_kiss::AsyncCommands;
+use revolt_result::Result;
+
+use crate::events::rabbit::DeliveryReceipt;
+use crate::util::retry::redis_connection;
+
+/// How long delivery receipts are kept for (in seconds)
+const DELIVERY_TTL: u64 = 24 * 60 * 60;
+
+/// Key of the receipt for a message notification delivered to a user
+pub fn delivery_key(user_id: &str, message_id: &str) -> String {
+    format!("delivered:{}:{}", user_id, message_id)
+}
+
+/// Record that a message notification reached one of the user's devices
+///
+/// Only the latest delivery to any of the user's sessions is kept.
+pub async fn record_delivery(receipt: &DeliveryReceipt) -> Result<()> {
+    let mut conn = redis_connection().await?;
+
+    conn.set_ex::<_, _, ()>(
+        delivery_key(&receipt.user_id, &receipt.message_id),
+        serde_json::to_string(receipt).unwrap(),
+        DELIVERY_TTL as usize,
+    )
+    .await
+    .map_err(|_| create_database_error!("set_ex", "delivered"))
+}
+
+/// Fetch the latest delivery of a message notification to a user
+pub async fn fetch_delivery(user_id: &str, message_id: &str) -> Result<Option<DeliveryReceipt>> {
+    let mut conn = redis_connection().await?;
+
+    let receipt: Option<String> = conn
+        .get(delivery_key(user_id, message_id))
+        .await
+        .map_err(|_| create_database_error!("get", "delivered"))?;
+
+    Ok(receipt.and_then(|receipt| serde_json::from_str(&receipt).ok()))
+}
+
+/// Find which of the given users were already delivered a message notification
+pub async fn fetch_delivered(user_ids: &[String], message_id: &str) -> Result<HashSet<String>> {
+    if user_ids.is_empty() {
+        return Ok(HashSet::new());
+    }
+
+    let mut conn = redis_connection().await?;
+
+    let keys: Vec<String> = user_ids
+        .iter()
+        .map(|user_id| delivery_key(user_id, message_id))
+        .collect();
+    let receipts: Vec<Option<String>> = conn
+        .mget(keys)
+        .await
+        .map_err(|_| create_database_error!("mget", "delivered"))?;
+
+    Ok(user_ids
+        .iter()
+        .zip(receipts)
+        .filter(|(_, receipt)| receipt.is_some())
+        .map(|(user_id, _)| user_id.clone())
+        .collect())
+}
@@ -0,0 +1,85 @@
+use redis_kiss::{redis::AsyncIter, AsyncCommands};
+use revolt_result::Result;
+
+use crate::util::retry::redis_connection;
+
+/// Key of the list of message ids waiting to be sent to a user as a digest
+///
+/// `kind` distinguishes between digests, such as `mention` or `message`.
+pub fn digest_key(kind: &str, user_id: &str, channel_id: &str) -> String {
+    format!("{}_digest:{}:{}", kind, user_id, channel_id)
+}
+
+/// Digest which has not been sent yet
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PendingDigest {
+    /// Kind of digest
+    pub kind: String,
+    /// Channel the digest is for
+    pub channel_id: String,
+    /// Number of entries waiting in the digest
+    pub count: usize,
+}
+
+/// Split a digest key into its kind and channel id, if it belongs to the user
+fn parse_digest_key<'a>(key: &'a str, user_id: &str) -> Option<(&'a str, &'a str)> {
+    let (kind, rest) = key.split_once("_digest:")?;
+    let channel_id = rest.strip_prefix(user_id)?.strip_prefix(':')?;
+    if channel_id.is_empty() {
+        return None;
+    }
+
+    Some((kind, channel_id))
+}
+
+/// List all digests still waiting to be sent to a user, along with how many entries they hold
+pub async fn pending_digests(user_id: &str) -> Result<Vec<PendingDigest>> {
+    let mut conn = redis_connection().await?;
+
+    let mut keys = vec![];
+    {
+        let mut iter: AsyncIter<String> = conn
+            .scan_match(digest_key("*", user_id, "*"))
+            .await
+            .map_err(|_| create_database_error!("scan", "digest"))?;
+
+        while let Some(key) = iter.next_item().await {
+            keys.push(key);
+        }
+    }
+
+    let mut digests = vec![];
+    for key in keys {
+        let Some((kind, channel_id)) = parse_digest_key(&key, user_id) else {
+            continue;
+        };
+
+        let count: usize = conn
+            .llen(&key)
+            .await
+            .map_err(|_| create_database_error!("llen", "digest"))?;
+
+        digests.push(PendingDigest {
+            kind: kind.to_string(),
+            channel_id: channel_id.to_string(),
+            count,
+        });
+    }
+
+    Ok(digests)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{digest_key, parse_digest_key};
+
+    #[test]
+    fn parses_only_keys_for_the_user() {
+        let key = digest_key("mention", "user", "channel");
+        assert_eq!(parse_digest_key(&key, "user"), Some(("mention", "channel")));
+
+        assert_eq!(parse_digest_key(&key, "use"), None);
+        assert_eq!(parse_digest_key("mention_digest:user:", "user"), None);
+        assert_eq!(parse_digest_key("open_channels:user:session", "user"), None);
+    }
+}
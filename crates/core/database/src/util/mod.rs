@@ -1,6 +1,11 @@
+pub mod activity;
 pub mod bridge;
 pub mod bulk_permissions;
+pub mod delivery;
+pub mod digest;
 pub mod idempotency;
 pub mod permissions;
 pub mod reference;
+pub mod retry;
 pub mod test_fixtures;
+pub mod trace;
@@ -0,0 +1,139 @@
+use std::{future::Future, time::Duration};
+
+use rand::Rng;
+use redis_kiss::{get_connection, Conn};
+use revolt_result::{Error, ErrorType, Result};
+
+/// Upper bound on how long to wait between any two attempts
+const MAX_BACKOFF: Duration = Duration::from_secs(5);
+
+/// Number of attempts made to get a Redis connection
+const REDIS_ATTEMPTS: usize = 3;
+
+/// Base delay between attempts to get a Redis connection
+const REDIS_BACKOFF: Duration = Duration::from_millis(25);
+
+/// Check whether an error is likely to go away if the operation is retried
+pub fn is_transient(error: &Error) -> bool {
+    matches!(
+        error.error_type,
+        ErrorType::DatabaseError { .. } | ErrorType::InternalError
+    )
+}
+
+/// How long to wait after the given (1-based) failed attempt
+///
+/// The delay doubles with each attempt up to a cap, and a random half of it is
+/// dropped so that callers which failed together don't all retry at the same time.
+pub fn jittered_backoff(base: Duration, attempt: usize) -> Duration {
+    let exponent = attempt.saturating_sub(1).min(16) as u32;
+    let delay = base.saturating_mul(1 << exponent).min(MAX_BACKOFF);
+    let half = delay / 2;
+    half + half.mul_f64(rand::thread_rng().gen_range(0.0..=1.0))
+}
+
+/// Run an operation, retrying transient failures until `attempts` runs have been made
+///
+/// Waits an exponentially growing, jittered multiple of `backoff` between each run.
+pub async fn retry_transient<T, F, Fut>(
+    attempts: usize,
+    backoff: Duration,
+    mut operation: F,
+) -> Result<T>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T>>,
+{
+    let mut attempt = 1;
+    loop {
+        match operation().await {
+            Err(err) if attempt < attempts && is_transient(&err) => {
+                debug!("Retrying after transient failure on attempt {attempt}: {err:?}");
+                async_std::task::sleep(jittered_backoff(backoff, attempt)).await;
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Get a Redis connection, retrying with jittered backoff while Redis is unavailable
+///
+/// All Redis-touching code should go through this so retries after Redis recovers
+/// are spread out rather than arriving at once.
+pub async fn redis_connection() -> Result<Conn> {
+    retry_transient(REDIS_ATTEMPTS, REDIS_BACKOFF, || async {
+        get_connection()
+            .await
+            .map_err(|_| create_database_error!("get_connection", "redis"))
+    })
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::atomic::{AtomicUsize, Ordering},
+        time::Duration,
+    };
+
+    use super::{jittered_backoff, retry_transient, MAX_BACKOFF};
+
+    #[async_std::test]
+    async fn retries_transient_errors_until_success() {
+        let calls = AtomicUsize::new(0);
+        let result = retry_transient(3, Duration::ZERO, || {
+            let call = calls.fetch_add(1, Ordering::SeqCst);
+            async move {
+                if call < 2 {
+                    Err(create_database_error!("update_many", "sessions"))
+                } else {
+                    Ok(call)
+                }
+            }
+        })
+        .await;
+
+        assert_eq!(result.unwrap(), 2);
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+    }
+
+    #[async_std::test]
+    async fn gives_up_after_attempts() {
+        let calls = AtomicUsize::new(0);
+        let result: revolt_result::Result<()> = retry_transient(3, Duration::ZERO, || {
+            calls.fetch_add(1, Ordering::SeqCst);
+            async { Err(create_database_error!("update_many", "sessions")) }
+        })
+        .await;
+
+        assert!(result.is_err());
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+    }
+
+    #[async_std::test]
+    async fn does_not_retry_other_errors() {
+        let calls = AtomicUsize::new(0);
+        let result: revolt_result::Result<()> = retry_transient(3, Duration::ZERO, || {
+            calls.fetch_add(1, Ordering::SeqCst);
+            async { Err(create_error!(NotFound)) }
+        })
+        .await;
+
+        assert!(result.is_err());
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn backoff_grows_exponentially_with_jitter() {
+        let base = Duration::from_millis(100);
+        for attempt in 1..=4 {
+            let delay = jittered_backoff(base, attempt);
+            let full = base * (1 << (attempt - 1));
+            assert!(delay >= full / 2 && delay <= full, "{delay:?} for {attempt}");
+        }
+
+        assert!(jittered_backoff(base, 64) <= MAX_BACKOFF);
+        assert_eq!(jittered_backoff(Duration::ZERO, 3), Duration::ZERO);
+    }
+}
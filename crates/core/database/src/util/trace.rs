@@ -0,0 +1,86 @@
+#[cfg(feature = "rocket-impl")]
+use revolt_result::Error;
+
+/// Maximum length of a trace id supplied by a client
+const MAX_TRACE_ID_LENGTH: usize = 64;
+
+/// Id used to correlate a request with the notifications it causes
+pub struct TraceId(String);
+
+impl TraceId {
+    /// Generate a new trace id
+    pub fn generate() -> Self {
+        TraceId(ulid::Ulid::new().to_string())
+    }
+
+    pub fn into_string(self) -> String {
+        self.0
+    }
+}
+
+#[cfg(feature = "rocket-impl")]
+use revolt_rocket_okapi::{
+    gen::OpenApiGenerator,
+    request::{OpenApiFromRequest, RequestHeaderInput},
+    revolt_okapi::openapi3::{Parameter, ParameterValue},
+};
+
+#[cfg(feature = "rocket-impl")]
+use schemars::schema::{InstanceType, SchemaObject, SingleOrVec};
+
+#[cfg(feature = "rocket-impl")]
+impl OpenApiFromRequest<'_> for TraceId {
+    fn from_request_input(
+        _gen: &mut OpenApiGenerator,
+        _name: String,
+        _required: bool,
+    ) -> revolt_rocket_okapi::Result<RequestHeaderInput> {
+        Ok(RequestHeaderInput::Parameter(Parameter {
+            name: "X-Trace-Id".to_string(),
+            description: Some(
+                "Id to correlate this request with resulting notifications".to_string(),
+            ),
+            allow_empty_value: false,
+            required: false,
+            deprecated: false,
+            extensions: schemars::Map::new(),
+            location: "header".to_string(),
+            value: ParameterValue::Schema {
+                allow_reserved: false,
+                example: None,
+                examples: None,
+                explode: None,
+                style: None,
+                schema: SchemaObject {
+                    instance_type: Some(SingleOrVec::Single(Box::new(InstanceType::String))),
+                    ..Default::default()
+                },
+            },
+        }))
+    }
+}
+
+#[cfg(feature = "rocket-impl")]
+use rocket::request::{FromRequest, Outcome};
+
+#[cfg(feature = "rocket-impl")]
+#[async_trait]
+impl<'r> FromRequest<'r> for TraceId {
+    type Error = Error;
+
+    async fn from_request(request: &'r rocket::Request<'_>) -> Outcome<Self, Self::Error> {
+        // Ignore unusable ids rather than failing the request
+        if let Some(id) = request.headers().get("X-Trace-Id").next() {
+            if !id.is_empty()
+                && id.len() <= MAX_TRACE_ID_LENGTH
+                && id
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+            {
+                return Outcome::Success(TraceId(id.to_string()));
+            }
+        }
+
+        Outcome::Success(TraceId::generate())
+    }
+}
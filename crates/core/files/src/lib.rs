@@ -14,6 +14,8 @@ use aws_sdk_s3::{
 };
 
 use base64::prelude::*;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
 use tempfile::NamedTempFile;
 use tiny_skia::Pixmap;
 
@@ -48,6 +50,55 @@ pub fn create_cipher(key: &str) -> Aes256Gcm {
     Aes256Gcm::new(key)
 }
 
+/// Compute the signature for a download link to a file expiring at the given UNIX timestamp
+fn url_signature(key: &str, tag: &str, file_id: &str, expires: u64) -> Hmac<Sha256> {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(key.as_bytes()).expect("HMAC accepts keys of any size");
+    mac.update(format!("{tag}/{file_id}:{expires}").as_bytes());
+    mac
+}
+
+/// Create a download link to a file which stops working at the given UNIX timestamp
+pub fn sign_url(
+    key: &str,
+    base: &str,
+    tag: &str,
+    file_id: &str,
+    filename: &str,
+    expires: u64,
+) -> String {
+    let signature = BASE64_URL_SAFE_NO_PAD.encode(
+        url_signature(key, tag, file_id, expires)
+            .finalize()
+            .into_bytes(),
+    );
+
+    let filename = urlencoding::encode(filename);
+    format!("{base}/{tag}/{file_id}/{filename}?expires={expires}&signature={signature}")
+}
+
+/// Check that a download link was signed by us and has not yet expired
+pub fn verify_url_signature(
+    key: &str,
+    tag: &str,
+    file_id: &str,
+    expires: u64,
+    signature: &str,
+    now: u64,
+) -> bool {
+    if key.is_empty() || expires < now {
+        return false;
+    }
+
+    let Ok(signature) = BASE64_URL_SAFE_NO_PAD.decode(signature) else {
+        return false;
+    };
+
+    url_signature(key, tag, file_id, expires)
+        .verify_slice(&signature)
+        .is_ok()
+}
+
 /// Fetch a file from S3 (and decrypt it)
 pub async fn fetch_from_s3(bucket_id: &str, path: &str, nonce: &str) -> Result<Vec<u8>> {
     let config = config().await;
@@ -302,3 +353,48 @@ pub async fn create_thumbnail(image: DynamicImage, tag: &str) -> Vec<u8> {
         encoder.encode_lossless().to_vec()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{sign_url, verify_url_signature};
+
+    #[test]
+    fn signed_urls_expire() {
+        let url = sign_url("key", "https://cdn", "attachments", "file", "a.png", 100);
+        let signature = url.split("signature=").nth(1).unwrap();
+
+        let verify = |key, file_id, expires, now| {
+            verify_url_signature(key, "attachments", file_id, expires, signature, now)
+        };
+
+        assert!(verify("key", "file", 100, 50));
+        assert!(!verify("key", "file", 100, 101));
+        assert!(!verify("other", "file", 100, 50));
+        assert!(!verify("key", "other", 100, 50));
+        assert!(!verify("key", "file", 200, 50));
+    }
+
+    #[test]
+    fn signed_urls_encode_filename() {
+        let url = sign_url(
+            "key",
+            "https://cdn",
+            "attachments",
+            "file",
+            "a #1?.png",
+            100,
+        );
+        let (path, query) = url.split_once('?').unwrap();
+        assert_eq!(path, "https://cdn/attachments/file/a%20%231%3F.png");
+
+        let signature = query.strip_prefix("expires=100&signature=").unwrap();
+        assert!(verify_url_signature(
+            "key",
+            "attachments",
+            "file",
+            100,
+            signature,
+            50
+        ));
+    }
+}
@@ -24,4 +24,16 @@ auto_derived!(
         /// User Id
         pub user: String,
     }
+
+    /// Client's view of what has been read in a channel
+    pub struct DataReconcileRead {
+        /// Id of the last message the client has seen
+        pub last_seen: String,
+    }
+
+    /// Result of reconciling read state
+    pub struct ReconcileReadResponse {
+        /// Whether the server-side read state had to be moved forward
+        pub updated: bool,
+    }
 );
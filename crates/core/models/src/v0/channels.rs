@@ -1,5 +1,6 @@
 use super::File;
 
+use iso8601_timestamp::Timestamp;
 use revolt_permissions::{Override, OverrideField};
 use std::collections::{HashMap, HashSet};
 
@@ -107,6 +108,12 @@ auto_derived!(
                 serde(skip_serializing_if = "crate::if_false", default)
             )]
             nsfw: bool,
+            /// Whether notifications from this channel show spoilers instead of masking them
+            #[cfg_attr(
+                feature = "serde",
+                serde(skip_serializing_if = "crate::if_false", default)
+            )]
+            unmask_spoilers: bool,
         },
         /// Voice channel belonging to a server
         VoiceChannel {
@@ -161,6 +168,8 @@ auto_derived!(
         #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
         pub nsfw: Option<bool>,
         #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+        pub unmask_spoilers: Option<bool>,
+        #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
         pub active: Option<bool>,
         #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
         pub permissions: Option<i64>,
@@ -202,6 +211,11 @@ auto_derived!(
         /// Whether this channel is age-restricted
         pub nsfw: Option<bool>,
 
+        /// Whether notifications from this channel show spoilers instead of masking them
+        ///
+        /// Only applies to text channels.
+        pub unmask_spoilers: Option<bool>,
+
         /// Whether this channel is archived
         pub archived: Option<bool>,
 
@@ -288,6 +302,15 @@ auto_derived!(
         pub leave_silently: Option<bool>,
     }
 
+    /// Channel mute options
+    pub struct DataMuteChannel {
+        /// Time at which the mute should be lifted
+        ///
+        /// Omit to mute the channel indefinitely.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub until: Option<Timestamp>,
+    }
+
     /// Voice server token response
     pub struct LegacyCreateVoiceUserResponse {
         /// Token for authenticating with the voice server
@@ -8,6 +8,7 @@ use super::MessageSort;
 
 auto_derived!(
     /// File
+    #[derive(Default)]
     pub struct File {
         /// Unique Id
         #[cfg_attr(feature = "serde", serde(rename = "_id"))]
@@ -43,6 +44,47 @@ auto_derived!(
         /// Id of the object this file is associated with
         #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
         pub object_id: Option<String>,
+
+        /// Snippet of the content of the message this file was attached to
+        ///
+        /// Only present when requested while querying attachments.
+        #[cfg_attr(
+            feature = "serde",
+            serde(skip_serializing_if = "Option::is_none", default)
+        )]
+        pub snippet: Option<String>,
+        /// Signed link to download this file, valid for a short time
+        ///
+        /// Only present when requested while querying attachments.
+        #[cfg_attr(
+            feature = "serde",
+            serde(skip_serializing_if = "Option::is_none", default)
+        )]
+        pub url: Option<String>,
+        /// Width of this image or video, for laying out galleries
+        ///
+        /// Only present when querying attachments, if the dimensions are known.
+        #[cfg_attr(
+            feature = "serde",
+            serde(skip_serializing_if = "Option::is_none", default)
+        )]
+        pub width: Option<usize>,
+        /// Height of this image or video
+        ///
+        /// Only present when querying attachments, if the dimensions are known.
+        #[cfg_attr(
+            feature = "serde",
+            serde(skip_serializing_if = "Option::is_none", default)
+        )]
+        pub height: Option<usize>,
+        /// Position of this file among the attachments of its message, starting from 0
+        ///
+        /// Only present when querying attachments.
+        #[cfg_attr(
+            feature = "serde",
+            serde(skip_serializing_if = "Option::is_none", default)
+        )]
+        pub index: Option<usize>,
     }
 
     /// Metadata associated with a file
@@ -67,7 +109,9 @@ auto_derived!(
     #[cfg_attr(feature = "rocket", derive(FromForm))]
     pub struct OptionsQueryAttachments {
         /// Maximum number of attachments to fetch
-        #[cfg_attr(feature = "validator", validate(range(min = 1, max = 100)))]
+        ///
+        /// Set to 0 to only count matching attachments, which implies `include_total`.
+        #[cfg_attr(feature = "validator", validate(range(min = 0, max = 100)))]
         pub limit: Option<i64>,
         /// Message id before which attachments should be fetched
         #[cfg_attr(feature = "validator", validate(length(min = 26, max = 26)))]
@@ -77,11 +121,127 @@ auto_derived!(
         pub after: Option<String>,
         /// Message sort direction
         pub sort: Option<MessageSort>,
+        /// Whether to only fetch spoiler (`true`) or non-spoiler (`false`) attachments
+        ///
+        /// All attachments are fetched if not present.
+        pub spoiler: Option<bool>,
+        /// Whether to include a snippet of the message content with each attachment
+        pub include_snippet: Option<bool>,
+        /// Whether to include a short-lived signed download link with each attachment
+        pub include_url: Option<bool>,
+        /// Comma-separated list of attachment types to fetch
+        ///
+        /// Any of `file`, `text`, `image`, `video` and `audio`, e.g. `image,video`.
+        /// All attachments are fetched if not present.
+        #[cfg_attr(feature = "validator", validate(length(min = 1, max = 64)))]
+        pub content_type: Option<String>,
+        /// Whether to only fetch attachments from your own messages
+        pub mine: Option<bool>,
+        /// Minimum size of attachments to fetch, in bytes
+        pub min_size: Option<usize>,
+        /// Minimum width and height of images and videos to fetch, in pixels
+        ///
+        /// Skips small images such as stickers and inline emoji.
+        /// Other attachments, and those without known dimensions, are always fetched.
+        pub min_dimension: Option<usize>,
+        /// Whether to sort attachments by how many reactions their message has, most first
+        ///
+        /// Only the most recent 500 messages with attachments (within `before` and `after`)
+        /// are ranked, and the result is not paginated, so `has_more` is always false.
+        pub popular: Option<bool>,
+        /// Whether `limit` counts attachments rather than messages
+        ///
+        /// Pages then end after exactly `limit` attachments, even in the middle of a
+        /// message, and are continued with `next_cursor`.
+        pub by_attachment: Option<bool>,
+        /// Cursor to continue paging after, as returned in `next_cursor`
+        ///
+        /// Implies `by_attachment`, and takes precedence over `before` and `after`.
+        #[cfg_attr(feature = "validator", validate(length(min = 28, max = 48)))]
+        pub cursor: Option<String>,
+        /// Whether to include the total number of matching attachments in the response
+        ///
+        /// Counting every matching attachment requires an additional query,
+        /// so only request this when it will be displayed.
+        /// Attachments skipped by `min_size` or `min_dimension` aren't counted.
+        pub include_total: Option<bool>,
     }
 
     /// Bulk Attachments Response
     pub struct BulkAttachmentsResponse {
         /// List of attachments
+        ///
+        /// Each attachment is identified by its own file id (`_id`), which stays the
+        /// same across pages, even when several attachments belong to one message.
+        /// Attachments are ordered by their message, following the requested sort,
+        /// and within a message in the order they were attached.
         pub attachments: Vec<File>,
+        /// Whether there may be more attachments beyond this page
+        pub has_more: bool,
+        /// Message id to pass as `before` to fetch the next page
+        #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+        pub next_before: Option<String>,
+        /// Cursor to pass as `cursor` to fetch the next page, when paging by attachment
+        #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+        pub next_cursor: Option<String>,
+        /// Total number of attachments matching the query, across all pages
+        ///
+        /// Only present when `include_total` was requested, or `limit` was 0.
+        #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+        pub total: Option<usize>,
+    }
+
+    /// Options for fetching attachment storage usage
+    #[cfg_attr(feature = "validator", derive(Validate))]
+    #[cfg_attr(feature = "rocket", derive(FromForm))]
+    pub struct OptionsAttachmentUsage {
+        /// Comma-separated list of attachment types to count
+        ///
+        /// Any of `file`, `text`, `image`, `video` and `audio`, e.g. `image,video`.
+        /// All attachments are counted if not present.
+        #[cfg_attr(feature = "validator", validate(length(min = 1, max = 64)))]
+        pub content_type: Option<String>,
+    }
+
+    /// Attachment Usage Response
+    pub struct AttachmentUsageResponse {
+        /// Number of attachments in the channel
+        pub count: usize,
+        /// Total size of the attachments in bytes
+        pub size: usize,
     }
 );
+
+impl File {
+    /// Whether this file was marked as a spoiler when uploaded
+    pub fn is_spoiler(&self) -> bool {
+        self.filename.starts_with("SPOILER_")
+    }
+
+    /// Width and height of this file, if it is an image or video with known dimensions
+    pub fn dimensions(&self) -> Option<(usize, usize)> {
+        match self.metadata {
+            Metadata::Image { width, height } | Metadata::Video { width, height }
+                if width > 0 && height > 0 =>
+            {
+                Some((width, height))
+            }
+            _ => None,
+        }
+    }
+
+    /// Whether this file meets the minimum size and dimensions, if any were given
+    ///
+    /// Files without known dimensions only have to meet the minimum size.
+    pub fn is_large_enough(&self, min_size: Option<usize>, min_dimension: Option<usize>) -> bool {
+        let size = usize::try_from(self.size).unwrap_or_default();
+        if min_size.is_some_and(|min| size < min) {
+            return false;
+        }
+
+        match (min_dimension, self.dimensions()) {
+            (Some(min), Some((width, height))) => width >= min && height >= min,
+            _ => true,
+        }
+    }
+}
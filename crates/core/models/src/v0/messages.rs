@@ -214,6 +214,32 @@ auto_derived!(
         pub server: Option<String>,
     }
 
+    /// Message to preview as a push notification
+    pub struct DataPushPreview {
+        /// Id of the channel the message would be sent in
+        pub channel: String,
+        /// Message content
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub content: Option<String>,
+        /// Ids of uploaded attachments
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub attachments: Option<Vec<String>>,
+    }
+
+    /// Push notification as it would be delivered
+    pub struct PushPreview {
+        /// Notification title
+        pub title: String,
+        /// Notification body
+        pub body: String,
+        /// URL to the notification image
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub image: Option<String>,
+        /// Whether no notification would be sent at all, such as for messages
+        /// with only attachments when those are suppressed
+        pub suppressed: bool,
+    }
+
     /// Representation of a text embed before it is sent.
     #[derive(Default)]
     #[cfg_attr(feature = "validator", derive(Validate))]
@@ -430,6 +456,27 @@ impl MessageAuthor<'_> {
     }
 }
 
+impl SystemMessage {
+    /// Name of this type of system message, as it is serialised
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            SystemMessage::Text { .. } => "text",
+            SystemMessage::UserAdded { .. } => "user_added",
+            SystemMessage::UserRemove { .. } => "user_remove",
+            SystemMessage::UserJoined { .. } => "user_joined",
+            SystemMessage::UserLeft { .. } => "user_left",
+            SystemMessage::UserKicked { .. } => "user_kicked",
+            SystemMessage::UserBanned { .. } => "user_banned",
+            SystemMessage::ChannelRenamed { .. } => "channel_renamed",
+            SystemMessage::ChannelDescriptionChanged { .. } => "channel_description_changed",
+            SystemMessage::ChannelIconChanged { .. } => "channel_icon_changed",
+            SystemMessage::ChannelOwnershipChanged { .. } => "channel_ownership_changed",
+            SystemMessage::MessagePinned { .. } => "message_pinned",
+            SystemMessage::MessageUnpinned { .. } => "message_unpinned",
+        }
+    }
+}
+
 impl From<SystemMessage> for String {
     fn from(s: SystemMessage) -> String {
         match s {
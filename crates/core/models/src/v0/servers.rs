@@ -78,6 +78,12 @@ auto_derived_partial!(
             serde(skip_serializing_if = "crate::if_false", default)
         )]
         pub discoverable: bool,
+        /// Whether staff should be notified when someone joins
+        #[cfg_attr(
+            feature = "serde",
+            serde(skip_serializing_if = "crate::if_false", default)
+        )]
+        pub notify_member_joins: bool,
     },
     "PartialServer"
 );
@@ -254,6 +260,8 @@ auto_derived!(
         ///
         /// Must be enabled in order to show up on [Revolt Discover](https://rvlt.gg).
         pub analytics: Option<bool>,
+        /// Whether staff should be notified when someone joins
+        pub notify_member_joins: Option<bool>,
 
         /// Fields to remove from server object
         #[cfg_attr(feature = "serde", serde(default))]
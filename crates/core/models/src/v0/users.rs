@@ -1,3 +1,4 @@
+use iso8601_timestamp::Timestamp;
 use once_cell::sync::Lazy;
 use regex::Regex;
 
@@ -272,6 +273,18 @@ auto_derived!(
         pub owner_id: String,
     }
 
+    /// Notification snooze options
+    pub struct DataSnoozeNotifications {
+        /// Time at which notifications should resume
+        pub until: Timestamp,
+    }
+
+    /// Push notification delivery options
+    pub struct DataNotificationDelivery {
+        /// Whether only the most recently active session should receive push notifications
+        pub primary_session_only: bool,
+    }
+
     /// User lookup information
     pub struct DataSendFriendRequest {
         /// Username and discriminator combo separated by #
@@ -93,6 +93,22 @@ pub async fn is_online(user_id: &str) -> bool {
     }
 }
 
+/// Make sure a user with presence sessions is listed as online, returns whether they are online
+///
+/// Repairs the online set if it has gone stale, without creating any sessions.
+pub async fn refresh_online(user_id: &str) -> bool {
+    if let Ok(mut conn) = get_connection().await {
+        if __get_set_size(&mut conn, user_id).await == 0 {
+            return false;
+        }
+
+        __add_to_set_string(&mut conn, ONLINE_SET, user_id).await;
+        true
+    } else {
+        false
+    }
+}
+
 /// Check whether a set of users is online, returns a set of the online user IDs
 #[cfg(feature = "redis-is-patched")]
 pub async fn filter_online(user_ids: &'_ [String]) -> HashSet<String> {
@@ -192,8 +208,35 @@ pub async fn clear_region(region_id: Option<&str>) {
 
 #[cfg(test)]
 mod tests {
-    use crate::{clear_region, create_session, delete_session, filter_online, is_online};
+    use crate::{
+        clear_region, create_session, delete_session, filter_online, is_online, refresh_online,
+        ONLINE_SET,
+    };
     use rand::Rng;
+    use redis_kiss::{get_connection, AsyncCommands};
+
+    #[async_std::test]
+    async fn refresh_restores_users_with_sessions() {
+        revolt_config::config().await;
+
+        let user_id = rand::thread_rng().gen::<u32>().to_string();
+        let offline_id = rand::thread_rng().gen::<u32>().to_string();
+
+        // Session which went missing from the online set
+        let mut conn = get_connection().await.expect("Redis connection");
+        let _: () = conn.sadd(&user_id, 2_u32).await.unwrap();
+
+        assert!(refresh_online(&user_id).await);
+        assert!(!refresh_online(&offline_id).await);
+
+        let user_ids = filter_online(&[user_id.to_string(), offline_id.to_string()]).await;
+
+        let _: () = conn.srem(ONLINE_SET, &user_id).await.unwrap();
+        let _: () = conn.del(&user_id).await.unwrap();
+
+        assert_eq!(user_ids.len(), 1);
+        assert!(user_ids.contains(&user_id));
+    }
 
     #[async_std::test]
     async fn it_works() {
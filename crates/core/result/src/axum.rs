@@ -28,6 +28,8 @@ impl IntoResponse for Error {
             ErrorType::CannotJoinCall => StatusCode::BAD_REQUEST,
             ErrorType::TooManyAttachments { .. } => StatusCode::BAD_REQUEST,
             ErrorType::TooManyReplies { .. } => StatusCode::BAD_REQUEST,
+            ErrorType::TooManyOpenChannels { .. } => StatusCode::BAD_REQUEST,
+            ErrorType::ActivityNotSupported => StatusCode::BAD_REQUEST,
             ErrorType::EmptyMessage => StatusCode::UNPROCESSABLE_ENTITY,
             ErrorType::PayloadTooLarge => StatusCode::UNPROCESSABLE_ENTITY,
             ErrorType::CannotRemoveYourself => StatusCode::BAD_REQUEST,
@@ -91,6 +91,10 @@ pub enum ErrorType {
     TooManyChannels {
         max: usize,
     },
+    TooManyOpenChannels {
+        max: usize,
+    },
+    ActivityNotSupported,
     EmptyMessage,
     PayloadTooLarge,
     CannotRemoveYourself,
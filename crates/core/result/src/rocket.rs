@@ -34,6 +34,8 @@ impl<'r> Responder<'r, 'static> for Error {
             ErrorType::CannotJoinCall => Status::BadRequest,
             ErrorType::TooManyAttachments { .. } => Status::BadRequest,
             ErrorType::TooManyReplies { .. } => Status::BadRequest,
+            ErrorType::TooManyOpenChannels { .. } => Status::BadRequest,
+            ErrorType::ActivityNotSupported => Status::BadRequest,
             ErrorType::EmptyMessage => Status::UnprocessableEntity,
             ErrorType::PayloadTooLarge => Status::UnprocessableEntity,
             ErrorType::CannotRemoveYourself => Status::BadRequest,
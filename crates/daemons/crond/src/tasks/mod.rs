@@ -1,3 +1,4 @@
 pub mod file_deletion;
 pub mod prune_dangling_files;
 pub mod prune_members;
+pub mod prune_push_subscriptions;
@@ -0,0 +1,32 @@
+use std::time::Duration;
+
+use log::{info, warn};
+use revolt_config::config;
+use revolt_database::{
+    iso8601_timestamp::{Duration as TimestampDuration, Timestamp},
+    Database,
+};
+use revolt_result::Result;
+use tokio::time::sleep;
+
+pub async fn task(db: Database) -> Result<()> {
+    loop {
+        let config = config().await;
+        let now = Timestamp::now_utc();
+        let stale_before = match config.pushd.stale_subscription_days {
+            0 => None,
+            days => now.checked_sub(TimestampDuration::days(days as i64)),
+        };
+
+        match db.prune_push_subscriptions(now, stale_before).await {
+            Ok(0) => {}
+            Ok(pruned) => info!("Pruned {pruned} expired push subscriptions"),
+            Err(s) => {
+                revolt_config::capture_error(&s);
+                warn!("Failed to prune push subscriptions: {:?}", &s);
+            }
+        }
+
+        sleep(Duration::from_secs(60 * 60)).await;
+    }
+}
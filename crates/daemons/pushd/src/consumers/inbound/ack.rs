@@ -51,33 +51,20 @@ impl AckConsumer {
             channel: None,
         }
     }
-}
-
-#[allow(unused_variables)]
-#[async_trait]
-impl AsyncConsumer for AckConsumer {
-    /// This consumer processes all acks the platform receives, and sends relevant badge updates to apple platforms.
-    async fn consume(
-        &mut self,
-        channel: &Channel,
-        deliver: Deliver,
-        basic_properties: BasicProperties,
-        content: Vec<u8>,
-    ) {
-        let content = String::from_utf8(content).unwrap();
-        let payload: AckPayload = serde_json::from_str(content.as_str()).unwrap();
 
+    /// Send the user's current mention count to their apple sessions as a badge update
+    async fn update_badges(&mut self, user_id: &str) {
         // Step 1: fetch unreads and don't continue if there's no unreads
         #[allow(clippy::disallowed_methods)]
-        let unreads = self.db.fetch_unread_mentions(&payload.user_id).await;
+        let unreads = self.db.fetch_unread_mentions(user_id).await;
 
-        debug!("Processing unreads for {:}", &payload.user_id);
+        debug!("Processing unreads for {:}", user_id);
 
         if let Ok(u) = &unreads {
             if u.is_empty() {
                 debug!(
                     "Discarding unread task (no mentions found) for {:}",
-                    &payload.user_id
+                    user_id
                 );
                 return;
             }
@@ -85,7 +72,7 @@ impl AsyncConsumer for AckConsumer {
             return;
         }
 
-        if let Ok(sessions) = self.authifier_db.find_sessions(&payload.user_id).await {
+        if let Ok(sessions) = self.authifier_db.find_sessions(user_id).await {
             let config = revolt_config::config().await;
             // Step 2: find any apple sessions, since we don't need to calculate this for anything else.
             // If there's no apple sessions, we can return early
@@ -103,7 +90,7 @@ impl AsyncConsumer for AckConsumer {
             if apple_sessions.is_empty() {
                 debug!(
                     "Discarding unread task (no apn sessions found) for {:}",
-                    &payload.user_id
+                    user_id
                 );
                 return;
             }
@@ -116,13 +103,13 @@ impl AsyncConsumer for AckConsumer {
 
             // Step 4: loop through each apple session and send the badge update
             for session in apple_sessions {
-                let service_payload = PayloadToService {
-                    notification: PayloadKind::BadgeUpdate(mention_count),
-                    user_id: payload.user_id.clone(),
-                    session_id: session.id.clone(),
-                    token: session.subscription.as_ref().unwrap().auth.clone(),
-                    extras: Default::default(),
-                };
+                let service_payload = PayloadToService::new(
+                    PayloadKind::BadgeUpdate(mention_count),
+                    NotificationClass::Normal,
+                    user_id.to_string(),
+                    session.id.clone(),
+                    session.subscription.as_ref().unwrap().auth.clone(),
+                );
                 let raw_service_payload = serde_json::to_string(&service_payload);
 
                 if let Ok(p) = raw_service_payload {
@@ -146,3 +133,32 @@ impl AsyncConsumer for AckConsumer {
         }
     }
 }
+
+#[allow(unused_variables)]
+#[async_trait]
+impl AsyncConsumer for AckConsumer {
+    /// This consumer processes all acks the platform receives, and sends relevant badge updates to apple platforms.
+    async fn consume(
+        &mut self,
+        channel: &Channel,
+        deliver: Deliver,
+        basic_properties: BasicProperties,
+        content: Vec<u8>,
+    ) {
+        let content = String::from_utf8(content).unwrap();
+        let event: AckQueueEvent = serde_json::from_str(content.as_str()).unwrap();
+
+        // Badges only depend on the user, so update each user once per batch
+        let mut user_ids: Vec<String> = event
+            .into_acks()
+            .into_iter()
+            .map(|ack| ack.user_id)
+            .collect();
+        user_ids.sort();
+        user_ids.dedup();
+
+        for user_id in user_ids {
+            self.update_badges(&user_id).await;
+        }
+    }
+}
@@ -1,5 +1,3 @@
-use std::collections::HashMap;
-
 use crate::consumers::inbound::internal::*;
 use amqprs::{
     channel::{BasicPublishArguments, Channel},
@@ -72,13 +70,13 @@ impl FRAcceptedConsumer {
             let config = revolt_config::config().await;
             for session in sessions {
                 if let Some(sub) = session.subscription {
-                    let mut sendable = PayloadToService {
-                        notification: PayloadKind::FRAccepted(payload.clone()),
-                        token: sub.auth,
-                        user_id: session.user_id,
-                        session_id: session.id,
-                        extras: HashMap::new(),
-                    };
+                    let mut sendable = PayloadToService::new(
+                        PayloadKind::FRAccepted(payload.clone()),
+                        NotificationClass::Normal,
+                        session.user_id,
+                        session.id,
+                        sub.auth,
+                    );
 
                     let args: BasicPublishArguments;
 
@@ -1,5 +1,3 @@
-use std::collections::HashMap;
-
 use crate::consumers::inbound::internal::*;
 use amqprs::{
     channel::{BasicPublishArguments, Channel},
@@ -64,21 +62,30 @@ impl FRReceivedConsumer {
         content: Vec<u8>,
     ) -> Result<()> {
         let content = String::from_utf8(content)?;
-        let payload: FRReceivedPayload = serde_json::from_str(content.as_str())?;
+        let event: FRReceivedEvent = serde_json::from_str(content.as_str())?;
 
         debug!("Received FR received event");
 
+        for payload in event.into_requests() {
+            self.notify(payload).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Send a single friend request notification to all of the recipient's sessions
+    async fn notify(&mut self, payload: FRReceivedPayload) -> Result<()> {
         if let Ok(sessions) = self.authifier_db.find_sessions(&payload.user).await {
             let config = revolt_config::config().await;
             for session in sessions {
                 if let Some(sub) = session.subscription {
-                    let mut sendable = PayloadToService {
-                        notification: PayloadKind::FRReceived(payload.clone()),
-                        token: sub.auth,
-                        user_id: session.user_id,
-                        session_id: session.id,
-                        extras: HashMap::new(),
-                    };
+                    let mut sendable = PayloadToService::new(
+                        PayloadKind::FRReceived(payload.clone()),
+                        NotificationClass::Normal,
+                        session.user_id,
+                        session.id,
+                        sub.auth,
+                    );
 
                     let args: BasicPublishArguments;
 
@@ -1,5 +1,3 @@
-use std::collections::HashMap;
-
 use crate::consumers::inbound::internal::*;
 use amqprs::{
     channel::{BasicPublishArguments, Channel},
@@ -10,10 +8,9 @@ use amqprs::{
 use anyhow::Result;
 use async_trait::async_trait;
 use log::debug;
-use revolt_database::{events::rabbit::*, Database};
+use revolt_database::{events::rabbit::*, Database, User};
 
 pub struct GenericConsumer {
-    #[allow(dead_code)]
     db: Database,
     authifier_db: authifier::Database,
     conn: Option<Connection>,
@@ -64,27 +61,44 @@ impl GenericConsumer {
         content: Vec<u8>,
     ) -> Result<()> {
         let content = String::from_utf8(content)?;
-        let payload: MessageSentPayload = serde_json::from_str(content.as_str())?;
+        let event: GenericEvent = serde_json::from_str(content.as_str())?;
 
-        debug!("Received message event on origin");
+        debug!("Received generic event");
 
-        if let Ok(sessions) = self
-            .authifier_db
-            .find_sessions_with_subscription(&payload.users)
-            .await
-        {
-            let config = revolt_config::config().await;
+        for payload in event.into_payloads() {
+            self.notify(payload).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Send a single generic notification to all of the recipient's sessions
+    async fn notify(&mut self, mut payload: GenericPayload) -> Result<()> {
+        let config = revolt_config::config().await;
+
+        // Resolve the icon from a user's avatar if requested
+        if payload.icon.is_none() {
+            if let Some(avatar_user) = payload.avatar_user.take() {
+                payload.icon = Some(match self.db.fetch_user(&avatar_user).await {
+                    Ok(User {
+                        avatar: Some(avatar),
+                        ..
+                    }) => format!("{}/avatars/{}", config.hosts.autumn, avatar.id),
+                    _ => format!("{}/users/{}/default_avatar", config.hosts.api, avatar_user),
+                });
+            }
+        }
+
+        if let Ok(sessions) = self.authifier_db.find_sessions(&payload.user.id).await {
             for session in sessions {
                 if let Some(sub) = session.subscription {
-                    let mut sendable = PayloadToService {
-                        notification: PayloadKind::MessageNotification(
-                            payload.notification.clone(),
-                        ),
-                        token: sub.auth,
-                        user_id: session.user_id,
-                        session_id: session.id,
-                        extras: HashMap::new(),
-                    };
+                    let mut sendable = PayloadToService::new(
+                        PayloadKind::Generic(payload.clone()),
+                        NotificationClass::Normal,
+                        session.user_id,
+                        session.id,
+                        sub.auth,
+                    );
 
                     let args: BasicPublishArguments;
 
@@ -1,7 +1,7 @@
 use amqprs::{
     channel::{BasicPublishArguments, Channel},
     connection::{Connection, OpenConnectionArguments},
-    BasicProperties,
+    BasicProperties, FieldName, FieldValue,
 };
 use log::{debug, warn};
 
@@ -51,3 +51,12 @@ pub(crate) async fn publish_message<T: Channeled>(
         warn!("Failed to unwrap channel (including attempt to make a channel)!")
     }
 }
+
+/// Trace id the publisher attached to a payload, if any
+pub(crate) fn trace_id(properties: &BasicProperties) -> Option<String> {
+    let key: FieldName = "x-trace-id".try_into().ok()?;
+    match properties.headers()?.get(&key)? {
+        FieldValue::S(value) => Some(String::from(value.clone())),
+        _ => None,
+    }
+}
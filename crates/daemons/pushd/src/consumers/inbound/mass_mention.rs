@@ -1,7 +1,4 @@
-use std::{
-    collections::{HashMap, HashSet},
-    hash::RandomState,
-};
+use std::{collections::HashSet, hash::RandomState};
 
 use crate::consumers::inbound::internal::*;
 use amqprs::{
@@ -75,13 +72,13 @@ impl MassMessageConsumer {
             let config = revolt_config::config().await;
             for session in sessions {
                 if let Some(sub) = session.subscription {
-                    let mut sendable = PayloadToService {
-                        notification: PayloadKind::MessageNotification(push.clone()),
-                        token: sub.auth,
-                        user_id: session.user_id,
-                        session_id: session.id,
-                        extras: HashMap::new(),
-                    };
+                    let mut sendable = PayloadToService::new(
+                        PayloadKind::MessageNotification(push.clone()),
+                        NotificationClass::Mention,
+                        session.user_id,
+                        session.id,
+                        sub.auth,
+                    );
 
                     let args: BasicPublishArguments;
 
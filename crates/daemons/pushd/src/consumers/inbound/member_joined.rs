@@ -0,0 +1,169 @@
+use crate::consumers::inbound::internal::*;
+use amqprs::{
+    channel::{BasicPublishArguments, Channel},
+    connection::Connection,
+    consumer::AsyncConsumer,
+    BasicProperties, Deliver,
+};
+use anyhow::Result;
+use async_trait::async_trait;
+use log::debug;
+use revolt_database::{events::rabbit::*, Database};
+
+pub struct MemberJoinedConsumer {
+    #[allow(dead_code)]
+    db: Database,
+    authifier_db: authifier::Database,
+    conn: Option<Connection>,
+    channel: Option<Channel>,
+}
+
+impl Channeled for MemberJoinedConsumer {
+    fn get_connection(&self) -> Option<&Connection> {
+        if self.conn.is_none() {
+            None
+        } else {
+            Some(self.conn.as_ref().unwrap())
+        }
+    }
+
+    fn get_channel(&self) -> Option<&Channel> {
+        if self.channel.is_none() {
+            None
+        } else {
+            Some(self.channel.as_ref().unwrap())
+        }
+    }
+
+    fn set_connection(&mut self, conn: Connection) {
+        self.conn = Some(conn);
+    }
+
+    fn set_channel(&mut self, channel: Channel) {
+        self.channel = Some(channel)
+    }
+}
+
+impl MemberJoinedConsumer {
+    pub fn new(db: Database, authifier_db: authifier::Database) -> MemberJoinedConsumer {
+        MemberJoinedConsumer {
+            db,
+            authifier_db,
+            conn: None,
+            channel: None,
+        }
+    }
+
+    async fn consume_event(
+        &mut self,
+        _channel: &Channel,
+        _deliver: Deliver,
+        _basic_properties: BasicProperties,
+        content: Vec<u8>,
+    ) -> Result<()> {
+        let content = String::from_utf8(content)?;
+        let payload: MemberJoinedPayload = serde_json::from_str(content.as_str())?;
+
+        debug!("Received member joined event on origin");
+
+        let config = revolt_config::config().await;
+        let name = payload.member.display_name.clone().unwrap_or_else(|| {
+            format!(
+                "{}#{}",
+                payload.member.username, payload.member.discriminator
+            )
+        });
+
+        let icon = match &payload.member.avatar {
+            Some(avatar) => format!("{}/avatars/{}", config.hosts.autumn, avatar.id),
+            None => format!(
+                "{}/users/{}/default_avatar",
+                config.hosts.api, payload.member.id
+            ),
+        };
+
+        let notification = GenericPayload {
+            title: payload.server_name.clone(),
+            body: format!("{name} joined the server"),
+            icon: Some(icon),
+            avatar_user: None,
+            user: payload.member.clone(),
+        };
+
+        if let Ok(sessions) = self
+            .authifier_db
+            .find_sessions_with_subscription(&payload.users)
+            .await
+        {
+            for session in sessions {
+                if let Some(sub) = session.subscription {
+                    let mut sendable = PayloadToService::new(
+                        PayloadKind::Generic(notification.clone()),
+                        NotificationClass::Normal,
+                        session.user_id,
+                        session.id,
+                        sub.auth,
+                    );
+
+                    sendable
+                        .extras
+                        .insert("server".to_string(), payload.server_id.clone());
+
+                    let args: BasicPublishArguments;
+
+                    if sub.endpoint == "apn" {
+                        args = BasicPublishArguments::new(
+                            config.pushd.exchange.as_str(),
+                            config.pushd.apn.queue.as_str(),
+                        )
+                        .finish();
+                    } else if sub.endpoint == "fcm" {
+                        args = BasicPublishArguments::new(
+                            config.pushd.exchange.as_str(),
+                            config.pushd.fcm.queue.as_str(),
+                        )
+                        .finish();
+                    } else {
+                        // web push (vapid)
+                        args = BasicPublishArguments::new(
+                            config.pushd.exchange.as_str(),
+                            config.pushd.vapid.queue.as_str(),
+                        )
+                        .finish();
+                        sendable.extras.insert("p265dh".to_string(), sub.p256dh);
+                        sendable
+                            .extras
+                            .insert("endpoint".to_string(), sub.endpoint.clone());
+                    }
+
+                    let payload = serde_json::to_string(&sendable)?;
+
+                    publish_message(self, payload.into(), args).await;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[allow(unused_variables)]
+#[async_trait]
+impl AsyncConsumer for MemberJoinedConsumer {
+    /// This consumer handles delegating messages into their respective platform queues.
+    async fn consume(
+        &mut self,
+        channel: &Channel,
+        deliver: Deliver,
+        basic_properties: BasicProperties,
+        content: Vec<u8>,
+    ) {
+        if let Err(err) = self
+            .consume_event(channel, deliver, basic_properties, content)
+            .await
+        {
+            revolt_config::capture_anyhow(&err);
+            eprintln!("Failed to process member joined event: {err:?}");
+        }
+    }
+}
@@ -1,5 +1,3 @@
-use std::collections::HashMap;
-
 use crate::consumers::inbound::internal::*;
 use amqprs::{
     channel::{BasicPublishArguments, Channel},
@@ -60,13 +58,16 @@ impl MessageConsumer {
         &mut self,
         _channel: &Channel,
         _deliver: Deliver,
-        _basic_properties: BasicProperties,
+        basic_properties: BasicProperties,
         content: Vec<u8>,
     ) -> Result<()> {
         let content = String::from_utf8(content)?;
         let payload: MessageSentPayload = serde_json::from_str(content.as_str())?;
 
-        debug!("Received message event on origin");
+        debug!(
+            "Received message event on origin (trace {})",
+            trace_id(&basic_properties).as_deref().unwrap_or("none")
+        );
 
         if let Ok(sessions) = self
             .authifier_db
@@ -75,16 +76,25 @@ impl MessageConsumer {
         {
             let config = revolt_config::config().await;
             for session in sessions {
+                if !payload.should_notify_session(&session.user_id, &session.id) {
+                    continue;
+                }
+
                 if let Some(sub) = session.subscription {
-                    let mut sendable = PayloadToService {
-                        notification: PayloadKind::MessageNotification(
-                            payload.notification.clone(),
+                    let class = payload.class_for_user(&session.user_id);
+                    let mut sendable = PayloadToService::new(
+                        PayloadKind::MessageNotification(
+                            payload.notification_for_session(&session.id),
                         ),
-                        token: sub.auth,
-                        user_id: session.user_id,
-                        session_id: session.id,
-                        extras: HashMap::new(),
-                    };
+                        class,
+                        session.user_id,
+                        session.id,
+                        sub.auth,
+                    );
+
+                    sendable
+                        .extras
+                        .insert("class".to_string(), class.as_str().to_string());
 
                     let args: BasicPublishArguments;
 
@@ -4,4 +4,5 @@ pub mod fr_received;
 pub mod generic;
 mod internal;
 pub mod mass_mention;
+pub mod member_joined;
 pub mod message;
@@ -14,10 +14,12 @@ use revolt_a2::{
     },
     Client, ClientConfig, Endpoint, Error, ErrorBody, ErrorReason, Priority, PushType, Response,
 };
-use revolt_database::{events::rabbit::*, Database};
-use revolt_models::v0::{Channel, Message, PushNotification};
+use revolt_database::{events::rabbit::*, format, Database};
+use revolt_models::v0::Message;
 use serde::Serialize;
 
+use super::record_receipt;
+
 // region: payload
 
 #[derive(Serialize, Debug)]
@@ -54,23 +56,6 @@ pub struct ApnsOutboundConsumer {
 }
 
 impl ApnsOutboundConsumer {
-    fn format_title(&self, notification: &PushNotification) -> String {
-        // ideally this changes depending on context
-        // in a server, it would look like "Sendername, #channelname in servername"
-        // in a group, it would look like "Sendername in groupname"
-        // in a dm it should just be "Sendername".
-        // not sure how feasible all those are given the PushNotification object as it currently stands.
-
-        match &notification.channel {
-            Channel::DirectMessage { .. } => notification.author.clone(),
-            Channel::Group { name, .. } => format!("{}, #{}", notification.author, name),
-            Channel::TextChannel { name, .. } | Channel::VoiceChannel { name, .. } => {
-                format!("{} in #{}", notification.author, name)
-            }
-            _ => "Unknown".to_string(),
-        }
-    }
-
     async fn get_badge_count(&self, user: &str) -> Option<u32> {
         if let Ok(unreads) = self.db.fetch_unread_mentions(user).await {
             let mut mention_count = 0;
@@ -131,6 +116,7 @@ impl ApnsOutboundConsumer {
     ) -> Result<()> {
         let content = String::from_utf8(content)?;
         let payload: PayloadToService = serde_json::from_str(content.as_str())?;
+        let receipt = DeliveryReceipt::for_payload(&payload);
 
         let payload_options = NotificationOptions {
             apns_id: None,
@@ -174,7 +160,7 @@ impl ApnsOutboundConsumer {
                         sound: Some(APSSound::Sound("default")),
                         thread_id: None,
                         content_available: None,
-                        category: None,
+                        category: Some(payload.category.as_str()),
                         mutable_content: Some(1),
                         url_args: None,
                     },
@@ -220,7 +206,7 @@ impl ApnsOutboundConsumer {
                         sound: Some(APSSound::Sound("default")),
                         thread_id: None,
                         content_available: None,
-                        category: None,
+                        category: Some(payload.category.as_str()),
                         mutable_content: Some(1),
                         url_args: None,
                     },
@@ -253,7 +239,7 @@ impl ApnsOutboundConsumer {
                         sound: Some(APSSound::Sound("default")),
                         thread_id: None,
                         content_available: None,
-                        category: None,
+                        category: Some(payload.category.as_str()),
                         mutable_content: Some(1),
                         url_args: None,
                     },
@@ -270,7 +256,7 @@ impl ApnsOutboundConsumer {
             }
 
             PayloadKind::MessageNotification(alert) => {
-                let title = self.format_title(&alert);
+                let title = format::notification_title(&alert);
                 let apn_payload = MessagePayload {
                     aps: APS {
                         alert: Some(APSAlert::Default(DefaultAlert {
@@ -288,7 +274,7 @@ impl ApnsOutboundConsumer {
                         sound: Some(APSSound::Sound("default")),
                         thread_id: Some(alert.channel.id()),
                         content_available: None,
-                        category: None,
+                        category: Some(payload.category.as_str()),
                         mutable_content: Some(1),
                         url_args: None,
                     },
@@ -323,6 +309,10 @@ impl ApnsOutboundConsumer {
             }
         }
 
+        if resp.is_ok() {
+            record_receipt(receipt).await;
+        }
+
         if let Err(err) = resp {
             match err {
                 Error::ResponseError(Response {
@@ -13,6 +13,8 @@ use fcm_v1::{
 use revolt_database::{events::rabbit::*, Database};
 use serde_json::Value;
 
+use super::record_receipt;
+
 pub struct FcmOutboundConsumer {
     db: Database,
     client: Client,
@@ -55,6 +57,8 @@ impl FcmOutboundConsumer {
     ) -> Result<()> {
         let content = String::from_utf8(content)?;
         let payload: PayloadToService = serde_json::from_str(content.as_str())?;
+        let receipt = DeliveryReceipt::for_payload(&payload);
+        let category = Value::String(payload.category.as_str().to_string());
 
         #[allow(clippy::needless_late_init)]
         let resp: Result<Message, FcmError>;
@@ -78,6 +82,7 @@ impl FcmOutboundConsumer {
                 );
                 data.insert("id".to_string(), Value::String(alert.from_user.id));
                 data.insert("username".to_string(), Value::String(name));
+                data.insert("category".to_string(), category);
 
                 let msg = Message {
                     token: Some(payload.token),
@@ -106,6 +111,7 @@ impl FcmOutboundConsumer {
                 );
                 data.insert("id".to_string(), Value::String(alert.accepted_user.id));
                 data.insert("username".to_string(), Value::String(name));
+                data.insert("category".to_string(), category);
 
                 let msg = Message {
                     token: Some(payload.token),
@@ -116,8 +122,12 @@ impl FcmOutboundConsumer {
                 resp = self.client.send(&msg).await;
             }
             PayloadKind::Generic(alert) => {
+                let mut data: HashMap<String, Value> = HashMap::new();
+                data.insert("category".to_string(), category);
+
                 let msg = Message {
                     token: Some(payload.token),
+                    data: Some(data),
                     notification: Some(Notification {
                         title: Some(alert.title),
                         body: Some(alert.body),
@@ -135,6 +145,7 @@ impl FcmOutboundConsumer {
                     "payload".to_string(),
                     Value::String(serde_json::to_string(&alert).unwrap()),
                 );
+                data.insert("category".to_string(), category);
 
                 let msg = Message {
                     token: Some(payload.token),
@@ -155,6 +166,10 @@ impl FcmOutboundConsumer {
             }
         }
 
+        if resp.is_ok() {
+            record_receipt(receipt).await;
+        }
+
         if let Err(err) = resp {
             match err {
                 FcmError::Auth => {
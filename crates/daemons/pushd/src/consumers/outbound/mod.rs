@@ -1,3 +1,14 @@
+use revolt_database::{events::rabbit::DeliveryReceipt, util::delivery::record_delivery};
+
 pub mod apn;
 pub mod fcm;
 pub mod vapid;
+
+/// Record that a notification was handed to a push service, if it was for a message
+pub async fn record_receipt(receipt: Option<DeliveryReceipt>) {
+    if let Some(receipt) = receipt {
+        if let Err(err) = record_delivery(&receipt).await {
+            revolt_config::capture_error(&err);
+        }
+    }
+}
@@ -14,6 +14,8 @@ use web_push::{
     WebPushClient, WebPushError, WebPushMessageBuilder,
 };
 
+use super::record_receipt;
+
 pub struct VapidOutboundConsumer {
     db: Database,
     client: IsahcWebPushClient,
@@ -48,6 +50,7 @@ impl VapidOutboundConsumer {
     ) -> Result<()> {
         let content = String::from_utf8(content)?;
         let payload: PayloadToService = serde_json::from_str(content.as_str())?;
+        let receipt = DeliveryReceipt::for_payload(&payload);
 
         let subscription = SubscriptionInfo {
             endpoint: payload
@@ -122,12 +125,14 @@ impl VapidOutboundConsumer {
 
                     match builder.build() {
                         Ok(msg) => {
-                            if let Err(err) = self.client.send(msg).await {
-                                if err == WebPushError::Unauthorized {
+                            match self.client.send(msg).await {
+                                Ok(_) => record_receipt(receipt).await,
+                                Err(WebPushError::Unauthorized) => {
                                     self.db
                                         .remove_push_subscription_by_session_id(&payload.session_id)
                                         .await?;
                                 }
+                                Err(_) => {}
                             }
 
                             Ok(())
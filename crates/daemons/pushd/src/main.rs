@@ -17,7 +17,8 @@ mod consumers;
 use consumers::{
     inbound::{
         ack::AckConsumer, fr_accepted::FRAcceptedConsumer, fr_received::FRReceivedConsumer,
-        generic::GenericConsumer, mass_mention::MassMessageConsumer, message::MessageConsumer,
+        generic::GenericConsumer, mass_mention::MassMessageConsumer,
+        member_joined::MemberJoinedConsumer, message::MessageConsumer,
     },
     outbound::{apn::ApnsOutboundConsumer, fcm::FcmOutboundConsumer, vapid::VapidOutboundConsumer},
 };
@@ -78,6 +79,30 @@ async fn main() {
         .await,
     );
 
+    // inbound: pinned messages, shaped like regular message notifications
+    connections.push(
+        make_queue_and_consume(
+            &config,
+            &config.pushd.pinned_queue,
+            config.pushd.get_pinned_routing_key().as_str(),
+            None,
+            MessageConsumer::new(db.clone(), authifier.clone()),
+        )
+        .await,
+    );
+
+    // inbound: new server members, for staff of servers that opted in
+    connections.push(
+        make_queue_and_consume(
+            &config,
+            &config.pushd.member_joined_queue,
+            config.pushd.get_member_joined_routing_key().as_str(),
+            None,
+            MemberJoinedConsumer::new(db.clone(), authifier.clone()),
+        )
+        .await,
+    );
+
     // inbound: FR received
     connections.push(
         make_queue_and_consume(
@@ -10,18 +10,14 @@ pub mod util;
 
 use revolt_config::config;
 use revolt_database::events::client::EventV1;
-use revolt_database::AMQP;
+use revolt_database::{Database, AMQP};
 use revolt_ratelimits::rocket as ratelimiter;
-use rocket::{Build, Rocket};
+use rocket::{fairing::AdHoc, Build, Rocket};
 use rocket_cors::{AllowedOrigins, CorsOptions};
 use rocket_prometheus::PrometheusMetrics;
 use std::net::Ipv4Addr;
 use std::str::FromStr;
 
-use amqprs::{
-    channel::ExchangeDeclareArguments,
-    connection::{Connection, OpenConnectionArguments},
-};
 use async_std::channel::unbounded;
 use authifier::AuthifierEvent;
 use rocket::data::ToByteUnit;
@@ -90,31 +86,8 @@ pub async fn web() -> Rocket<Build> {
     )
     .into();
 
-    // Configure Rabbit
-    let connection = Connection::open(&OpenConnectionArguments::new(
-        &config.rabbit.host,
-        config.rabbit.port,
-        &config.rabbit.username,
-        &config.rabbit.password,
-    ))
-    .await
-    .expect("Failed to connect to RabbitMQ");
-
-    let channel = connection
-        .open_channel(None)
-        .await
-        .expect("Failed to open RabbitMQ channel");
-
-    channel
-        .exchange_declare(
-            ExchangeDeclareArguments::new(&config.pushd.exchange, "direct")
-                .durable(true)
-                .finish(),
-        )
-        .await
-        .expect("Failed to declare exchange");
-
-    let amqp = AMQP::new(connection, channel);
+    // Configure notifications
+    let amqp = AMQP::connect(&config).await;
 
     // Launch background task workers
     revolt_database::tasks::start_workers(db.clone(), amqp.clone());
@@ -122,6 +95,8 @@ pub async fn web() -> Rocket<Build> {
     // Configure Rocket
     let rocket = rocket::build();
     let prometheus = PrometheusMetrics::new();
+    revolt_database::amqp::metrics::register(prometheus.registry())
+        .expect("Failed to register metrics");
 
     // Ratelimits
     let ratelimits = ratelimiter::RatelimitStorage::new(util::ratelimits::DeltaRatelimits);
@@ -140,6 +115,14 @@ pub async fn web() -> Rocket<Build> {
         .manage(ratelimits)
         .attach(ratelimiter::RatelimitFairing)
         .attach(cors)
+        .attach(AdHoc::on_shutdown("Flush notifications", |rocket| {
+            Box::pin(async move {
+                if let (Some(amqp), Some(db)) = (rocket.state::<AMQP>(), rocket.state::<Database>())
+                {
+                    amqp.shutdown(db).await;
+                }
+            })
+        }))
         .configure(rocket::Config {
             limits: rocket::data::Limits::default().limit("string", 5.megabytes()),
             address: Ipv4Addr::new(0, 0, 0, 0).into(),
@@ -0,0 +1,33 @@
+use revolt_database::{util::activity::fetch_last_channel, User};
+use revolt_result::{create_error, Result};
+use revolt_rocket_okapi::revolt_okapi::schemars::JsonSchema;
+use rocket::serde::json::Json;
+use serde::Serialize;
+
+/// Last channel opened by the user
+#[derive(Serialize, JsonSchema)]
+pub struct LastChannelResponse {
+    /// Id of the channel
+    pub channel: String,
+    /// When the channel was opened, as unix time in seconds
+    pub opened_at: u64,
+}
+
+/// # Fetch Last Channel
+///
+/// Fetch the channel you most recently opened in any session, such as to resume reading.
+///
+/// Channels opened more than 30 days ago are forgotten.
+#[openapi(tag = "Channel Information")]
+#[get("/last")]
+pub async fn last(user: User) -> Result<Json<LastChannelResponse>> {
+    if user.bot.is_some() {
+        return Err(create_error!(IsBot));
+    }
+
+    let (channel, opened_at) = fetch_last_channel(&user.id)
+        .await?
+        .ok_or_else(|| create_error!(NotFound))?;
+
+    Ok(Json(LastChannelResponse { channel, opened_at }))
+}
@@ -0,0 +1,32 @@
+use revolt_database::{util::activity::fetch_open_channels, User};
+use revolt_result::{create_error, Result};
+use rocket::serde::json::Json;
+
+/// Maximum number of channels which may be queried at once
+const MAX_CHANNELS: usize = 200;
+
+/// # Query Channel Activity
+///
+/// Find which of the given channels you currently have open in any session.
+#[openapi(tag = "Channel Information")]
+#[post("/query", data = "<data>")]
+pub async fn query(user: User, data: Json<Vec<String>>) -> Result<Json<Vec<String>>> {
+    if user.bot.is_some() {
+        return Err(create_error!(IsBot));
+    }
+
+    let channel_ids = data.into_inner();
+    if channel_ids.len() > MAX_CHANNELS {
+        return Err(create_error!(FailedValidation {
+            error: format!("at most {MAX_CHANNELS} channels may be queried")
+        }));
+    }
+
+    let open = fetch_open_channels(&user.id).await?;
+    Ok(Json(
+        channel_ids
+            .into_iter()
+            .filter(|id| open.contains(id))
+            .collect(),
+    ))
+}
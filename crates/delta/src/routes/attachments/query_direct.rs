@@ -0,0 +1,92 @@
+use revolt_database::{
+    util::permissions::DatabasePermissionQuery, Channel, Database, MessageFilter, User,
+};
+use revolt_models::v0::{self, BulkAttachmentsResponse};
+use revolt_permissions::{calculate_channel_permissions, ChannelPermission};
+use revolt_result::{create_error, Result};
+use rocket::{serde::json::Json, State};
+use validator::Validate;
+
+use crate::routes::channels::attachment_query::query_attachments;
+
+/// Maximum number of conversations searched for attachments
+const MAX_CHANNELS: usize = 100;
+
+/// # Fetch Direct Attachments
+///
+/// Fetch attachments shared across your direct messages and groups.
+///
+/// Only the 100 most recently active conversations are searched. Attachments
+/// from all of them are paginated together by message id, using the same options
+/// as fetching attachments in a single channel.
+#[openapi(tag = "Messaging")]
+#[get("/?<options..>")]
+pub async fn query(
+    db: &State<Database>,
+    user: User,
+    options: v0::OptionsQueryAttachments,
+) -> Result<Json<BulkAttachmentsResponse>> {
+    options.validate().map_err(|error| {
+        create_error!(FailedValidation {
+            error: error.to_string()
+        })
+    })?;
+
+    let mut channels: Vec<Channel> = db
+        .find_direct_messages(&user.id)
+        .await?
+        .into_iter()
+        .filter(|channel| {
+            matches!(
+                channel,
+                Channel::DirectMessage { .. } | Channel::Group { .. }
+            )
+        })
+        .collect();
+
+    // Ids are ULIDs, so the latest message id sorts the most recently active first
+    channels.sort_by_key(|channel| {
+        std::cmp::Reverse(match channel {
+            Channel::DirectMessage {
+                last_message_id, ..
+            }
+            | Channel::Group {
+                last_message_id, ..
+            } => last_message_id.clone(),
+            _ => None,
+        })
+    });
+
+    let mut channel_ids = vec![];
+    for channel in channels.iter().take(MAX_CHANNELS) {
+        let mut query = DatabasePermissionQuery::new(db, &user).channel(channel);
+        if calculate_channel_permissions(&mut query)
+            .await
+            .has_channel_permission(ChannelPermission::ReadMessageHistory)
+        {
+            channel_ids.push(channel.id().to_string());
+        }
+    }
+
+    if channel_ids.is_empty() {
+        return Ok(Json(BulkAttachmentsResponse {
+            attachments: vec![],
+            has_more: false,
+            next_before: None,
+            next_cursor: None,
+            total: (options.include_total == Some(true) || options.limit == Some(0)).then_some(0),
+        }));
+    }
+
+    query_attachments(
+        db,
+        &user,
+        MessageFilter {
+            channels: Some(channel_ids),
+            ..Default::default()
+        },
+        options,
+    )
+    .await
+    .map(Json)
+}
@@ -0,0 +1,79 @@
+use authifier::models::Session;
+use revolt_database::{util::activity::fetch_session_open_channels, User};
+use revolt_result::{create_error, Result};
+use revolt_rocket_okapi::revolt_okapi::schemars::JsonSchema;
+use rocket::serde::json::Json;
+use serde::Serialize;
+
+/// State of the current session needed to start up a client
+#[derive(Serialize, JsonSchema)]
+pub struct BootstrapResponse {
+    /// Channels this session currently has open
+    pub open_channels: Vec<String>,
+    /// Whether this session has a push subscription
+    pub push_subscribed: bool,
+}
+
+/// # Fetch Session Bootstrap
+///
+/// Fetch the state a client restores on start up for the current session,
+/// saving separate activity and push subscription requests.
+#[openapi(tag = "Core")]
+#[get("/bootstrap")]
+pub async fn bootstrap(user: User, session: Session) -> Result<Json<BootstrapResponse>> {
+    if user.bot.is_some() {
+        return Err(create_error!(IsBot));
+    }
+
+    let mut open_channels: Vec<String> = fetch_session_open_channels(&user.id, &session.id)
+        .await?
+        .into_iter()
+        .collect();
+    open_channels.sort();
+
+    Ok(Json(BootstrapResponse {
+        open_channels,
+        push_subscribed: session.subscription.is_some(),
+    }))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{rocket, util::test::TestHarness};
+    use rocket::http::{ContentType, Header, Status};
+
+    #[rocket::async_test]
+    async fn includes_channels_opened_by_this_session_only() {
+        let harness = TestHarness::new().await;
+        let (account, session, user) = harness.new_user().await;
+        let (_, other_session) = harness.account_from_user(account.id.clone()).await;
+        let (server, channels) = harness.new_server(&user).await;
+        let other_channel = harness.new_channel(&server).await;
+
+        for (session, channel) in [(&session, &channels[0]), (&other_session, &other_channel)] {
+            let response = harness
+                .client
+                .put(format!("/channels/{}", channel.id()))
+                .header(Header::new("x-session-token", session.token.to_string()))
+                .header(ContentType::JSON)
+                .body(json!({ "type": "open" }).to_string())
+                .dispatch()
+                .await;
+
+            assert_eq!(response.status(), Status::NoContent);
+        }
+
+        let response = harness
+            .client
+            .get("/bootstrap")
+            .header(Header::new("x-session-token", session.token.to_string()))
+            .dispatch()
+            .await;
+
+        assert_eq!(response.status(), Status::Ok);
+
+        let body: serde_json::Value = response.into_json().await.expect("bootstrap");
+        assert_eq!(body["open_channels"], json!([channels[0].id()]));
+        assert_eq!(body["push_subscribed"], false);
+    }
+}
@@ -1,6 +1,9 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
 use revolt_database::{
+    format,
     util::{permissions::DatabasePermissionQuery, reference::Reference},
-    Database, MessageFilter, MessageQuery, MessageTimePeriod, User,
+    Database, Message, MessageFilter, MessageQuery, MessageTimePeriod, User,
 };
 use revolt_models::v0::{self, BulkAttachmentsResponse};
 use revolt_permissions::{calculate_channel_permissions, ChannelPermission};
@@ -8,9 +11,70 @@ use revolt_result::{create_error, Result};
 use rocket::{serde::json::Json, State};
 use validator::Validate;
 
+/// Maximum number of characters of message content included with each attachment
+const SNIPPET_LENGTH: usize = 100;
+
+/// Number of most recent messages ranked when sorting attachments by popularity
+///
+/// Ranking happens in memory, so this bounds how many messages are loaded per request.
+const POPULAR_WINDOW: i64 = 500;
+
+/// Maximum number of attachment types which may be requested at once
+const MAX_CONTENT_TYPES: usize = 5;
+
+/// Parse a comma-separated list of attachment types into metadata type names
+pub(crate) fn parse_content_types(value: &str) -> Result<Vec<String>> {
+    let mut types: Vec<String> = vec![];
+    for kind in value.split(',').map(str::trim) {
+        let kind = match kind.to_lowercase().as_str() {
+            "file" => "File",
+            "text" => "Text",
+            "image" => "Image",
+            "video" => "Video",
+            "audio" => "Audio",
+            _ => {
+                return Err(create_error!(FailedValidation {
+                    error: format!("unknown content type `{kind}`")
+                }))
+            }
+        };
+
+        if !types.iter().any(|existing| existing == kind) {
+            types.push(kind.to_string());
+        }
+    }
+
+    if types.len() > MAX_CONTENT_TYPES {
+        return Err(create_error!(FailedValidation {
+            error: format!("at most {MAX_CONTENT_TYPES} content types may be requested")
+        }));
+    }
+
+    Ok(types)
+}
+
+/// Copy the dimensions of images and videos out of their metadata
+fn with_dimensions(file: &mut v0::File) {
+    if let Some((width, height)) = file.dimensions() {
+        file.width = Some(width);
+        file.height = Some(height);
+    }
+}
+
 /// # Fetch Attachments
 ///
 /// Fetch attachments uploaded to a channel.
+///
+/// If requested and enabled on this instance, each attachment includes a signed
+/// download link which expires after `files.signed_url_ttl` seconds (5 minutes by default).
+///
+/// Set `include_total` to also receive the number of matching attachments across
+/// all pages. This runs an extra count query, so avoid it when the total is not shown.
+/// Set `limit` to 0 to only receive the total, without any attachments.
+///
+/// Set `min_size` or `min_dimension` to skip small files such as stickers and emoji.
+/// Pages may then hold fewer than `limit` attachments. The total only counts attachments
+/// which meet these minimums too.
 #[openapi(tag = "Messaging")]
 #[get("/<target>/attachments?<options..>")]
 pub async fn query(
@@ -32,44 +96,514 @@ pub async fn query(
         .await
         .throw_if_lacking_channel_permission(ChannelPermission::ReadMessageHistory)?;
 
+    query_attachments(
+        db,
+        &user,
+        MessageFilter {
+            channel: Some(channel.id().to_string()),
+            ..Default::default()
+        },
+        options,
+    )
+    .await
+    .map(Json)
+}
+
+/// Cursor pointing at the attachment at `index` within a message
+fn format_cursor(message_id: &str, index: usize) -> String {
+    format!("{message_id}:{index}")
+}
+
+/// Parse a cursor into the message id and attachment index it points at
+fn parse_cursor(cursor: &str) -> Result<(String, usize)> {
+    cursor
+        .split_once(':')
+        .filter(|(message_id, _)| message_id.len() == 26)
+        .and_then(|(message_id, index)| Some((message_id.to_string(), index.parse().ok()?)))
+        .ok_or_else(|| {
+            create_error!(FailedValidation {
+                error: "invalid attachment cursor".to_string()
+            })
+        })
+}
+
+/// Cursor to resume from after a page of attachments
+///
+/// A page cut off at `limit` resumes after the last attachment on it. Otherwise every
+/// fetched message was looked at, so the page resumes after the last of them, even if
+/// none of its attachments made it through the filters.
+fn next_page_cursor(
+    last_kept: Option<(&str, usize)>,
+    truncated: bool,
+    last_fetched: Option<(&str, usize)>,
+) -> Option<String> {
+    let (message_id, index) = if truncated { last_kept? } else { last_fetched? };
+    Some(format_cursor(message_id, index))
+}
+
+/// Whether a message is covered by the filter attachments are being fetched with
+fn in_filter(message: &Message, filter: &MessageFilter) -> bool {
+    filter
+        .channel
+        .as_ref()
+        .map_or(true, |channel| channel == &message.channel)
+        && filter
+            .channels
+            .as_ref()
+            .map_or(true, |channels| channels.contains(&message.channel))
+        && filter
+            .author
+            .as_ref()
+            .map_or(true, |author| author == &message.author)
+}
+
+/// Sort messages by how many reactions they have, most first and newest first among equals
+fn rank_by_reactions(messages: &mut [Message]) {
+    messages.sort_by(|a, b| {
+        let reactions = |message: &Message| {
+            message
+                .reactions
+                .values()
+                .map(|users| users.len())
+                .sum::<usize>()
+        };
+        reactions(b)
+            .cmp(&reactions(a))
+            .then_with(|| b.id.cmp(&a.id))
+    });
+}
+
+/// Fetch a page of attachments from messages matching the given filter
+///
+/// Attachments are ordered by their message, following the requested sort, and
+/// within a message in the order they were attached. Paging by attachment resumes
+/// from a cursor in that order, so no attachment is skipped or repeated.
+///
+/// Permissions must already have been checked for every channel the filter covers.
+pub(crate) async fn query_attachments(
+    db: &Database,
+    user: &User,
+    filter: MessageFilter,
+    options: v0::OptionsQueryAttachments,
+) -> Result<BulkAttachmentsResponse> {
     let v0::OptionsQueryAttachments {
         limit,
         before,
         after,
         sort,
+        spoiler,
+        include_snippet,
+        include_url,
+        content_type,
+        mine,
+        min_size,
+        min_dimension,
+        popular,
+        by_attachment,
+        cursor,
+        include_total,
     } = options;
 
+    let content_types = content_type
+        .as_deref()
+        .map(parse_content_types)
+        .transpose()?;
+
+    let config = revolt_config::config().await;
+    let signed_url_expiry = if include_url == Some(true) && !config.files.signed_url_key.is_empty()
+    {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("time went backwards")
+            .as_secs();
+
+        Some(now + config.files.signed_url_ttl)
+    } else {
+        None
+    };
+
     // Fetch messages with attachments, paginated by message ID
-    let messages = db
+    let count_only = limit == Some(0);
+    let limit = limit.unwrap_or(50);
+    let oldest_first = matches!(sort, Some(v0::MessageSort::Oldest));
+    let filter = MessageFilter {
+        author: (mine == Some(true)).then(|| user.id.clone()),
+        has_attachments: Some(true),
+        attachment_types: content_types.clone(),
+        ..filter
+    };
+
+    // Count across all pages with the same filter, ignoring pagination
+    let total = if include_total == Some(true) || count_only {
+        Some(
+            db.count_attachments(&filter, spoiler, min_size, min_dimension)
+                .await?,
+        )
+    } else {
+        None
+    };
+
+    if count_only {
+        return Ok(BulkAttachmentsResponse {
+            attachments: vec![],
+            has_more: total.is_some_and(|total| total > 0),
+            next_before: None,
+            next_cursor: None,
+            total,
+        });
+    }
+
+    // Resume strictly after the attachment named by the cursor, even within its message
+    let cursor = cursor.as_deref().map(parse_cursor).transpose()?;
+    let by_attachment = by_attachment == Some(true) || cursor.is_some();
+    let (before, after, resumed) = match &cursor {
+        Some((message_id, _)) => {
+            let message = db.fetch_message(message_id).await?;
+            if !in_filter(&message, &filter) {
+                return Err(create_error!(NotFound));
+            }
+
+            if oldest_first {
+                (None, Some(message_id.clone()), Some(message))
+            } else {
+                (Some(message_id.clone()), None, Some(message))
+            }
+        }
+        None => (before, after, None),
+    };
+
+    // Rank a window of recent messages by popularity, rather than paginating
+    let popular = popular == Some(true);
+    let mut messages = db
         .fetch_messages(MessageQuery {
-            filter: MessageFilter {
-                channel: Some(channel.id().to_string()),
-                has_attachments: Some(true),
-                ..Default::default()
-            },
+            filter,
             time_period: MessageTimePeriod::Absolute {
                 before,
                 after,
-                sort,
+                sort: if popular {
+                    Some(v0::MessageSort::Latest)
+                } else {
+                    sort
+                },
             },
-            limit,
+            limit: Some(if popular { POPULAR_WINDOW } else { limit }),
         })
         .await?;
 
-    // Flatten attachments from messages, setting message_id on each
-    let attachments = messages
+    let (mut has_more, next_before) = if popular {
+        rank_by_reactions(&mut messages);
+        messages.truncate(limit as usize);
+        (false, None)
+    } else {
+        // A full page of messages means there may be more to fetch
+        let has_more = messages.len() as i64 >= limit;
+        let next_before = if has_more && !oldest_first && !by_attachment {
+            messages.iter().map(|msg| msg.id.clone()).min()
+        } else {
+            None
+        };
+
+        (has_more, next_before)
+    };
+
+    // Last attachment of the last message fetched, whether or not it passes the filters
+    let last_fetched = messages.last().map(|msg| {
+        let count = msg.attachments.as_ref().map_or(0, Vec::len);
+        (msg.id.clone(), count.saturating_sub(1))
+    });
+
+    // Flatten attachments from messages in canonical order, setting message_id and index on each
+    let signed_url_key = &config.files.signed_url_key;
+    let autumn = &config.hosts.autumn;
+    let cursor = &cursor;
+    let mut attachments: Vec<(String, usize, v0::File)> = resumed
         .into_iter()
+        .chain(messages)
         .flat_map(|msg| {
             let message_id = msg.id.clone();
+            let snippet = if include_snippet == Some(true) {
+                msg.content
+                    .as_deref()
+                    .map(|content| format::snippet(content, SNIPPET_LENGTH))
+            } else {
+                None
+            };
+
+            // Skip what was already returned from the message the cursor points into
+            let skip = match cursor {
+                Some((cursor_id, index)) if cursor_id == &message_id => index + 1,
+                _ => 0,
+            };
+
+            let content_types = &content_types;
             msg.attachments
                 .unwrap_or_default()
                 .into_iter()
-                .map(move |mut file| {
+                .enumerate()
+                .skip(skip)
+                .filter(move |(_, file)| {
+                    content_types.as_ref().map_or(true, |types| {
+                        types.iter().any(|kind| kind == file.metadata.type_name())
+                    })
+                })
+                .map(move |(index, mut file)| {
                     file.message_id = Some(message_id.clone());
-                    file.into()
+                    file.ensure_content_type();
+                    let mut file = v0::File::from(file);
+                    file.snippet = snippet.clone();
+                    file.index = Some(index);
+                    with_dimensions(&mut file);
+                    file.url = signed_url_expiry.map(|expires| {
+                        revolt_files::sign_url(
+                            signed_url_key,
+                            autumn,
+                            &file.tag,
+                            &file.id,
+                            &file.filename,
+                            expires,
+                        )
+                    });
+                    (message_id.clone(), index, file)
                 })
         })
+        .filter(|(_, _, file)| spoiler.map_or(true, |spoiler| file.is_spoiler() == spoiler))
+        .filter(|(_, _, file)| file.is_large_enough(min_size, min_dimension))
         .collect();
 
-    Ok(Json(BulkAttachmentsResponse { attachments }))
+    // Cut the page off at exactly `limit` attachments, even in the middle of a message
+    let mut next_cursor = None;
+    if by_attachment && !popular {
+        let truncated = attachments.len() > limit as usize;
+        if truncated {
+            attachments.truncate(limit as usize);
+            has_more = true;
+        }
+
+        if has_more {
+            next_cursor = next_page_cursor(
+                attachments
+                    .last()
+                    .map(|(message_id, index, _)| (message_id.as_str(), *index)),
+                truncated,
+                last_fetched
+                    .as_ref()
+                    .map(|(message_id, index)| (message_id.as_str(), *index)),
+            );
+        }
+    }
+
+    Ok(BulkAttachmentsResponse {
+        attachments: attachments.into_iter().map(|(_, _, file)| file).collect(),
+        has_more,
+        next_before,
+        next_cursor,
+        total,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use revolt_database::Message;
+    use revolt_models::v0;
+
+    use super::{
+        format_cursor, next_page_cursor, parse_content_types, parse_cursor, rank_by_reactions,
+        with_dimensions,
+    };
+
+    /// Image attachment of the given size
+    fn file(metadata: v0::Metadata, size: isize) -> v0::File {
+        v0::File {
+            id: "file".to_string(),
+            tag: "attachments".to_string(),
+            filename: "image.png".to_string(),
+            metadata,
+            content_type: "image/png".to_string(),
+            size,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn parses_content_type_lists() {
+        assert_eq!(
+            parse_content_types("image, Video,image").unwrap(),
+            vec!["Image".to_string(), "Video".to_string()]
+        );
+        assert!(parse_content_types("image,spreadsheet").is_err());
+        assert!(parse_content_types("").is_err());
+    }
+
+    #[test]
+    fn resumes_after_attachments_which_were_filtered_out() {
+        let kept = Some(("01HZ0000000000000000000000", 1));
+        let fetched = Some(("01HZ0000000000000000000009", 3));
+
+        assert_eq!(
+            next_page_cursor(kept, true, fetched).as_deref(),
+            Some("01HZ0000000000000000000000:1")
+        );
+        assert_eq!(
+            next_page_cursor(kept, false, fetched).as_deref(),
+            Some("01HZ0000000000000000000009:3")
+        );
+
+        // Nothing on the page made it through the filters
+        assert_eq!(
+            next_page_cursor(None, false, fetched).as_deref(),
+            Some("01HZ0000000000000000000009:3")
+        );
+    }
+
+    #[test]
+    fn includes_dimensions_of_images() {
+        let mut image = file(
+            v0::Metadata::Image {
+                width: 640,
+                height: 480,
+            },
+            1024,
+        );
+        with_dimensions(&mut image);
+        assert_eq!((image.width, image.height), (Some(640), Some(480)));
+
+        let mut plain = file(v0::Metadata::File, 1024);
+        with_dimensions(&mut plain);
+        assert_eq!((plain.width, plain.height), (None, None));
+    }
+
+    #[test]
+    fn skips_small_attachments() {
+        let sticker = file(
+            v0::Metadata::Image {
+                width: 64,
+                height: 64,
+            },
+            2048,
+        );
+        let photo = file(
+            v0::Metadata::Image {
+                width: 1920,
+                height: 1080,
+            },
+            512 * 1024,
+        );
+        let document = file(v0::Metadata::File, 100);
+
+        assert!(sticker.is_large_enough(None, None));
+        assert!(!sticker.is_large_enough(None, Some(128)));
+        assert!(photo.is_large_enough(Some(4096), Some(128)));
+        assert!(document.is_large_enough(None, Some(128)));
+        assert!(!document.is_large_enough(Some(4096), None));
+    }
+
+    #[test]
+    fn ranks_messages_by_reactions() {
+        let message = |id: &str, reactions: &[(&str, &[&str])]| Message {
+            id: id.to_string(),
+            reactions: reactions
+                .iter()
+                .map(|(emoji, users)| {
+                    (
+                        emoji.to_string(),
+                        users.iter().map(|user| user.to_string()).collect(),
+                    )
+                })
+                .collect(),
+            ..Default::default()
+        };
+
+        let mut messages = vec![
+            message("01", &[("👍", &["a"])]),
+            message("02", &[]),
+            message("03", &[("👍", &["a", "b"]), ("🎉", &["c"])]),
+            message("04", &[("🎉", &["a"])]),
+        ];
+        rank_by_reactions(&mut messages);
+
+        let ids: Vec<&str> = messages.iter().map(|msg| msg.id.as_str()).collect();
+        assert_eq!(ids, vec!["03", "04", "01", "02"]);
+    }
+
+    #[test]
+    fn round_trips_attachment_cursors() {
+        let message_id = "01HZ0000000000000000000000";
+        let cursor = format_cursor(message_id, 2);
+        assert_eq!(parse_cursor(&cursor).unwrap(), (message_id.to_string(), 2));
+
+        assert!(parse_cursor("01HZ0000000000000000000000").is_err());
+        assert!(parse_cursor("short:1").is_err());
+        assert!(parse_cursor("01HZ0000000000000000000000:first").is_err());
+    }
+
+    #[rocket::async_test]
+    async fn documents_paging_fields() {
+        let harness = crate::util::test::TestHarness::new().await;
+        let spec: serde_json::Value = harness
+            .client
+            .get("/openapi.json")
+            .dispatch()
+            .await
+            .into_json()
+            .await
+            .expect("openapi spec");
+
+        let response = &spec["components"]["schemas"]["BulkAttachmentsResponse"]["properties"];
+        for field in [
+            "attachments",
+            "has_more",
+            "next_before",
+            "next_cursor",
+            "total",
+        ] {
+            assert!(
+                response.get(field).is_some(),
+                "missing response field {field}"
+            );
+        }
+
+        let file = &spec["components"]["schemas"]["File"]["properties"];
+        assert!(file.get("index").is_some(), "missing attachment index");
+
+        let parameters: Vec<&str> = spec["paths"]["/channels/{target}/attachments"]["get"]
+            ["parameters"]
+            .as_array()
+            .expect("parameters")
+            .iter()
+            .filter_map(|parameter| parameter["name"].as_str())
+            .collect();
+        for option in ["limit", "by_attachment", "cursor", "include_total"] {
+            assert!(
+                parameters.contains(&option),
+                "missing query option {option}"
+            );
+        }
+    }
+
+    #[rocket::async_test]
+    async fn zero_limit_only_counts() {
+        let harness = crate::util::test::TestHarness::new().await;
+        let (_, session, user) = harness.new_user().await;
+        let (_, channels) = harness.new_server(&user).await;
+
+        let response: serde_json::Value = harness
+            .client
+            .get(format!(
+                "/channels/{}/attachments?limit=0",
+                channels[0].id()
+            ))
+            .header(rocket::http::Header::new(
+                "x-session-token",
+                session.token.to_string(),
+            ))
+            .dispatch()
+            .await
+            .into_json()
+            .await
+            .expect("response");
+
+        assert_eq!(response["attachments"], serde_json::json!([]));
+        assert_eq!(response["total"], 0);
+        assert_eq!(response["has_more"], false);
+    }
 }
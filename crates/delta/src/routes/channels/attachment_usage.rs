@@ -0,0 +1,54 @@
+use revolt_database::{
+    util::{permissions::DatabasePermissionQuery, reference::Reference},
+    Database, User,
+};
+use revolt_models::v0;
+use revolt_permissions::{calculate_channel_permissions, ChannelPermission};
+use revolt_result::{create_error, Result};
+use rocket::{serde::json::Json, State};
+use validator::Validate;
+
+use super::attachment_query::parse_content_types;
+
+/// # Fetch Attachment Usage
+///
+/// Fetch the number and total size of attachments uploaded to a channel.
+///
+/// Requires either `ReadMessageHistory` or `ManageChannel`.
+#[openapi(tag = "Messaging")]
+#[get("/<target>/attachments/usage?<options..>")]
+pub async fn usage(
+    db: &State<Database>,
+    user: User,
+    target: Reference<'_>,
+    options: v0::OptionsAttachmentUsage,
+) -> Result<Json<v0::AttachmentUsageResponse>> {
+    options.validate().map_err(|error| {
+        create_error!(FailedValidation {
+            error: error.to_string()
+        })
+    })?;
+
+    let channel = target.as_channel(db).await?;
+
+    let mut query = DatabasePermissionQuery::new(db, &user).channel(&channel);
+    let permissions = calculate_channel_permissions(&mut query).await;
+    if !permissions.has_channel_permission(ChannelPermission::ManageChannel) {
+        permissions.throw_if_lacking_channel_permission(ChannelPermission::ReadMessageHistory)?;
+    }
+
+    let content_types = options
+        .content_type
+        .as_deref()
+        .map(parse_content_types)
+        .transpose()?;
+
+    let usage = db
+        .fetch_attachment_usage(channel.id(), content_types.as_deref())
+        .await?;
+
+    Ok(Json(v0::AttachmentUsageResponse {
+        count: usage.count,
+        size: usage.size,
+    }))
+}
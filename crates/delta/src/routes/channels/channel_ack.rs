@@ -1,5 +1,10 @@
+use authifier::models::Session;
 use revolt_database::{
-    util::{permissions::DatabasePermissionQuery, reference::Reference},
+    util::{
+        activity::{refresh_session_activity, touch_session},
+        permissions::DatabasePermissionQuery,
+        reference::Reference,
+    },
     Database, User,
 };
 use revolt_permissions::{calculate_channel_permissions, ChannelPermission};
@@ -10,11 +15,14 @@ use rocket_empty::EmptyResponse;
 /// # Acknowledge Message
 ///
 /// Lets the server and all other clients know that we've seen this message id in this channel.
+///
+/// If this session has the channel open, it is kept open for longer.
 #[openapi(tag = "Messaging")]
 #[put("/<target>/ack/<message>")]
 pub async fn ack(
     db: &State<Database>,
     user: User,
+    session: Session,
     target: Reference<'_>,
     message: Reference<'_>,
 ) -> Result<EmptyResponse> {
@@ -28,10 +36,18 @@ pub async fn ack(
         .await
         .throw_if_lacking_channel_permission(ChannelPermission::ViewChannel)?;
 
-    channel
-        .ack(&user.id, message.id)
-        .await
-        .map(|_| EmptyResponse)
+    channel.ack(&user.id, message.id).await?;
+
+    // Reading activity should not fail the ack itself
+    if let Err(err) = refresh_session_activity(&user.id, &session.id, channel.id()).await {
+        revolt_config::capture_error(&err);
+    }
+
+    if let Err(err) = touch_session(&user.id, &session.id).await {
+        revolt_config::capture_error(&err);
+    }
+
+    Ok(EmptyResponse)
 }
 
 #[cfg(test)]
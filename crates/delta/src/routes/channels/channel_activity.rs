@@ -1,14 +1,30 @@
 use authifier::models::Session;
+use revolt_config::{ApiUsers, Settings};
 use revolt_database::{
-    util::{permissions::DatabasePermissionQuery, reference::Reference},
-    Database, User,
+    events::client::EventV1,
+    metrics,
+    util::{
+        activity::{
+            channel_viewers_key, extend_session_expiry, open_channels_key, record_last_channel,
+            touch_session, unfocused_session_key, ACTIVITY_TTL,
+        },
+        permissions::DatabasePermissionQuery,
+        reference::Reference,
+        retry::redis_connection,
+    },
+    Channel, Database, PartialUser, Presence, User, AMQP,
 };
+use revolt_models::v0;
 use revolt_permissions::{calculate_channel_permissions, ChannelPermission};
-use revolt_result::{create_error, Result};
+use revolt_result::{create_database_error, create_error, Error, ErrorType, Result};
 use revolt_rocket_okapi::{openapi, revolt_okapi::schemars::JsonSchema};
 use rocket::{serde::json::Json, State};
 use rocket_empty::EmptyResponse;
 use serde::{Deserialize, Serialize};
+use std::{
+    fmt::Debug,
+    time::{SystemTime, UNIX_EPOCH},
+};
 
 /// Request body for channel activity
 #[derive(Deserialize, JsonSchema)]
@@ -16,6 +32,38 @@ pub struct ChannelActivityRequest {
     /// Type of activity: 'open' to mark channel as open, 'close' to mark as closed
     #[serde(rename = "type")]
     pub activity_type: ChannelActivityType,
+    /// Message to mark the channel as read up to when opening or closing it
+    ///
+    /// When closing, this is the latest message the user saw before navigating away.
+    #[serde(default)]
+    pub mark_read_message_id: Option<String>,
+    /// Whether the window showing the channel is focused when opening it, `true` if omitted
+    ///
+    /// Notifications are only suppressed for sessions which have the channel open and focused.
+    #[serde(default)]
+    pub focused: Option<bool>,
+    /// Seconds the channel should stay open for without being refreshed, 5 minutes if omitted
+    ///
+    /// Clients which refresh less often may ask for longer. Kept within the bounds the server allows.
+    #[serde(default)]
+    pub ttl_hint: Option<u64>,
+}
+
+/// Request body for channel activity alongside a presence change
+#[derive(Deserialize, JsonSchema)]
+pub struct ChannelActivityPresenceRequest {
+    /// Channel activity to record
+    #[serde(flatten)]
+    pub activity: ChannelActivityRequest,
+    /// Presence to set on the user
+    pub presence: v0::Presence,
+}
+
+/// Number of sessions with a channel open
+#[derive(Serialize, JsonSchema)]
+pub struct OpenSessionsResponse {
+    /// Number of the user's sessions which currently have the channel open
+    pub count: usize,
 }
 
 /// Type of channel activity
@@ -29,69 +77,587 @@ pub enum ChannelActivityType {
 /// # Update Channel Activity
 ///
 /// Mark a channel as opened or closed by the user.
+///
+/// Opening or closing a channel may also acknowledge a message in it, saving a separate ack request.
+/// Channel types which don't track activity still acknowledge it, but respond with `ActivityNotSupported`.
 #[openapi(tag = "Channel Information")]
 #[put("/<target>", data = "<data>")]
 pub async fn update_activity(
     db: &State<Database>,
+    amqp: &State<AMQP>,
     user: User,
     session: Session,
     target: Reference<'_>,
     data: Json<ChannelActivityRequest>,
 ) -> Result<EmptyResponse> {
+    let config = revolt_config::config().await;
+    record_activity(db, amqp, &config, &user, &session, &target, &data).await?;
+
+    if matches!(data.activity_type, ChannelActivityType::Open)
+        && config.api.users.activity_refreshes_presence
+    {
+        // Only refreshes whether they're online, leaving any status they chose alone
+        revolt_presence::refresh_online(&user.id).await;
+    }
+
+    Ok(EmptyResponse)
+}
+
+/// # Update Channel Activity and Presence
+///
+/// Mark a channel as opened or closed by the user, and set their presence in the same request.
+///
+/// Saves a separate presence request when switching channels.
+#[openapi(tag = "Channel Information")]
+#[put("/<target>/activity", data = "<data>")]
+pub async fn update_activity_presence(
+    db: &State<Database>,
+    amqp: &State<AMQP>,
+    user: User,
+    session: Session,
+    target: Reference<'_>,
+    data: Json<ChannelActivityPresenceRequest>,
+) -> Result<EmptyResponse> {
+    let ChannelActivityPresenceRequest { activity, presence } = data.into_inner();
+
+    let config = revolt_config::config().await;
+    record_activity(db, amqp, &config, &user, &session, &target, &activity).await?;
+    set_presence(db, user, presence.into()).await?;
+
+    Ok(EmptyResponse)
+}
+
+/// Record a user opening or closing a channel they can view
+async fn record_activity(
+    db: &Database,
+    amqp: &AMQP,
+    config: &Settings,
+    user: &User,
+    session: &Session,
+    target: &Reference<'_>,
+    data: &ChannelActivityRequest,
+) -> Result<()> {
     if user.bot.is_some() {
         return Err(create_error!(IsBot));
     }
 
-    let channel = target.as_channel(db).await?;
-    let mut query = DatabasePermissionQuery::new(db, &user).channel(&channel);
+    let ttl = activity_ttl(&config.api.users, data.ttl_hint)?;
+    let channel = fetch_activity_channel(db, target).await?;
+    let mut query = DatabasePermissionQuery::new(db, user).channel(&channel);
     calculate_channel_permissions(&mut query)
         .await
         .throw_if_lacking_channel_permission(ChannelPermission::ViewChannel)?;
 
+    if let Some(message_id) = &data.mark_read_message_id {
+        mark_read(db, amqp, user, &channel, message_id).await?;
+    }
+
+    // Keep track of the primary session even if this channel isn't tracked
+    if let Err(err) = touch_session(&user.id, &session.id).await {
+        revolt_config::capture_error(&err);
+    }
+
+    // Viewing some channel types is meaningless, so don't let them suppress notifications
+    if is_untracked_channel(config, &channel) {
+        return Err(create_error!(ActivityNotSupported));
+    }
+
     // Update channel activity in Redis
-    update_channel_activity_in_redis(&user.id, &session.id, channel.id(), &data.activity_type)
+    update_channel_activity_in_redis(
+        &user.id,
+        &session.id,
+        &channel,
+        &data.activity_type,
+        data.focused.unwrap_or(true),
+        ttl,
+        &config.api.users,
+    )
+    .await?;
+
+    // Remember where the user was, so they can pick up from there later
+    if matches!(data.activity_type, ChannelActivityType::Open) {
+        if let Err(err) = record_last_channel(&user.id, channel.id()).await {
+            revolt_config::capture_error(&err);
+        }
+    }
+
+    Ok(())
+}
+
+/// How long channels opened by an activity update stay open for, in seconds
+///
+/// Hints are clamped to the configured bounds, as is the default if no hint was given.
+pub(crate) fn activity_ttl(users: &ApiUsers, hint: Option<u64>) -> Result<u64> {
+    if hint == Some(0) {
+        return Err(create_error!(FailedValidation {
+            error: "ttl_hint must be at least one second".to_string()
+        }));
+    }
+
+    let max = users.max_activity_ttl.max(users.min_activity_ttl);
+    Ok(hint
+        .unwrap_or(ACTIVITY_TTL)
+        .clamp(users.min_activity_ttl, max))
+}
+
+/// Fetch the channel targeted by an activity update
+///
+/// A server id is rejected as an invalid operation rather than being reported as an unknown channel.
+async fn fetch_activity_channel(db: &Database, target: &Reference<'_>) -> Result<Channel> {
+    match target.as_channel(db).await {
+        Err(err) if matches!(err.error_type, ErrorType::NotFound) => {
+            if target.as_server(db).await.is_ok() {
+                Err(create_error!(InvalidOperation))
+            } else {
+                Err(create_error!(UnknownChannel))
+            }
+        }
+        result => result,
+    }
+}
+
+/// Whether activity in a channel is ignored because of its type
+pub(crate) fn is_untracked_channel(config: &Settings, channel: &Channel) -> bool {
+    config
+        .api
+        .users
+        .untracked_activity_channel_types
+        .iter()
+        .any(|channel_type| channel_type == channel_type_name(channel))
+}
+
+/// Acknowledge a message in a channel which is being opened or closed
+async fn mark_read(
+    db: &Database,
+    amqp: &AMQP,
+    user: &User,
+    channel: &Channel,
+    message_id: &str,
+) -> Result<()> {
+    let message = Reference::from_unchecked(message_id).as_message(db).await?;
+    if message.channel != channel.id() {
+        return Err(create_error!(NotFound));
+    }
+
+    db.acknowledge_message(channel.id(), &user.id, &message.id)
         .await?;
 
-    Ok(EmptyResponse)
+    EventV1::ChannelAck {
+        id: channel.id().to_string(),
+        user: user.id.clone(),
+        message_id: message.id.clone(),
+    }
+    .private(user.id.clone())
+    .await;
+
+    if let Err(err) = amqp
+        .ack_message(user.id.clone(), channel.id().to_string(), message.id)
+        .await
+    {
+        revolt_config::capture_error(&err);
+    }
+
+    Ok(())
+}
+
+/// Name of the channel type, as used in configuration
+pub(crate) fn channel_type_name(channel: &Channel) -> &'static str {
+    match channel {
+        Channel::SavedMessages { .. } => "SavedMessages",
+        Channel::DirectMessage { .. } => "DirectMessage",
+        Channel::Group { .. } => "Group",
+        Channel::TextChannel { .. } => "TextChannel",
+        Channel::VoiceChannel { .. } => "VoiceChannel",
+    }
+}
+
+/// Set the presence of a user, if it has changed
+async fn set_presence(db: &Database, mut user: User, presence: Presence) -> Result<()> {
+    let current = user
+        .status
+        .as_ref()
+        .and_then(|status| status.presence.as_ref());
+    if current == Some(&presence) {
+        return Ok(());
+    }
+
+    let mut status = user.status.take().unwrap_or_default();
+    status.presence = Some(presence);
+
+    user.update(
+        db,
+        PartialUser {
+            status: Some(status),
+            ..Default::default()
+        },
+        vec![],
+    )
+    .await
+}
+
+/// # Fetch Open Sessions
+///
+/// Count how many of your sessions currently have a channel open.
+#[openapi(tag = "Channel Information")]
+#[get("/<target>/sessions")]
+pub async fn fetch_open_sessions(
+    db: &State<Database>,
+    user: User,
+    target: Reference<'_>,
+) -> Result<Json<OpenSessionsResponse>> {
+    if user.bot.is_some() {
+        return Err(create_error!(IsBot));
+    }
+
+    let channel = fetch_activity_channel(db, &target).await?;
+    let count = count_open_sessions(&user.id, channel.id()).await?;
+
+    Ok(Json(OpenSessionsResponse { count }))
+}
+
+/// Count distinct sessions of a user which have the given channel open
+pub async fn count_open_sessions(user_id: &str, channel_id: &str) -> Result<usize> {
+    use redis_kiss::{redis::AsyncIter, AsyncCommands};
+
+    let mut conn = redis_connection().await?;
+
+    let mut keys = vec![];
+    {
+        let mut iter: AsyncIter<String> = conn
+            .scan_match(open_channels_key(user_id, "*"))
+            .await
+            .map_err(redis_error("scan", "open_channels"))?;
+
+        while let Some(key) = iter.next_item().await {
+            keys.push(key);
+        }
+    }
+
+    let mut count = 0;
+    for key in keys {
+        let open: bool = conn
+            .sismember(&key, channel_id)
+            .await
+            .map_err(redis_error("sismember", "open_channels"))?;
+
+        if open {
+            count += 1;
+        }
+    }
+
+    Ok(count)
+}
+
+/// Log a failed Redis command and describe it as a database error
+pub(crate) fn redis_error<E: Debug>(
+    operation: &'static str,
+    collection: &'static str,
+) -> impl FnOnce(E) -> Error {
+    move |err| {
+        log::error!("Redis `{operation}` on `{collection}` failed: {err:?}");
+        create_database_error!(operation, collection)
+    }
+}
+
+/// Close whichever channel the session opened (or last refreshed) longest ago
+///
+/// Channels are ordered by when their viewer entry for this session expires.
+async fn evict_oldest_channel<C: redis_kiss::AsyncCommands>(
+    conn: &mut C,
+    session_key: &str,
+    viewer: &str,
+) -> Result<()> {
+    let channels: Vec<String> = conn
+        .smembers(session_key)
+        .await
+        .map_err(redis_error("smembers", "open_channels"))?;
+
+    let mut oldest: Option<(u64, String)> = None;
+    for channel_id in channels {
+        // Channels without a viewer entry have already lapsed, so go first
+        let expires_at: Option<u64> = conn
+            .zscore(channel_viewers_key(&channel_id), viewer)
+            .await
+            .map_err(redis_error("zscore", "channel_viewers"))?;
+
+        let expires_at = expires_at.unwrap_or_default();
+        if oldest
+            .as_ref()
+            .map_or(true, |(oldest_expiry, _)| expires_at < *oldest_expiry)
+        {
+            oldest = Some((expires_at, channel_id));
+        }
+    }
+
+    if let Some((_, channel_id)) = oldest {
+        let _: () = conn
+            .srem(session_key, &channel_id)
+            .await
+            .map_err(redis_error("srem", "open_channels"))?;
+
+        let _: () = conn
+            .zrem(channel_viewers_key(&channel_id), viewer)
+            .await
+            .map_err(redis_error("zrem", "channel_viewers"))?;
+    }
+
+    Ok(())
 }
 
 /// Update channel activity status in Redis
+///
+/// Sessions may have at most `max_open_channels` channels open (unlimited if zero). Opening
+/// another either closes the least recently opened one or fails, depending on `evict_open_channels`.
+/// Opened channels stay open for `ttl` seconds unless refreshed.
+/// Opens, closes and evictions are counted in the activity metrics.
 async fn update_channel_activity_in_redis(
     user_id: &str,
     session_id: &str,
-    channel_id: &str,
+    channel: &Channel,
     activity_type: &ChannelActivityType,
+    focused: bool,
+    ttl: u64,
+    users: &ApiUsers,
 ) -> Result<()> {
-    use redis_kiss::{get_connection, AsyncCommands};
+    use redis_kiss::AsyncCommands;
 
-    let mut conn = get_connection()
-        .await
-        .map_err(|_| create_error!(InternalError))?;
+    let channel_id = channel.id();
+    let channel_type = channel_type_name(channel);
+    let max_open = users.max_open_channels;
 
-    let session_key = format!("open_channels:{}:{}", user_id, session_id);
+    let mut conn = redis_connection().await?;
+
+    let session_key = open_channels_key(user_id, session_id);
+    let viewers_key = channel_viewers_key(channel_id);
+    let viewer = format!("{}:{}", user_id, session_id);
 
     match activity_type {
         ChannelActivityType::Open => {
+            if max_open > 0 {
+                let open: bool = conn
+                    .sismember(&session_key, channel_id)
+                    .await
+                    .map_err(redis_error("sismember", "open_channels"))?;
+
+                let count: usize = conn
+                    .scard(&session_key)
+                    .await
+                    .map_err(redis_error("scard", "open_channels"))?;
+
+                if !open && count >= max_open {
+                    if !users.evict_open_channels {
+                        return Err(create_error!(TooManyOpenChannels { max: max_open }));
+                    }
+
+                    evict_oldest_channel(&mut conn, &session_key, &viewer).await?;
+                    metrics::record_channel_evicted();
+                }
+            }
+
             // Add channel to open channels set
             let _: () = conn
                 .sadd(&session_key, channel_id)
                 .await
-                .map_err(|_| create_error!(InternalError))?;
+                .map_err(redis_error("sadd", "open_channels"))?;
+
+            // Other channels in the session may have asked for longer, so never shorten it
+            extend_session_expiry(&mut conn, &session_key, ttl).await?;
+
+            // Index this session as a viewer of the channel until the TTL lapses
+            let expires_at = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .expect("Time went backwards")
+                .as_secs()
+                + ttl;
+
+            let _: () = conn
+                .zadd(&viewers_key, &viewer, expires_at)
+                .await
+                .map_err(redis_error("zadd", "channel_viewers"))?;
 
-            // Set TTL for the session key (5 minutes)
+            // Other sessions may have asked for longer, so keep the index for as long as allowed
             let _: () = conn
-                .expire(&session_key, 300)
+                .expire(&viewers_key, ttl.max(users.max_activity_ttl) as usize)
                 .await
-                .map_err(|_| create_error!(InternalError))?;
+                .map_err(redis_error("expire", "channel_viewers"))?;
+
+            // Remember a backgrounded session for as long as its channels stay open
+            let unfocused_key = unfocused_session_key(user_id, session_id);
+            if focused {
+                let _: () = conn
+                    .del(&unfocused_key)
+                    .await
+                    .map_err(redis_error("del", "unfocused_session"))?;
+            } else {
+                let _: () = conn
+                    .set_ex(&unfocused_key, 1, ttl as usize)
+                    .await
+                    .map_err(redis_error("set_ex", "unfocused_session"))?;
+            }
+
+            metrics::record_channel_opened(channel_type);
         }
         ChannelActivityType::Close => {
             // Remove channel from the set
             let _: () = conn
                 .srem(&session_key, channel_id)
                 .await
-                .map_err(|_| create_error!(InternalError))?;
+                .map_err(redis_error("srem", "open_channels"))?;
+
+            // Remove this session from the channel's viewers
+            let _: () = conn
+                .zrem(&viewers_key, &viewer)
+                .await
+                .map_err(redis_error("zrem", "channel_viewers"))?;
+
+            metrics::record_channel_closed(channel_type);
         }
     }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod test {
+    use crate::{rocket, util::test::TestHarness};
+    use revolt_result::ErrorType;
+    use rocket::http::{ContentType, Header, Status};
+
+    use super::{activity_ttl, ACTIVITY_TTL};
+
+    #[rocket::async_test]
+    async fn clamps_ttl_hints() {
+        let config = revolt_config::config().await;
+        let users = &config.api.users;
+
+        assert_eq!(activity_ttl(users, None).unwrap(), ACTIVITY_TTL);
+        assert_eq!(
+            activity_ttl(users, Some(1)).unwrap(),
+            users.min_activity_ttl
+        );
+        assert_eq!(
+            activity_ttl(users, Some(u64::MAX)).unwrap(),
+            users.max_activity_ttl
+        );
+        assert_eq!(
+            activity_ttl(users, Some(users.min_activity_ttl + 1)).unwrap(),
+            users.min_activity_ttl + 1
+        );
+
+        let err = activity_ttl(users, Some(0)).unwrap_err();
+        assert!(matches!(err.error_type, ErrorType::FailedValidation { .. }));
+    }
+
+    #[rocket::async_test]
+    async fn short_hints_keep_longer_channels_open() {
+        use redis_kiss::AsyncCommands;
+        use revolt_database::util::{activity::open_channels_key, retry::redis_connection};
+
+        let harness = TestHarness::new().await;
+        let (_, session, user) = harness.new_user().await;
+        let (_, long) = harness.new_server(&user).await;
+        let (_, short) = harness.new_server(&user).await;
+
+        let config = revolt_config::config().await;
+        let users = &config.api.users;
+
+        for (channel, ttl_hint) in [
+            (&long[0], users.max_activity_ttl),
+            (&short[0], users.min_activity_ttl),
+        ] {
+            let response = harness
+                .client
+                .put(format!("/channels/{}", channel.id()))
+                .header(Header::new("x-session-token", session.token.to_string()))
+                .header(ContentType::JSON)
+                .body(json!({ "type": "open", "ttl_hint": ttl_hint }).to_string())
+                .dispatch()
+                .await;
+
+            assert_eq!(response.status(), Status::NoContent);
+        }
+
+        let mut conn = redis_connection().await.unwrap();
+        let ttl: i64 = conn
+            .ttl(open_channels_key(&user.id, &session.id))
+            .await
+            .unwrap();
+
+        assert!(ttl > users.min_activity_ttl as i64);
+    }
+
+    #[rocket::async_test]
+    async fn rejects_non_channel_targets() {
+        let harness = TestHarness::new().await;
+        let (_, session, user) = harness.new_user().await;
+        let (server, _) = harness.new_server(&user).await;
+
+        for (target, status, error) in [
+            (server.id, Status::BadRequest, "InvalidOperation"),
+            (
+                ulid::Ulid::new().to_string(),
+                Status::NotFound,
+                "UnknownChannel",
+            ),
+        ] {
+            let response = harness
+                .client
+                .put(format!("/channels/{target}"))
+                .header(Header::new("x-session-token", session.token.to_string()))
+                .header(ContentType::JSON)
+                .body(json!({ "type": "open" }).to_string())
+                .dispatch()
+                .await;
+
+            assert_eq!(response.status(), status);
+
+            let body: serde_json::Value = response.into_json().await.expect("error");
+            assert_eq!(body["type"], error);
+        }
+    }
+
+    #[rocket::async_test]
+    async fn closing_marks_the_channel_read() {
+        let harness = TestHarness::new().await;
+        let (_, session, user) = harness.new_user().await;
+        let (server, channels) = harness.new_server(&user).await;
+        let (channel, _, message) = harness.new_message(&user, &server, channels).await;
+
+        let response = harness
+            .client
+            .put(format!("/channels/{}", channel.id()))
+            .header(Header::new("x-session-token", session.token.to_string()))
+            .header(ContentType::JSON)
+            .body(json!({ "type": "close", "mark_read_message_id": message.id }).to_string())
+            .dispatch()
+            .await;
+
+        assert_eq!(response.status(), Status::NoContent);
+
+        let unread = harness
+            .db
+            .fetch_unread(&user.id, channel.id())
+            .await
+            .unwrap()
+            .expect("channel should be acknowledged");
+        assert_eq!(unread.last_id, Some(message.id));
+    }
+
+    #[rocket::async_test]
+    async fn rejects_unknown_presence() {
+        let harness = TestHarness::new().await;
+        let (_, session, user) = harness.new_user().await;
+        let (_, channels) = harness.new_server(&user).await;
+
+        let response = harness
+            .client
+            .put(format!("/channels/{}/activity", channels[0].id()))
+            .header(Header::new("x-session-token", session.token.to_string()))
+            .header(ContentType::JSON)
+            .body(json!({ "type": "open", "presence": "Sleeping" }).to_string())
+            .dispatch()
+            .await;
+
+        assert_eq!(response.status(), Status::UnprocessableEntity);
+    }
+}
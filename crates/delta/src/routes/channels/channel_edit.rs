@@ -37,6 +37,7 @@ pub async fn edit(
         && data.description.is_none()
         && data.icon.is_none()
         && data.nsfw.is_none()
+        && data.unmask_spoilers.is_none()
         && data.owner.is_none()
         && data.remove.is_empty()
     {
@@ -87,6 +88,18 @@ pub async fn edit(
         .ok();
     }
 
+    if let Some(new_unmask_spoilers) = data.unmask_spoilers {
+        let Channel::TextChannel {
+            unmask_spoilers, ..
+        } = &mut channel
+        else {
+            return Err(create_error!(InvalidOperation));
+        };
+
+        *unmask_spoilers = new_unmask_spoilers;
+        partial.unmask_spoilers = Some(new_unmask_spoilers);
+    }
+
     match &mut channel {
         Channel::Group {
             id,
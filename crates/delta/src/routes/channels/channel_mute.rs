@@ -0,0 +1,55 @@
+use revolt_database::{
+    util::{permissions::DatabasePermissionQuery, reference::Reference},
+    Database, User,
+};
+use revolt_models::v0;
+use revolt_permissions::{calculate_channel_permissions, ChannelPermission};
+use revolt_result::{create_error, Result};
+use rocket::{serde::json::Json, State};
+use rocket_empty::EmptyResponse;
+
+/// # Mute Channel
+///
+/// Stop receiving push notifications from a channel, optionally until a given time.
+#[openapi(tag = "Channel Information")]
+#[put("/<target>/mute", data = "<data>")]
+pub async fn mute(
+    db: &State<Database>,
+    user: User,
+    target: Reference<'_>,
+    data: Json<v0::DataMuteChannel>,
+) -> Result<EmptyResponse> {
+    if user.bot.is_some() {
+        return Err(create_error!(IsBot));
+    }
+
+    let channel = target.as_channel(db).await?;
+    let mut query = DatabasePermissionQuery::new(db, &user).channel(&channel);
+    calculate_channel_permissions(&mut query)
+        .await
+        .throw_if_lacking_channel_permission(ChannelPermission::ViewChannel)?;
+
+    db.mute_channel(channel.id(), &user.id, data.into_inner().until)
+        .await
+        .map(|_| EmptyResponse)
+}
+
+/// # Unmute Channel
+///
+/// Resume receiving push notifications from a channel.
+#[openapi(tag = "Channel Information")]
+#[delete("/<target>/mute")]
+pub async fn unmute(
+    db: &State<Database>,
+    user: User,
+    target: Reference<'_>,
+) -> Result<EmptyResponse> {
+    if user.bot.is_some() {
+        return Err(create_error!(IsBot));
+    }
+
+    let channel = target.as_channel(db).await?;
+    db.unmute_channel(channel.id(), &user.id)
+        .await
+        .map(|_| EmptyResponse)
+}
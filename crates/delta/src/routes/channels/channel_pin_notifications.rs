@@ -0,0 +1,53 @@
+use revolt_database::{
+    util::{permissions::DatabasePermissionQuery, reference::Reference},
+    Database, User,
+};
+use revolt_permissions::{calculate_channel_permissions, ChannelPermission};
+use revolt_result::{create_error, Result};
+use rocket::State;
+use rocket_empty::EmptyResponse;
+
+/// # Enable Pin Notifications
+///
+/// Receive push notifications when messages are pinned in a channel.
+#[openapi(tag = "Channel Information")]
+#[put("/<target>/pin-notifications")]
+pub async fn enable(
+    db: &State<Database>,
+    user: User,
+    target: Reference<'_>,
+) -> Result<EmptyResponse> {
+    if user.bot.is_some() {
+        return Err(create_error!(IsBot));
+    }
+
+    let channel = target.as_channel(db).await?;
+    let mut query = DatabasePermissionQuery::new(db, &user).channel(&channel);
+    calculate_channel_permissions(&mut query)
+        .await
+        .throw_if_lacking_channel_permission(ChannelPermission::ViewChannel)?;
+
+    db.set_pin_notifications(channel.id(), &user.id, true)
+        .await
+        .map(|_| EmptyResponse)
+}
+
+/// # Disable Pin Notifications
+///
+/// Stop receiving push notifications when messages are pinned in a channel.
+#[openapi(tag = "Channel Information")]
+#[delete("/<target>/pin-notifications")]
+pub async fn disable(
+    db: &State<Database>,
+    user: User,
+    target: Reference<'_>,
+) -> Result<EmptyResponse> {
+    if user.bot.is_some() {
+        return Err(create_error!(IsBot));
+    }
+
+    let channel = target.as_channel(db).await?;
+    db.set_pin_notifications(channel.id(), &user.id, false)
+        .await
+        .map(|_| EmptyResponse)
+}
@@ -0,0 +1,72 @@
+use revolt_database::{
+    events::client::EventV1,
+    util::{permissions::DatabasePermissionQuery, reference::Reference},
+    Database, User, AMQP,
+};
+use revolt_models::v0;
+use revolt_permissions::{calculate_channel_permissions, ChannelPermission};
+use revolt_result::{create_error, Result};
+use rocket::{serde::json::Json, State};
+
+/// # Reconcile Read State
+///
+/// Bring the server's read state in line with the last message the client has seen.
+///
+/// Useful after reconnecting, when earlier acknowledgements may have been missed.
+#[openapi(tag = "Messaging")]
+#[post("/<target>/reconcile-read", data = "<data>")]
+pub async fn reconcile_read(
+    db: &State<Database>,
+    amqp: &State<AMQP>,
+    user: User,
+    target: Reference<'_>,
+    data: Json<v0::DataReconcileRead>,
+) -> Result<Json<v0::ReconcileReadResponse>> {
+    if user.bot.is_some() {
+        return Err(create_error!(IsBot));
+    }
+
+    let channel = target.as_channel(db).await?;
+    let mut query = DatabasePermissionQuery::new(db, &user).channel(&channel);
+    calculate_channel_permissions(&mut query)
+        .await
+        .throw_if_lacking_channel_permission(ChannelPermission::ReadMessageHistory)?;
+
+    let message = Reference::from_unchecked(&data.last_seen)
+        .as_message(db)
+        .await?;
+
+    if message.channel != channel.id() {
+        return Err(create_error!(NotFound));
+    }
+
+    // Message ids are ULIDs, so they order chronologically
+    let unread = db.fetch_unread(&user.id, channel.id()).await?;
+    let behind = unread
+        .and_then(|unread| unread.last_id)
+        .map_or(true, |last_id| last_id < message.id);
+
+    if !behind {
+        return Ok(Json(v0::ReconcileReadResponse { updated: false }));
+    }
+
+    db.acknowledge_message(channel.id(), &user.id, &message.id)
+        .await?;
+
+    EventV1::ChannelAck {
+        id: channel.id().to_string(),
+        user: user.id.clone(),
+        message_id: message.id.clone(),
+    }
+    .private(user.id.clone())
+    .await;
+
+    if let Err(err) = amqp
+        .ack_message(user.id, channel.id().to_string(), message.id)
+        .await
+    {
+        revolt_config::capture_error(&err);
+    }
+
+    Ok(Json(v0::ReconcileReadResponse { updated: true }))
+}
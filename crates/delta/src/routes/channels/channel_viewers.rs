@@ -0,0 +1,71 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use revolt_database::{
+    util::{
+        activity::channel_viewers_key, permissions::DatabasePermissionQuery, reference::Reference,
+        retry::redis_connection,
+    },
+    Database, User,
+};
+use revolt_permissions::{calculate_channel_permissions, ChannelPermission};
+use revolt_result::{create_error, Result};
+use rocket::{serde::json::Json, State};
+
+/// Maximum number of viewer entries read from the index
+const MAX_VIEWER_ENTRIES: isize = 500;
+
+/// Maximum number of users returned
+const MAX_VIEWERS: usize = 100;
+
+/// # Fetch Channel Viewers
+///
+/// Fetch the ids of users who currently have this channel open.
+///
+/// Requires the `ManageMessages` permission, at most 100 users are returned.
+#[openapi(tag = "Channel Information")]
+#[get("/<target>/viewers")]
+pub async fn fetch_viewers(
+    db: &State<Database>,
+    user: User,
+    target: Reference<'_>,
+) -> Result<Json<Vec<String>>> {
+    let channel = target.as_channel(db).await?;
+    let mut query = DatabasePermissionQuery::new(db, &user).channel(&channel);
+    calculate_channel_permissions(&mut query)
+        .await
+        .throw_if_lacking_channel_permission(ChannelPermission::ManageMessages)?;
+
+    use redis_kiss::AsyncCommands;
+
+    let mut conn = redis_connection().await?;
+
+    let key = channel_viewers_key(channel.id());
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("Time went backwards")
+        .as_secs();
+
+    // Drop sessions whose activity has lapsed
+    let _: () = conn
+        .zrembyscore(&key, "-inf", now)
+        .await
+        .map_err(|_| create_error!(InternalError))?;
+
+    let entries: Vec<String> = conn
+        .zrangebyscore_limit(&key, now, "+inf", 0, MAX_VIEWER_ENTRIES)
+        .await
+        .map_err(|_| create_error!(InternalError))?;
+
+    // Collapse sessions into distinct users
+    let mut viewers: Vec<String> = vec![];
+    for entry in entries {
+        if let Some((user_id, _)) = entry.split_once(':') {
+            if !viewers.iter().any(|id| id == user_id) {
+                viewers.push(user_id.to_string());
+            }
+        }
+    }
+
+    viewers.truncate(MAX_VIEWERS);
+    Ok(Json(viewers))
+}
@@ -1,8 +1,8 @@
 use revolt_database::{
     util::{permissions::DatabasePermissionQuery, reference::Reference},
-    Channel, Database, PartialMessage, SystemMessage, User, AMQP,
+    Channel, Database, Message, PartialMessage, SystemMessage, User, AMQP,
 };
-use revolt_models::v0::MessageAuthor;
+use revolt_models::v0::{self, MessageAuthor};
 use revolt_permissions::{calculate_channel_permissions, ChannelPermission};
 use revolt_result::{create_error, Result};
 use rocket::State;
@@ -65,9 +65,71 @@ pub async fn message_pin(
     )
     .await?;
 
+    notify_pin_subscribers(db, amqp, &user, &channel, message).await;
+
     Ok(EmptyResponse)
 }
 
+/// Let users who opted in know that a message was pinned
+async fn notify_pin_subscribers(
+    db: &Database,
+    amqp: &AMQP,
+    pinner: &User,
+    channel: &Channel,
+    message: Message,
+) {
+    let subscribers = match db.fetch_pin_notification_subscribers(channel.id()).await {
+        Ok(subscribers) => subscribers,
+        Err(err) => {
+            revolt_config::capture_error(&err);
+            return;
+        }
+    };
+
+    let mut recipients = vec![];
+    for subscriber in subscribers {
+        if subscriber == pinner.id {
+            continue;
+        }
+
+        let Ok(user) = db.fetch_user(&subscriber).await else {
+            continue;
+        };
+
+        let mut query = DatabasePermissionQuery::new(db, &user).channel(channel);
+        if calculate_channel_permissions(&mut query)
+            .await
+            .has_channel_permission(ChannelPermission::ViewChannel)
+        {
+            recipients.push(subscriber);
+        }
+    }
+
+    if recipients.is_empty() {
+        return;
+    }
+
+    let author = match db.fetch_user(&message.author).await {
+        Ok(author) => Some(author.into(db, Some(pinner)).await),
+        Err(_) => None,
+    };
+
+    let notification = v0::PushNotification::from(
+        message.into_model(None, None),
+        author.as_ref().map(MessageAuthor::User),
+        channel.clone().into(),
+        None,
+    )
+    .await;
+
+    if let Err(err) = amqp
+        .message_pinned(db, &pinner.username, notification, recipients)
+        .await
+    {
+        revolt_config::capture_error(&err);
+    }
+}
+
 #[cfg(test)]
 mod test {
     use crate::{rocket, util::test::TestHarness};
@@ -1,7 +1,8 @@
 use chrono::{Duration, Utc};
 use revolt_database::util::permissions::DatabasePermissionQuery;
 use revolt_database::{
-    util::idempotency::IdempotencyKey, util::reference::Reference, Database, User,
+    util::idempotency::IdempotencyKey, util::reference::Reference, util::trace::TraceId, Database,
+    User,
 };
 use revolt_database::{Interactions, Message, AMQP};
 use revolt_models::v0;
@@ -24,6 +25,7 @@ pub async fn message_send(
     target: Reference<'_>,
     data: Json<v0::DataMessageSend>,
     idempotency: IdempotencyKey,
+    trace_id: TraceId,
 ) -> Result<Json<v0::Message>> {
     let data = data.into_inner();
     data.validate().map_err(|error| {
@@ -92,10 +94,12 @@ pub async fn message_send(
         .as_ref()
         .map(|member| member.clone().into_owned().into());
 
+    let amqp = amqp.traced(trace_id.into_string());
+
     Ok(Json(
         Message::create_from_api(
             db,
-            Some(amqp),
+            Some(&amqp),
             channel,
             data,
             v0::MessageAuthor::User(&author),
@@ -185,6 +189,7 @@ mod test {
             description: None,
             icon: None,
             nsfw: None,
+            unmask_spoilers: None,
             active: None,
             permissions: None,
             role_permissions: Some(overrides),
@@ -1,12 +1,18 @@
 use revolt_rocket_okapi::revolt_okapi::openapi3::OpenApi;
 use rocket::Route;
 
-mod attachment_query;
+pub(crate) mod attachment_query;
+mod attachment_usage;
 mod channel_ack;
-mod channel_activity;
+pub(crate) mod channel_activity;
+mod channel_always_notify;
 mod channel_delete;
 mod channel_edit;
 mod channel_fetch;
+mod channel_mute;
+mod channel_pin_notifications;
+mod channel_reconcile_read;
+mod channel_viewers;
 mod group_add_member;
 mod group_create;
 mod group_remove_member;
@@ -33,9 +39,20 @@ mod webhook_fetch_all;
 pub fn routes() -> (Vec<Route>, OpenApi) {
     openapi_get_routes_spec![
         attachment_query::query,
+        attachment_usage::usage,
         channel_ack::ack,
         channel_activity::update_activity,
+        channel_activity::update_activity_presence,
+        channel_activity::fetch_open_sessions,
+        channel_always_notify::enable,
+        channel_always_notify::disable,
         channel_fetch::fetch,
+        channel_mute::mute,
+        channel_mute::unmute,
+        channel_pin_notifications::enable,
+        channel_pin_notifications::disable,
+        channel_reconcile_read::reconcile_read,
+        channel_viewers::fetch_viewers,
         members_fetch::fetch_members,
         channel_delete::delete,
         channel_edit::edit,
@@ -1,5 +1,8 @@
-use revolt_database::{util::reference::Reference, Channel, Database, Invite, Member, User, AMQP};
+use revolt_database::{
+    util::reference::Reference, Channel, Database, Invite, Member, Server, User, AMQP,
+};
 use revolt_models::v0::{self, InviteJoinResponse};
+use revolt_permissions::ChannelPermission;
 use revolt_result::{create_error, Result};
 use rocket::{serde::json::Json, State};
 
@@ -25,6 +28,7 @@ pub async fn join(
         Invite::Server { server, .. } => {
             let server = db.fetch_server(server).await?;
             let (_, channels) = Member::create(db, &server, &user, None).await?;
+            notify_staff(db, amqp, &server, &user).await;
 
             Ok(Json(InviteJoinResponse::Server {
                 channels: channels.into_iter().map(|c| c.into()).collect(),
@@ -47,3 +51,33 @@ pub async fn join(
         }
     }
 }
+
+/// Let staff of servers which opted in know that someone joined
+async fn notify_staff(db: &Database, amqp: &AMQP, server: &Server, user: &User) {
+    if !server.notify_member_joins {
+        return;
+    }
+
+    let config = revolt_config::config().await;
+    let Ok(permission) = serde_json::from_value::<ChannelPermission>(serde_json::Value::String(
+        config.pushd.member_join_permission.clone(),
+    )) else {
+        log::warn!(
+            "Invalid pushd.member_join_permission: {}",
+            config.pushd.member_join_permission
+        );
+        return;
+    };
+
+    let staff = match server.fetch_staff_ids(db, permission).await {
+        Ok(staff) => staff,
+        Err(err) => {
+            revolt_config::capture_error(&err);
+            return;
+        }
+    };
+
+    if let Err(err) = amqp.member_joined(db, server, user, staff).await {
+        revolt_config::capture_error(&err);
+    }
+}
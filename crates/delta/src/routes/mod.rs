@@ -4,6 +4,9 @@ pub use rocket::http::Status;
 pub use rocket::response::Redirect;
 use rocket::{Build, Rocket};
 
+mod activity;
+mod attachments;
+mod bootstrap;
 mod bots;
 mod channels;
 mod customisation;
@@ -25,8 +28,10 @@ pub fn mount(config: Settings, mut rocket: Rocket<Build>) -> Rocket<Build> {
         mount_endpoints_and_merged_docs! {
             rocket, "/".to_owned(), settings,
             "/" => (vec![], custom_openapi_spec()),
-            "" => openapi_get_routes_spec![root::root],
+            "" => openapi_get_routes_spec![root::root, bootstrap::bootstrap],
             "/users" => users::routes(),
+            "/activity" => activity::routes(),
+            "/attachments" => attachments::routes(),
             "/bots" => bots::routes(),
             "/channels" => channels::routes(),
             "/servers" => servers::routes(),
@@ -46,8 +51,10 @@ pub fn mount(config: Settings, mut rocket: Rocket<Build>) -> Rocket<Build> {
         mount_endpoints_and_merged_docs! {
             rocket, "/".to_owned(), settings,
             "/" => (vec![], custom_openapi_spec()),
-            "" => openapi_get_routes_spec![root::root],
+            "" => openapi_get_routes_spec![root::root, bootstrap::bootstrap],
             "/users" => users::routes(),
+            "/activity" => activity::routes(),
+            "/attachments" => attachments::routes(),
             "/bots" => bots::routes(),
             "/channels" => channels::routes(),
             "/servers" => servers::routes(),
@@ -68,8 +75,10 @@ pub fn mount(config: Settings, mut rocket: Rocket<Build>) -> Rocket<Build> {
         mount_endpoints_and_merged_docs! {
             rocket, "/0.8".to_owned(), settings,
             "/" => (vec![], custom_openapi_spec()),
-            "" => openapi_get_routes_spec![root::root],
+            "" => openapi_get_routes_spec![root::root, bootstrap::bootstrap],
             "/users" => users::routes(),
+            "/activity" => activity::routes(),
+            "/attachments" => attachments::routes(),
             "/bots" => bots::routes(),
             "/channels" => channels::routes(),
             "/servers" => servers::routes(),
@@ -88,8 +97,10 @@ pub fn mount(config: Settings, mut rocket: Rocket<Build>) -> Rocket<Build> {
         mount_endpoints_and_merged_docs! {
             rocket, "/0.8".to_owned(), settings,
             "/" => (vec![], custom_openapi_spec()),
-            "" => openapi_get_routes_spec![root::root],
+            "" => openapi_get_routes_spec![root::root, bootstrap::bootstrap],
             "/users" => users::routes(),
+            "/activity" => activity::routes(),
+            "/attachments" => attachments::routes(),
             "/bots" => bots::routes(),
             "/channels" => channels::routes(),
             "/servers" => servers::routes(),
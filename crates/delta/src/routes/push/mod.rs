@@ -1,9 +1,84 @@
+use authifier::models::WebPushSubscription;
+use iso8601_timestamp::Timestamp;
+use revolt_database::events::client::EventV1;
 use revolt_rocket_okapi::revolt_okapi::openapi3::OpenApi;
 use rocket::Route;
 
+mod preview;
+mod settings;
 mod subscribe;
 mod unsubscribe;
+mod unsubscribe_all;
 
 pub fn routes() -> (Vec<Route>, OpenApi) {
-    openapi_get_routes_spec![subscribe::subscribe, unsubscribe::unsubscribe]
+    openapi_get_routes_spec![
+        subscribe::subscribe,
+        unsubscribe::unsubscribe,
+        unsubscribe_all::unsubscribe_all,
+        preview::preview,
+        settings::settings
+    ]
+}
+
+/// Platform a push subscription delivers to
+fn subscription_platform(endpoint: &str) -> &'static str {
+    match endpoint {
+        "apn" => "apn",
+        "fcm" => "fcm",
+        _ => "web",
+    }
+}
+
+/// Record a push subscription being added to or removed from a session
+///
+/// The change is logged and sent to the user's other sessions, so that an
+/// unexpected subscription on an account can be noticed. Sessions which lost
+/// their subscription to `replaced_by` can prompt to re-enable notifications.
+async fn audit_subscription(
+    user_id: &str,
+    session_id: &str,
+    subscription: &WebPushSubscription,
+    subscribed: bool,
+    replaced_by: Option<&str>,
+) {
+    let platform = subscription_platform(&subscription.endpoint);
+
+    log::info!(
+        "Push subscription {} for user {} on session {} ({platform}: {})",
+        match (subscribed, replaced_by) {
+            (true, _) => "created".to_string(),
+            (false, Some(replaced_by)) => format!("replaced by session {replaced_by}"),
+            (false, None) => "removed".to_string(),
+        },
+        user_id,
+        session_id,
+        subscription.endpoint
+    );
+
+    EventV1::PushSubscriptionUpdate {
+        user_id: user_id.to_string(),
+        session_id: session_id.to_string(),
+        subscribed,
+        endpoint: subscription.endpoint.clone(),
+        platform: platform.to_string(),
+        timestamp: Timestamp::now_utc(),
+        replaced_by: replaced_by.map(str::to_string),
+    }
+    .private(user_id.to_string())
+    .await;
+}
+
+#[cfg(test)]
+mod test {
+    use super::subscription_platform;
+
+    #[test]
+    fn identifies_subscription_platform() {
+        assert_eq!(subscription_platform("apn"), "apn");
+        assert_eq!(subscription_platform("fcm"), "fcm");
+        assert_eq!(
+            subscription_platform("https://updates.push.services.mozilla.com/wpush/v2/abc"),
+            "web"
+        );
+    }
 }
@@ -0,0 +1,86 @@
+use revolt_database::{
+    format,
+    util::{permissions::DatabasePermissionQuery, reference::Reference},
+    Channel, Database, Message, User,
+};
+use revolt_models::v0;
+use revolt_permissions::{calculate_channel_permissions, ChannelPermission};
+use revolt_result::{create_error, Result};
+use rocket::{serde::json::Json, State};
+use ulid::Ulid;
+
+/// # Preview Push Notification
+///
+/// Render a message the same way it would be delivered as a push notification.
+///
+/// Short messages get the same generic body as they would when sent, and messages
+/// which wouldn't be notified at all are reported as `suppressed`. Nothing is sent or stored.
+#[openapi(tag = "Web Push")]
+#[post("/preview", data = "<data>")]
+pub async fn preview(
+    db: &State<Database>,
+    user: User,
+    data: Json<v0::DataPushPreview>,
+) -> Result<Json<v0::PushPreview>> {
+    let v0::DataPushPreview {
+        channel,
+        content,
+        attachments,
+    } = data.into_inner();
+
+    let channel = Reference::from_unchecked(&channel).as_channel(db).await?;
+    let mut query = DatabasePermissionQuery::new(db, &user).channel(&channel);
+    calculate_channel_permissions(&mut query)
+        .await
+        .throw_if_lacking_channel_permission(ChannelPermission::ViewChannel)?;
+
+    let mut files = vec![];
+    for id in attachments.unwrap_or_default() {
+        let file = db.fetch_attachment("attachments", &id).await?;
+        if file.uploader_id.as_ref() != Some(&user.id) {
+            return Err(create_error!(NotFound));
+        }
+
+        files.push(file);
+    }
+
+    let message = Message {
+        id: Ulid::new().to_string(),
+        channel: channel.id().to_string(),
+        author: user.id.clone(),
+        content,
+        attachments: if files.is_empty() { None } else { Some(files) },
+        ..Default::default()
+    };
+
+    let server = match &channel {
+        Channel::TextChannel { server, .. } | Channel::VoiceChannel { server, .. } => {
+            db.fetch_server(server).await.ok().map(|server| server.name)
+        }
+        _ => None,
+    };
+
+    let author: v0::User = user.clone().into(db, Some(&user)).await;
+    let mut notification = v0::PushNotification::from(
+        message.into_model(None, None),
+        Some(v0::MessageAuthor::User(&author)),
+        channel.into(),
+        server,
+    )
+    .await;
+
+    let config = revolt_config::config().await;
+    let rule = format::apply_body_rule(
+        &mut notification,
+        config.pushd.min_body_length,
+        config.pushd.suppress_attachment_only,
+    );
+    format::format_notification(db, &mut notification, config.pushd.max_body_length).await;
+
+    Ok(Json(v0::PushPreview {
+        title: format::notification_title(&notification),
+        body: notification.body,
+        image: notification.image,
+        suppressed: rule == format::BodyRule::Suppress,
+    }))
+}
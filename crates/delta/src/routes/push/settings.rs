@@ -0,0 +1,240 @@
+use authifier::models::Session;
+use revolt_database::{
+    iso8601_timestamp::Timestamp, ChannelNotificationSettings, Database, PushSubscriptionSession,
+    User,
+};
+use revolt_result::Result;
+use revolt_rocket_okapi::revolt_okapi::schemars::JsonSchema;
+use rocket::{serde::json::Json, State};
+use serde::Serialize;
+
+use super::subscription_platform;
+
+/// Notification settings of a channel which differ from the defaults
+#[derive(Serialize, JsonSchema)]
+pub struct ChannelNotificationOverrides {
+    /// Channel id
+    pub channel: String,
+    /// Whether the channel is currently muted
+    pub muted: bool,
+    /// Time at which the mute lifts, indefinite if not present
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub muted_until: Option<Timestamp>,
+    /// Whether you are notified when messages are pinned
+    pub notify_pins: bool,
+    /// Whether you are notified of every message, even while viewing the channel
+    pub always_notify: bool,
+}
+
+/// Push subscription held by one of your sessions
+#[derive(Serialize, JsonSchema)]
+pub struct PushSubscriptionSummary {
+    /// Session id
+    pub session_id: String,
+    /// Platform the subscription delivers to: `web`, `fcm` or `apn`
+    pub platform: String,
+    /// Push service endpoint, only meaningful for Web Push
+    pub endpoint: String,
+    /// Whether this is the session making the request
+    pub current: bool,
+    /// Whether notifications to this session hide message content
+    pub redacted: bool,
+}
+
+/// Notification settings in effect for a user
+#[derive(Serialize, JsonSchema)]
+pub struct PushSettingsResponse {
+    /// Time until which all notifications are snoozed, not present unless currently snoozed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub snoozed_until: Option<Timestamp>,
+    /// Whether only your most recently active session is notified
+    pub primary_session_only: bool,
+    /// Channels with notification settings which differ from the defaults
+    pub channels: Vec<ChannelNotificationOverrides>,
+    /// Sessions subscribed to push notifications
+    pub subscriptions: Vec<PushSubscriptionSummary>,
+}
+
+/// # Fetch Push Settings
+///
+/// Fetch the notification settings which currently decide whether you are notified.
+///
+/// Expired snoozes and mutes are left out. Subscription keys are never included.
+#[openapi(tag = "Web Push")]
+#[get("/settings")]
+pub async fn settings(
+    db: &State<Database>,
+    user: User,
+    session: Session,
+) -> Result<Json<PushSettingsResponse>> {
+    let user_ids = [user.id.clone()];
+    let channels = db
+        .fetch_user_channel_notification_settings(&user.id)
+        .await?;
+    let sessions = db.fetch_push_subscribed_sessions(&user_ids).await?;
+    let redacted = db.fetch_redacted_push_session_ids(&user_ids).await?;
+
+    Ok(Json(PushSettingsResponse {
+        snoozed_until: user
+            .notifications_snoozed_until
+            .filter(|until| *until > Timestamp::now_utc()),
+        primary_session_only: user.push_primary_session_only,
+        channels: channel_overrides(channels),
+        subscriptions: subscription_summaries(sessions, &session.id, &redacted),
+    }))
+}
+
+/// Describe the channel settings which currently have an effect
+fn channel_overrides(
+    settings: Vec<ChannelNotificationSettings>,
+) -> Vec<ChannelNotificationOverrides> {
+    settings
+        .into_iter()
+        .filter(|settings| !settings.is_default())
+        .map(|settings| {
+            let muted = settings.is_muted();
+            ChannelNotificationOverrides {
+                muted,
+                muted_until: settings.muted_until.filter(|_| muted),
+                notify_pins: settings.notify_pins,
+                always_notify: settings.always_notify,
+                channel: settings.id.channel,
+            }
+        })
+        .collect()
+}
+
+/// Describe subscribed sessions without exposing their keys
+fn subscription_summaries(
+    sessions: Vec<PushSubscriptionSession>,
+    current: &str,
+    redacted: &[String],
+) -> Vec<PushSubscriptionSummary> {
+    sessions
+        .into_iter()
+        .filter_map(|session| {
+            let subscription = session.subscription?;
+            Some(PushSubscriptionSummary {
+                platform: subscription_platform(&subscription.endpoint).to_string(),
+                endpoint: subscription.endpoint,
+                current: session.id == current,
+                redacted: redacted.contains(&session.id),
+                session_id: session.id,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use authifier::models::WebPushSubscription;
+    use revolt_database::{
+        iso8601_timestamp::{Duration, Timestamp},
+        ChannelCompositeKey, ChannelNotificationSettings, PushSubscriptionSession,
+    };
+    use rocket::http::{Header, Status};
+
+    use super::{channel_overrides, subscription_summaries};
+    use crate::{rocket, util::test::TestHarness};
+
+    #[test]
+    fn leaves_out_settings_without_effect() {
+        let settings = |channel: &str, muted_until: Option<Timestamp>, notify_pins: bool| {
+            ChannelNotificationSettings {
+                id: ChannelCompositeKey {
+                    channel: channel.to_string(),
+                    user: "user".to_string(),
+                },
+                muted: muted_until.is_some(),
+                muted_until,
+                notify_pins,
+                always_notify: false,
+            }
+        };
+
+        let now = Timestamp::now_utc();
+        let overrides = channel_overrides(vec![
+            settings("expired", now.checked_sub(Duration::hours(1)), false),
+            settings("muted", now.checked_add(Duration::hours(1)), false),
+            settings("pins", now.checked_sub(Duration::hours(1)), true),
+        ]);
+
+        let summary: Vec<(&str, bool, bool)> = overrides
+            .iter()
+            .map(|o| (o.channel.as_str(), o.muted, o.muted_until.is_some()))
+            .collect();
+        assert_eq!(summary, vec![("muted", true, true), ("pins", false, false)]);
+    }
+
+    #[test]
+    fn summarises_subscriptions_without_keys() {
+        let session = |id: &str, endpoint: Option<&str>| PushSubscriptionSession {
+            id: id.to_string(),
+            user_id: "user".to_string(),
+            subscription: endpoint.map(|endpoint| WebPushSubscription {
+                endpoint: endpoint.to_string(),
+                p256dh: "key".to_string(),
+                auth: "secret".to_string(),
+            }),
+        };
+
+        let summaries = subscription_summaries(
+            vec![
+                session("phone", Some("fcm")),
+                session("browser", Some("https://push.example.com/abc")),
+                session("unsubscribed", None),
+            ],
+            "browser",
+            &["phone".to_string()],
+        );
+
+        let summary: Vec<(&str, &str, bool, bool)> = summaries
+            .iter()
+            .map(|s| {
+                (
+                    s.session_id.as_str(),
+                    s.platform.as_str(),
+                    s.current,
+                    s.redacted,
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("phone", "fcm", false, true),
+                ("browser", "web", true, false)
+            ]
+        );
+
+        let json = serde_json::to_string(&summaries).unwrap();
+        assert!(!json.contains("secret") && !json.contains("key"));
+    }
+
+    #[rocket::async_test]
+    async fn reports_muted_channels() {
+        let harness = TestHarness::new().await;
+        let (_, session, user) = harness.new_user().await;
+        let (_, channels) = harness.new_server(&user).await;
+
+        harness
+            .db
+            .mute_channel(channels[0].id(), &user.id, None)
+            .await
+            .unwrap();
+
+        let response = harness
+            .client
+            .get("/push/settings")
+            .header(Header::new("x-session-token", session.token.to_string()))
+            .dispatch()
+            .await;
+
+        assert_eq!(response.status(), Status::Ok);
+
+        let body: serde_json::Value = response.into_json().await.expect("settings");
+        assert_eq!(body["channels"][0]["channel"], channels[0].id());
+        assert_eq!(body["channels"][0]["muted"], true);
+        assert!(body.get("snoozed_until").is_none());
+    }
+}
@@ -1,43 +1,114 @@
+use std::time::Duration;
+
 use authifier::{
     models::{Session, WebPushSubscription},
     Authifier,
 };
-use revolt_database::Database;
+use revolt_database::{
+    iso8601_timestamp::{Duration as TimestampDuration, Timestamp},
+    metrics,
+    util::retry::retry_transient,
+    Database,
+};
 use revolt_result::{create_database_error, Result};
+use revolt_rocket_okapi::revolt_okapi::schemars::JsonSchema;
 use rocket::{serde::json::Json, State};
 use rocket_empty::EmptyResponse;
+use serde::Deserialize;
+
+use super::audit_subscription;
+
+/// Number of attempts made at removing duplicate push subscriptions
+const DEDUP_ATTEMPTS: usize = 3;
+
+/// Base delay between attempts at removing duplicate push subscriptions
+const DEDUP_BACKOFF: Duration = Duration::from_millis(50);
+
+/// Push subscription to create
+#[derive(Deserialize, JsonSchema)]
+pub struct DataPushSubscribe {
+    /// Web Push subscription details
+    #[serde(flatten)]
+    pub subscription: WebPushSubscription,
+    /// Whether notifications sent to this session should hide message content
+    #[serde(default)]
+    pub redacted: bool,
+    /// When the subscription expires, in milliseconds since the Unix epoch
+    #[serde(default, rename = "expirationTime")]
+    pub expiration_time: Option<u64>,
+}
 
 /// # Push Subscribe
 ///
 /// Create a new Web Push subscription.
 ///
 /// If an existing subscription exists on this session, it will be removed.
-/// Also removes subscriptions from other sessions with the same FCM token.
+/// Also removes the same subscription from the user's other sessions, so a device
+/// which re-subscribes with rotated Web Push keys replaces its old subscription.
+/// Those sessions are sent a `PushSubscriptionUpdate` event with `replaced_by` set.
+///
+/// Subscriptions which give an `expirationTime` are pruned once it has passed.
 #[openapi(tag = "Web Push")]
 #[post("/subscribe", data = "<data>")]
 pub async fn subscribe(
     authifier: &State<Authifier>,
     db: &State<Database>,
     mut session: Session,
-    data: Json<WebPushSubscription>,
+    data: Json<DataPushSubscribe>,
 ) -> Result<EmptyResponse> {
-    let new_subscription = data.into_inner();
-
-    // If this is an FCM subscription, remove the same token from other sessions
-    if new_subscription.endpoint == "fcm" {
-        if let Err(err) = db
-            .remove_duplicate_fcm_subscriptions(&session.user_id, &new_subscription.auth)
-            .await
-        {
+    let DataPushSubscribe {
+        subscription: new_subscription,
+        redacted,
+        expiration_time,
+    } = data.into_inner();
+
+    // Remove the same device from other sessions
+    let replaced = match retry_transient(DEDUP_ATTEMPTS, DEDUP_BACKOFF, || {
+        db.remove_duplicate_push_subscriptions(&session.user_id, &session.id, &new_subscription)
+    })
+    .await
+    {
+        Ok(affected) => affected,
+        Err(err) => {
+            metrics::record_push_dedup_failure();
             revolt_config::capture_error(&err);
             // Don't fail, just log the error
+            vec![]
         }
+    };
+
+    // Let the sessions which lost their subscription know about it
+    for session_id in &replaced {
+        audit_subscription(
+            &session.user_id,
+            session_id,
+            &new_subscription,
+            false,
+            Some(&session.id),
+        )
+        .await;
     }
 
-    session.subscription = Some(new_subscription);
+    let previous = session.subscription.replace(new_subscription);
     session
         .save(authifier)
+        .await
+        .map_err(|_| create_database_error!("save", "session"))?;
+
+    if let Some(previous) = &previous {
+        audit_subscription(&session.user_id, &session.id, previous, false, None).await;
+    }
+
+    if let Some(subscription) = &session.subscription {
+        audit_subscription(&session.user_id, &session.id, subscription, true, None).await;
+    }
+
+    let expires_at = expiration_time.and_then(|ms| {
+        Timestamp::UNIX_EPOCH.checked_add(TimestampDuration::milliseconds(ms as i64))
+    });
+
+    db.set_session_push_expiry(&session.id, expires_at).await?;
+    db.set_session_push_redacted(&session.id, redacted)
         .await
         .map(|_| EmptyResponse)
-        .map_err(|_| create_database_error!("save", "session"))
 }
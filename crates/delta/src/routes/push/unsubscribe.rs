@@ -5,6 +5,8 @@ use rocket_empty::EmptyResponse;
 
 use rocket::State;
 
+use super::audit_subscription;
+
 /// # Unsubscribe
 ///
 /// Remove the Web Push subscription associated with the current session.
@@ -14,10 +16,15 @@ pub async fn unsubscribe(
     authifier: &State<Authifier>,
     mut session: Session,
 ) -> Result<EmptyResponse> {
-    session.subscription = None;
+    let previous = session.subscription.take();
     session
         .save(authifier)
         .await
-        .map(|_| EmptyResponse)
-        .map_err(|_| create_database_error!("save", "session"))
+        .map_err(|_| create_database_error!("save", "session"))?;
+
+    if let Some(previous) = &previous {
+        audit_subscription(&session.user_id, &session.id, previous, false, None).await;
+    }
+
+    Ok(EmptyResponse)
 }
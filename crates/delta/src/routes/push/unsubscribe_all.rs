@@ -0,0 +1,95 @@
+use authifier::models::Session;
+use revolt_database::Database;
+use revolt_result::Result;
+use revolt_rocket_okapi::revolt_okapi::schemars::JsonSchema;
+use rocket::{serde::json::Json, State};
+use serde::Serialize;
+
+use super::audit_subscription;
+
+/// Push subscriptions which were removed
+#[derive(Serialize, JsonSchema)]
+pub struct PushUnsubscribeAllResponse {
+    /// Number of sessions which had their subscription removed
+    pub cleared: usize,
+}
+
+/// # Unsubscribe All
+///
+/// Remove the Web Push subscriptions of all of your sessions, including the current one.
+///
+/// Sessions stay logged in but stop receiving notifications until they subscribe again.
+/// Calling this again when nothing is subscribed does nothing.
+#[openapi(tag = "Web Push")]
+#[post("/unsubscribe-all")]
+pub async fn unsubscribe_all(
+    db: &State<Database>,
+    session: Session,
+) -> Result<Json<PushUnsubscribeAllResponse>> {
+    let sessions = db
+        .fetch_push_subscribed_sessions(&[session.user_id.clone()])
+        .await?;
+
+    if sessions.is_empty() {
+        return Ok(Json(PushUnsubscribeAllResponse { cleared: 0 }));
+    }
+
+    let session_ids: Vec<String> = sessions.iter().map(|session| session.id.clone()).collect();
+    db.remove_push_subscriptions(&session_ids).await?;
+
+    for cleared in &sessions {
+        if let Some(subscription) = &cleared.subscription {
+            audit_subscription(&session.user_id, &cleared.id, subscription, false, None).await;
+        }
+    }
+
+    Ok(Json(PushUnsubscribeAllResponse {
+        cleared: sessions.len(),
+    }))
+}
+
+#[cfg(test)]
+mod test {
+    use rocket::http::{ContentType, Header, Status};
+
+    use crate::{rocket, util::test::TestHarness};
+
+    #[rocket::async_test]
+    async fn clears_every_session_once() {
+        let harness = TestHarness::new().await;
+        let (account, session, _) = harness.new_user().await;
+        let (_, other_session) = harness.account_from_user(account.id.clone()).await;
+
+        for (session, endpoint) in [
+            (&session, "https://push.example.com/phone"),
+            (&other_session, "https://push.example.com/laptop"),
+        ] {
+            let response = harness
+                .client
+                .post("/push/subscribe")
+                .header(Header::new("x-session-token", session.token.to_string()))
+                .header(ContentType::JSON)
+                .body(
+                    json!({ "endpoint": endpoint, "p256dh": "key", "auth": "secret" }).to_string(),
+                )
+                .dispatch()
+                .await;
+
+            assert_eq!(response.status(), Status::NoContent);
+        }
+
+        for cleared in [2, 0] {
+            let response = harness
+                .client
+                .post("/push/unsubscribe-all")
+                .header(Header::new("x-session-token", session.token.to_string()))
+                .dispatch()
+                .await;
+
+            assert_eq!(response.status(), Status::Ok);
+
+            let body: serde_json::Value = response.into_json().await.expect("response");
+            assert_eq!(body["cleared"], cleared);
+        }
+    }
+}
@@ -20,6 +20,7 @@ mod roles_edit;
 mod roles_edit_positions;
 mod roles_fetch;
 mod server_ack;
+mod server_activity;
 mod server_create;
 mod server_delete;
 mod server_edit;
@@ -32,6 +33,7 @@ pub fn routes() -> (Vec<Route>, OpenApi) {
         server_fetch::fetch,
         server_edit::edit,
         server_ack::ack,
+        server_activity::update_activity,
         channel_create::create_server_channel,
         member_fetch_all::fetch_all,
         member_remove::kick,
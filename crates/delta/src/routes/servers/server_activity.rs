@@ -0,0 +1,233 @@
+use std::{
+    collections::HashSet,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use authifier::models::Session;
+use redis_kiss::{redis, AsyncCommands};
+use revolt_config::ApiUsers;
+use revolt_database::{
+    metrics,
+    util::{
+        activity::{
+            channel_viewers_key, extend_session_expiry, open_channels_key, record_last_channel,
+            touch_session, unfocused_session_key,
+        },
+        permissions::DatabasePermissionQuery,
+        reference::Reference,
+        retry::redis_connection,
+    },
+    Channel, Database, User,
+};
+use revolt_permissions::{calculate_channel_permissions, ChannelPermission, PermissionQuery};
+use revolt_result::{create_error, Result};
+use rocket::{serde::json::Json, State};
+use rocket_empty::EmptyResponse;
+
+use crate::routes::channels::channel_activity::{
+    activity_ttl, channel_type_name, is_untracked_channel, redis_error, ChannelActivityRequest,
+    ChannelActivityType,
+};
+
+/// # Update Server Activity
+///
+/// Mark every channel you can view in a server as opened or closed in one go.
+///
+/// Opened channels expire after the same TTL as those opened individually, including
+/// any `ttl_hint`, so clients must keep refreshing the channel being read. Marking a
+/// message as read is not supported here, use the server ack instead.
+#[openapi(tag = "Server Information")]
+#[put("/<target>/activity", data = "<data>")]
+pub async fn update_activity(
+    db: &State<Database>,
+    user: User,
+    session: Session,
+    target: Reference<'_>,
+    data: Json<ChannelActivityRequest>,
+) -> Result<EmptyResponse> {
+    if user.bot.is_some() {
+        return Err(create_error!(IsBot));
+    }
+
+    if data.mark_read_message_id.is_some() {
+        return Err(create_error!(InvalidOperation));
+    }
+
+    let config = revolt_config::config().await;
+    let ttl = activity_ttl(&config.api.users, data.ttl_hint)?;
+
+    let server = target.as_server(db).await?;
+    let mut query = DatabasePermissionQuery::new(db, &user).server(&server);
+    if !query.are_we_a_member().await {
+        return Err(create_error!(NotFound));
+    }
+
+    // Keep track of the primary session even if no channels are tracked
+    if let Err(err) = touch_session(&user.id, &session.id).await {
+        revolt_config::capture_error(&err);
+    }
+
+    let mut channels = vec![];
+    for channel in db.fetch_channels(&server.channels).await? {
+        if is_untracked_channel(&config, &channel) {
+            continue;
+        }
+
+        let mut query = DatabasePermissionQuery::new(db, &user).channel(&channel);
+        if calculate_channel_permissions(&mut query)
+            .await
+            .has_channel_permission(ChannelPermission::ViewChannel)
+        {
+            channels.push(channel);
+        }
+    }
+
+    if channels.is_empty() {
+        return Ok(EmptyResponse);
+    }
+
+    update_server_activity_in_redis(
+        &user.id,
+        &session.id,
+        &channels,
+        &data.activity_type,
+        data.focused.unwrap_or(true),
+        ttl,
+        &config.api.users,
+    )
+    .await?;
+
+    // Remember the first channel of the server, as that's where the user lands
+    if matches!(data.activity_type, ChannelActivityType::Open) {
+        if let Err(err) = record_last_channel(&user.id, channels[0].id()).await {
+            revolt_config::capture_error(&err);
+        }
+    }
+
+    Ok(EmptyResponse)
+}
+
+/// Open or close several channels for a session in a single pipeline
+///
+/// The per-session cap on open channels applies to the channels as a whole.
+/// Each channel opened, closed or evicted is counted in the activity metrics.
+async fn update_server_activity_in_redis(
+    user_id: &str,
+    session_id: &str,
+    channels: &[Channel],
+    activity_type: &ChannelActivityType,
+    focused: bool,
+    ttl: u64,
+    users: &ApiUsers,
+) -> Result<()> {
+    let max_open = users.max_open_channels;
+    let mut conn = redis_connection().await?;
+
+    let channel_ids: Vec<String> = channels
+        .iter()
+        .map(|channel| channel.id().to_string())
+        .collect();
+
+    let session_key = open_channels_key(user_id, session_id);
+    let viewer = format!("{}:{}", user_id, session_id);
+    let mut pipe = redis::pipe();
+    let mut evicted = 0;
+
+    match activity_type {
+        ChannelActivityType::Open => {
+            if max_open > 0 {
+                if channel_ids.len() > max_open {
+                    return Err(create_error!(TooManyOpenChannels { max: max_open }));
+                }
+
+                let open: HashSet<String> = conn
+                    .smembers(&session_key)
+                    .await
+                    .map_err(redis_error("smembers", "open_channels"))?;
+
+                let opening = channel_ids.iter().filter(|id| !open.contains(*id)).count();
+                let overflow = (open.len() + opening).saturating_sub(max_open);
+                if overflow > 0 {
+                    if !users.evict_open_channels {
+                        return Err(create_error!(TooManyOpenChannels { max: max_open }));
+                    }
+
+                    // Close the channels outside this server which were opened longest ago
+                    let mut others = vec![];
+                    for channel_id in open.iter().filter(|id| !channel_ids.contains(*id)) {
+                        let expires_at: Option<u64> = conn
+                            .zscore(channel_viewers_key(channel_id), &viewer)
+                            .await
+                            .map_err(redis_error("zscore", "channel_viewers"))?;
+
+                        others.push((expires_at.unwrap_or_default(), channel_id));
+                    }
+
+                    others.sort();
+                    for (_, channel_id) in others.into_iter().take(overflow) {
+                        pipe.srem(&session_key, channel_id)
+                            .ignore()
+                            .zrem(channel_viewers_key(channel_id), &viewer)
+                            .ignore();
+
+                        evicted += 1;
+                    }
+                }
+            }
+
+            let expires_at = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .expect("Time went backwards")
+                .as_secs()
+                + ttl;
+
+            pipe.sadd(&session_key, &channel_ids).ignore();
+
+            for channel_id in &channel_ids {
+                let viewers_key = channel_viewers_key(channel_id);
+                pipe.zadd(&viewers_key, &viewer, expires_at)
+                    .ignore()
+                    .expire(&viewers_key, ttl.max(users.max_activity_ttl) as usize)
+                    .ignore();
+            }
+
+            let unfocused_key = unfocused_session_key(user_id, session_id);
+            if focused {
+                pipe.del(&unfocused_key).ignore();
+            } else {
+                pipe.set_ex(&unfocused_key, 1, ttl as usize).ignore();
+            }
+        }
+        ChannelActivityType::Close => {
+            pipe.srem(&session_key, &channel_ids).ignore();
+
+            for channel_id in &channel_ids {
+                pipe.zrem(channel_viewers_key(channel_id), &viewer).ignore();
+            }
+        }
+    }
+
+    pipe.query_async::<_, ()>(&mut conn)
+        .await
+        .map_err(redis_error("pipeline", "open_channels"))?;
+
+    // Other channels in the session may have asked for longer, so never shorten it
+    if matches!(activity_type, ChannelActivityType::Open) {
+        extend_session_expiry(&mut conn, &session_key, ttl).await?;
+    }
+
+    for _ in 0..evicted {
+        metrics::record_channel_evicted();
+    }
+
+    for channel in channels {
+        match activity_type {
+            ChannelActivityType::Open => metrics::record_channel_opened(channel_type_name(channel)),
+            ChannelActivityType::Close => {
+                metrics::record_channel_closed(channel_type_name(channel))
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -43,6 +43,7 @@ pub async fn edit(
         && data.flags.is_none()
         && data.analytics.is_none()
         && data.discoverable.is_none()
+        && data.notify_member_joins.is_none()
         && data.remove.is_empty()
     {
         return Ok(Json(server.into()));
@@ -52,6 +53,7 @@ pub async fn edit(
         || data.banner.is_some()
         || data.system_messages.is_some()
         || data.analytics.is_some()
+        || data.notify_member_joins.is_some()
         || !data.remove.is_empty()
     {
         permissions.throw_if_lacking_channel_permission(ChannelPermission::ManageServer)?;
@@ -80,6 +82,7 @@ pub async fn edit(
         // nsfw,
         discoverable,
         analytics,
+        notify_member_joins,
         remove,
     } = data;
 
@@ -92,6 +95,7 @@ pub async fn edit(
         // nsfw,
         discoverable,
         analytics,
+        notify_member_joins,
         ..Default::default()
     };
 
@@ -12,9 +12,11 @@ mod fetch_user;
 mod fetch_user_flags;
 mod find_mutual;
 mod get_default_avatar;
+mod notification_delivery;
 mod open_dm;
 mod remove_friend;
 mod send_friend_request;
+mod snooze_notifications;
 mod unblock_user;
 
 pub fn routes() -> (Vec<Route>, OpenApi) {
@@ -27,6 +29,9 @@ pub fn routes() -> (Vec<Route>, OpenApi) {
         change_username::change_username,
         get_default_avatar::default_avatar,
         fetch_profile::profile,
+        snooze_notifications::snooze,
+        snooze_notifications::unsnooze,
+        notification_delivery::set_delivery,
         // Direct Messaging
         fetch_dms::direct_messages,
         open_dm::open_dm,
@@ -0,0 +1,25 @@
+use revolt_database::{Database, User};
+use revolt_models::v0;
+use revolt_result::{create_error, Result};
+use rocket::{serde::json::Json, State};
+use rocket_empty::EmptyResponse;
+
+/// # Set Notification Delivery
+///
+/// Choose whether push notifications are delivered to all of your sessions
+/// or only to the one you were most recently active on.
+#[openapi(tag = "User Information")]
+#[put("/@me/notifications/delivery", data = "<data>")]
+pub async fn set_delivery(
+    db: &State<Database>,
+    mut user: User,
+    data: Json<v0::DataNotificationDelivery>,
+) -> Result<EmptyResponse> {
+    if user.bot.is_some() {
+        return Err(create_error!(IsBot));
+    }
+
+    user.set_push_primary_session_only(db, data.into_inner().primary_session_only)
+        .await
+        .map(|_| EmptyResponse)
+}
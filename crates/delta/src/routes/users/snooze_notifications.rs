@@ -0,0 +1,37 @@
+use revolt_database::{Database, User};
+use revolt_models::v0;
+use revolt_result::{create_error, Result};
+use rocket::{serde::json::Json, State};
+use rocket_empty::EmptyResponse;
+
+/// # Snooze Notifications
+///
+/// Stop receiving all push notifications until the given time.
+#[openapi(tag = "User Information")]
+#[put("/@me/notifications/snooze", data = "<data>")]
+pub async fn snooze(
+    db: &State<Database>,
+    mut user: User,
+    data: Json<v0::DataSnoozeNotifications>,
+) -> Result<EmptyResponse> {
+    if user.bot.is_some() {
+        return Err(create_error!(IsBot));
+    }
+
+    user.snooze_notifications(db, data.into_inner().until)
+        .await
+        .map(|_| EmptyResponse)
+}
+
+/// # Unsnooze Notifications
+///
+/// Resume receiving push notifications.
+#[openapi(tag = "User Information")]
+#[delete("/@me/notifications/snooze")]
+pub async fn unsnooze(db: &State<Database>, mut user: User) -> Result<EmptyResponse> {
+    if user.bot.is_some() {
+        return Err(create_error!(IsBot));
+    }
+
+    user.unsnooze_notifications(db).await.map(|_| EmptyResponse)
+}
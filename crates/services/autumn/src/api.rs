@@ -1,10 +1,10 @@
 use std::{
     io::{Cursor, Read},
-    time::Duration,
+    time::{Duration, SystemTime, UNIX_EPOCH},
 };
 
 use axum::{
-    extract::{DefaultBodyLimit, Path, State},
+    extract::{DefaultBodyLimit, Path, Query, State},
     http::{header, Method},
     response::{IntoResponse, Redirect, Response},
     routing::{get, post},
@@ -15,7 +15,8 @@ use lazy_static::lazy_static;
 use revolt_config::{config, report_internal_error};
 use revolt_database::{iso8601_timestamp::Timestamp, Database, FileHash, Metadata, User};
 use revolt_files::{
-    create_thumbnail, decode_image, fetch_from_s3, upload_to_s3, AUTHENTICATION_TAG_SIZE_BYTES,
+    create_thumbnail, decode_image, fetch_from_s3, upload_to_s3, verify_url_signature,
+    AUTHENTICATION_TAG_SIZE_BYTES,
 };
 use revolt_result::{create_error, Error, Result};
 use serde::{Deserialize, Serialize};
@@ -406,11 +407,20 @@ async fn fetch_preview(
         .into_response())
 }
 
+/// Signature of a short-lived download link
+#[derive(Deserialize)]
+struct SignedLink {
+    expires: Option<u64>,
+    signature: Option<String>,
+}
+
 /// Fetch original file
 ///
 /// Content disposition header will be set to 'attachment' to prevent browser from rendering anything.
 ///
 /// Using `original` as the file name parameter will redirect you to the original file.
+///
+/// Signed download links carry `expires` and `signature` parameters, which are rejected once expired.
 #[utoipa::path(
     get,
     path = "/{tag}/{file_id}/{file_name}",
@@ -426,8 +436,29 @@ async fn fetch_preview(
 async fn fetch_file(
     State(db): State<Database>,
     Path((tag, file_id, file_name)): Path<(Tag, String, String)>,
+    Query(link): Query<SignedLink>,
 ) -> Result<Response> {
     let tag: &'static str = tag.clone().into();
+
+    // Check signed links are still valid
+    if link.expires.is_some() || link.signature.is_some() {
+        let config = config().await;
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("time went backwards")
+            .as_secs();
+
+        if !verify_url_signature(
+            &config.files.signed_url_key,
+            tag,
+            &file_id,
+            link.expires.unwrap_or_default(),
+            link.signature.as_deref().unwrap_or_default(),
+            now,
+        ) {
+            return Err(create_error!(InvalidCredentials));
+        }
+    }
     let file = db.fetch_attachment(tag, &file_id).await?;
 
     // Ignore deleted files